@@ -1,59 +1,73 @@
-// use std::process;
+use std::io::{BufReader, BufWriter};
+use std::process;
 
 use clap::Parser;
-// use dirs::home_dir;
-use eyre::Result;
 
-use palmtop_telemetry::{self, metrics};
+use palmtop_client::program::{self, EXIT_ORACLE};
+use palmtop_preimage::client::OracleClientImpl;
+use palmtop_preimage::fds;
+use palmtop_preimage::hints::HintWriter;
 
-// use serde::Serialize;
-
-#[tokio::main]
-async fn main() -> Result<()> {
+/// Runs the program against the oracle channels inherited from the host and exits
+/// with the code of its verdict: 0 if the claim is valid, 1 if it is invalid and
+/// one of the [program] failure codes if it failed before reaching a verdict.
+///
+/// Only claims at the agreed L2 block reach a verdict for now. Later claims exit
+/// with [program::EXIT_UNSUPPORTED], see [program::run].
+fn main() {
     let cli = Cli::parse();
-    let logs_dir = cli.logs_dir.clone();
-    let logs_rotation = cli.logs_rotation.clone();
-    let verbose = cli.verbose;
-
-    let _guards = palmtop_telemetry::init(verbose, logs_dir, logs_rotation);
-    metrics::init()?;
+    let _guards = palmtop_telemetry::init(cli.verbose, cli.logs_dir, cli.logs_rotation);
 
-    tracing::info!(target: "palmtop", "Starting Magi...");
+    tracing::info!(target: "palmtop", "Starting palmtop client...");
 
-    // let runner = Runner::from_config(config)
-    //     .with_sync_mode(sync_mode)
-    //     .with_checkpoint_hash(checkpoint_hash);
-    //
-    // if let Err(err) = runner.run().await {
-    //     tracing::error!(target: "magi", "{}", err);
-    //     process::exit(1);
-    // }
+    for fd in [
+        fds::HINT_CLIENT_READ_FD,
+        fds::HINT_CLIENT_WRITE_FD,
+        fds::PREIMAGE_CLIENT_READ_FD,
+        fds::PREIMAGE_CLIENT_WRITE_FD,
+    ] {
+        // Safety: fcntl with F_GETFD only queries the descriptor.
+        if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+            tracing::error!(target: "palmtop", "Oracle channel fd {} is not open", fd);
+            drop(_guards);
+            process::exit(EXIT_ORACLE);
+        }
+    }
+    // Safety: the oracle channels are inherited from the host and are not opened
+    // anywhere else.
+    let ((hint_read, hint_write), (preimage_read, preimage_write)) =
+        unsafe { (fds::hint_channel(), fds::preimage_channel()) };
+    let mut oracle = OracleClientImpl::new(
+        BufReader::new(preimage_read),
+        BufWriter::new(preimage_write),
+    );
+    let mut hinter = HintWriter::new(BufReader::new(hint_read), BufWriter::new(hint_write));
 
-    Ok(())
+    let result = program::run(&mut oracle, &mut hinter);
+    let code = program::exit_code(&result);
+    match result {
+        Ok(true) => tracing::info!(target: "palmtop", "Claim is valid"),
+        Ok(false) => tracing::info!(target: "palmtop", "Claim is invalid"),
+        Err(err) => tracing::error!(target: "palmtop", "Program failed: {:#}", err),
+    }
+    drop(_guards);
+    process::exit(code);
 }
 
+/// The palmtop fault proof program, run by the host with its oracle channels.
 #[derive(Parser)]
+#[clap(
+    after_help = "The program cannot dispute real claims yet. Only a claim at the agreed L2 \
+                     block reaches a verdict: claims about later blocks need L2 blocks to be \
+                     derived and executed, which is not supported yet, and exit with code 5."
+)]
 pub struct Cli {
-    #[clap(short, long, default_value = "optimism")]
-    network: String,
-    #[clap(long)]
-    l1_rpc_url: Option<String>,
-    #[clap(long)]
-    l2_rpc_url: Option<String>,
-    #[clap(long)]
-    l2_engine_url: Option<String>,
-    #[clap(long)]
-    jwt_secret: Option<String>,
     #[clap(short = 'v', long)]
     verbose: bool,
-    #[clap(short = 'p', long)]
-    rpc_port: Option<u16>,
+    /// Directory log files are written to, if any.
     #[clap(long)]
     logs_dir: Option<String>,
+    /// The rotation strategy of log files.
     #[clap(long)]
     logs_rotation: Option<String>,
-    #[clap(long)]
-    checkpoint_hash: Option<String>,
-    #[clap(long)]
-    checkpoint_sync_url: Option<String>,
 }
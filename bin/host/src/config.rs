@@ -0,0 +1,262 @@
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use eyre::Result;
+use figment::providers::{Env, Format, Serialized, Toml};
+use figment::Figment;
+use palmtop_primitives::rollup::{RollupConfig, PRESETS};
+use serde::{Deserialize, Serialize};
+
+/// The prefix of environment variables overriding the config file.
+const ENV_PREFIX: &str = "PALMTOP_";
+
+/// The log rotation strategies the telemetry crate supports.
+const LOG_ROTATIONS: [&str; 4] = ["never", "daily", "hourly", "minutely"];
+
+/// ## Config
+///
+/// The effective configuration of the host, layered from lowest to highest
+/// precedence: built-in defaults, the `~/.palmtop/palmtop.toml` config file,
+/// `PALMTOP_`-prefixed environment variables and command line flags.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Config {
+    /// The network whose rollup config is served: a preset name or a path to
+    /// an op-node JSON rollup config.
+    pub network: String,
+    /// The L1 execution RPC endpoint.
+    pub l1_rpc_url: Option<String>,
+    /// The L1 beacon node API endpoint.
+    pub l1_beacon_url: Option<String>,
+    /// The L2 execution RPC endpoint.
+    pub l2_rpc_url: Option<String>,
+    /// Directory of the preimage store shared between runs.
+    pub datadir: Option<PathBuf>,
+    /// Directory log files are written to, if any.
+    pub logs_dir: Option<String>,
+    /// The rotation strategy of log files.
+    pub logs_rotation: Option<String>,
+    /// Whether to log verbosely.
+    pub verbose: bool,
+    /// The address metrics are served on.
+    pub metrics_addr: SocketAddr,
+    /// Path to the JSON rollup config served as boot info, overriding the network.
+    pub rollup_config: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            network: "optimism".into(),
+            l1_rpc_url: None,
+            l1_beacon_url: None,
+            l2_rpc_url: None,
+            datadir: None,
+            logs_dir: None,
+            logs_rotation: None,
+            verbose: false,
+            metrics_addr: SocketAddr::from(([0, 0, 0, 0], 9200)),
+            rollup_config: None,
+        }
+    }
+}
+
+/// ## CliConfig
+///
+/// The config values given as command line flags. Unset flags are skipped when
+/// serialized so they do not override the lower layers.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CliConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_rpc_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_beacon_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l2_rpc_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub datadir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logs_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logs_rotation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verbose: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics_addr: Option<SocketAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rollup_config: Option<PathBuf>,
+}
+
+impl Config {
+    /// Returns the path of the default config file, `~/.palmtop/palmtop.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".palmtop").join("palmtop.toml"))
+    }
+
+    /// Loads the effective config from the given config file, the environment
+    /// and the command line, and validates it.
+    ///
+    /// A missing config file is skipped.
+    pub fn load(path: Option<&Path>, cli: CliConfig) -> Result<Self> {
+        let mut figment = Figment::from(Serialized::defaults(Config::default()));
+        if let Some(path) = path {
+            figment = figment.merge(Toml::file(path));
+        }
+        let config: Config = figment
+            .merge(Env::prefixed(ENV_PREFIX))
+            .merge(Serialized::defaults(cli))
+            .extract()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks that the config values are usable.
+    pub fn validate(&self) -> Result<()> {
+        for (name, url) in [
+            ("l1_rpc_url", &self.l1_rpc_url),
+            ("l1_beacon_url", &self.l1_beacon_url),
+            ("l2_rpc_url", &self.l2_rpc_url),
+        ] {
+            if let Some(url) = url {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    eyre::bail!("{} must be an http(s) URL, got {}", name, url);
+                }
+            }
+        }
+        if let Some(rotation) = &self.logs_rotation {
+            if !LOG_ROTATIONS.contains(&rotation.as_str()) {
+                eyre::bail!(
+                    "logs_rotation must be one of {}, got {}",
+                    LOG_ROTATIONS.join(", "),
+                    rotation
+                );
+            }
+        }
+        if let Some(path) = &self.rollup_config {
+            if !path.is_file() {
+                eyre::bail!("rollup_config {} is not a file", path.display());
+            }
+        } else if !self.network.ends_with(".json") && !PRESETS.contains(&self.network.as_str()) {
+            eyre::bail!(
+                "network must be one of {} or a path to a JSON rollup config, got {}",
+                PRESETS.join(", "),
+                self.network
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the rollup config of the configured network, preferring the
+    /// rollup config file if one is set.
+    pub fn rollup_config(&self) -> Result<RollupConfig> {
+        let path = match &self.rollup_config {
+            Some(path) => path.clone(),
+            None if self.network.ends_with(".json") => PathBuf::from(&self.network),
+            None => {
+                return RollupConfig::preset(&self.network)
+                    .ok_or_else(|| eyre::eyre!("unknown network {}", self.network))
+            }
+        };
+        let json = std::fs::read(&path)
+            .map_err(|e| eyre::eyre!("failed to read rollup config {}: {}", path.display(), e))?;
+        RollupConfig::from_json(&json)
+    }
+
+    /// Returns the config as TOML.
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+// The jail closures return figment's own error type.
+#[allow(clippy::result_large_err)]
+mod tests {
+    use super::*;
+    use figment::Jail;
+
+    #[test]
+    fn test_config_layering() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "palmtop.toml",
+                r#"
+                    l1_rpc_url = "http://file-l1"
+                    l2_rpc_url = "http://file-l2"
+                    logs_rotation = "hourly"
+                "#,
+            )?;
+            jail.set_env("PALMTOP_L2_RPC_URL", "http://env-l2");
+            jail.set_env("PALMTOP_METRICS_ADDR", "127.0.0.1:9300");
+            let cli = CliConfig {
+                l1_rpc_url: Some("http://cli-l1".into()),
+                ..Default::default()
+            };
+            let config = Config::load(Some(Path::new("palmtop.toml")), cli).unwrap();
+            assert_eq!(config.network, "optimism");
+            assert_eq!(config.l1_rpc_url.as_deref(), Some("http://cli-l1"));
+            assert_eq!(config.l2_rpc_url.as_deref(), Some("http://env-l2"));
+            assert_eq!(config.logs_rotation.as_deref(), Some("hourly"));
+            assert_eq!(config.metrics_addr, "127.0.0.1:9300".parse().unwrap());
+            assert!(!config.verbose);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_config_defaults_without_file() {
+        Jail::expect_with(|_| {
+            let config = Config::load(Some(Path::new("missing.toml")), CliConfig::default());
+            assert_eq!(config.unwrap(), Config::default());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_config_custom_rollup_config() {
+        Jail::expect_with(|jail| {
+            let base = RollupConfig::preset("base").unwrap();
+            jail.create_binary("rollup.json", &base.to_json())?;
+            jail.set_env("PALMTOP_NETWORK", "rollup.json");
+            let config = Config::load(None, CliConfig::default()).unwrap();
+            assert_eq!(config.rollup_config().unwrap(), base);
+
+            let cli = CliConfig {
+                network: Some("base-sepolia".into()),
+                ..Default::default()
+            };
+            let config = Config::load(None, cli).unwrap();
+            assert_eq!(config.rollup_config().unwrap().l2_chain_id, 84532);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let invalid = [
+            Config {
+                l1_rpc_url: Some("localhost:8545".into()),
+                ..Default::default()
+            },
+            Config {
+                logs_rotation: Some("weekly".into()),
+                ..Default::default()
+            },
+            Config {
+                rollup_config: Some("missing.json".into()),
+                ..Default::default()
+            },
+            Config {
+                network: "optimism-goerli".into(),
+                ..Default::default()
+            },
+        ];
+        for config in invalid {
+            assert!(config.validate().is_err());
+        }
+        assert_eq!(Config::default().rollup_config().unwrap().l2_chain_id, 10);
+        let toml = Config::default().to_toml().unwrap();
+        assert!(toml.contains("metrics_addr = \"0.0.0.0:9200\""));
+    }
+}
@@ -1,43 +1,257 @@
-// use std::process;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
 
+use alloy_primitives::B256;
 use clap::Parser;
-// use dirs::home_dir;
 use eyre::Result;
 
+use palmtop_client::program;
+use palmtop_host::{
+    open_bundle_storage, open_offline_storage, open_storage, server::ignore_hint, BootInfoSource,
+    Fetcher,
+};
+use palmtop_kv::{BundleCompression, RecordingKeyValueStore, SplitKeyValueStore};
+#[cfg(unix)]
+use palmtop_preimage::fds;
+use palmtop_preimage::hints::HintHandler;
+use palmtop_primitives::RollupConfig;
 use palmtop_telemetry::{self, metrics};
 
-// use serde::Serialize;
+use crate::config::{CliConfig, Config};
+
+/// Layered host configuration.
+pub mod config;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
-    let logs_dir = cli.logs_dir.clone();
-    let logs_rotation = cli.logs_rotation.clone();
-    let verbose = cli.verbose;
+    let config_path = cli.config.clone().or_else(Config::default_path);
+    let config = Config::load(config_path.as_deref(), cli.cli_config())?;
+    if cli.print_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
+    if cli.command.is_none() && !cli.has_mode() {
+        eyre::bail!("no mode given: pass one of --exec, --native, --server, --offline or --bundle");
+    }
+    let offline = cli.offline || cli.bundle.is_some();
+    if cli.offline && config.datadir.is_none() {
+        eyre::bail!("offline mode requires a datadir");
+    }
+
+    let _guards = palmtop_telemetry::init(
+        config.verbose,
+        config.logs_dir.clone(),
+        config.logs_rotation.clone(),
+    );
+    metrics::init(config.metrics_addr)?;
+
+    tracing::info!(target: "palmtop", "Starting palmtop host...");
+    if offline {
+        // Endpoint flags conflict with the offline modes, but endpoints from the
+        // config file or the environment are only ignored.
+        for endpoint in configured_endpoints(&config) {
+            tracing::warn!(target: "palmtop", "Ignoring the configured {} offline", endpoint);
+        }
+    }
+
+    if let Some(HostCommand::Bundle { out, compression }) = &cli.command {
+        let Some(datadir) = &config.datadir else {
+            eyre::bail!("exporting a bundle requires a datadir");
+        };
+        let count = palmtop_host::export_bundle(datadir, out, *compression)?;
+        tracing::info!(target: "palmtop", "Exported {} preimages to {}", count, out.display());
+        return Ok(());
+    }
+
+    let boot_info = cli.boot_info(&config)?;
+    tracing::info!(target: "palmtop", "Configured boot keys: {:?}", boot_info.configured());
+    let storage = Arc::new(match (&cli.bundle, &config.datadir) {
+        (Some(bundle), _) => open_bundle_storage(bundle, &boot_info)?,
+        (None, Some(datadir)) if cli.offline => open_offline_storage(datadir, &boot_info)?,
+        (None, datadir) => open_storage(datadir.as_deref(), &boot_info)?,
+    });
+
+    if let Some(HostCommand::Prefetch { report, witness }) = &cli.command {
+        return prefetch(&cli, &config, storage, report.clone(), witness.as_deref());
+    }
+
+    let router = match offline {
+        true => {
+            let source = cli.bundle.as_ref().or(config.datadir.as_ref());
+            tracing::info!(target: "palmtop", "Serving preimages offline from {:?}", source);
+            Arc::new(ignore_hint)
+        }
+        false => cli.fetcher(&config, storage.clone()).into_handler(),
+    };
+
+    if let Some(client) = &cli.exec {
+        let code = exec(client, &cli.client_args, storage, router)?;
+        if code != 0 {
+            tracing::error!(target: "palmtop", "Client exited with code {}", code);
+            drop(_guards);
+            process::exit(code);
+        }
+    } else if cli.native {
+        let result = palmtop_host::run_native(program::run, storage, router);
+        let code = program::exit_code(&result);
+        match result {
+            Ok(valid) => tracing::info!(target: "palmtop", "Claim is valid: {}", valid),
+            Err(e) => tracing::error!(target: "palmtop", "Client program failed: {:#}", e),
+        }
+        if code != 0 {
+            drop(_guards);
+            process::exit(code);
+        }
+    } else {
+        serve_inherited(storage, router)?;
+    }
+
+    Ok(())
+}
+
+/// Spawns the client program with the oracle channels and serves it until it
+/// exits, returning its exit code.
+#[cfg(unix)]
+fn exec(
+    client: &Path,
+    args: &[String],
+    storage: Arc<SplitKeyValueStore>,
+    router: Arc<HintHandler>,
+) -> Result<i32> {
+    use std::os::unix::process::ExitStatusExt;
 
-    let _guards = palmtop_telemetry::init(verbose, logs_dir, logs_rotation);
-    metrics::init()?;
+    let mut command = std::process::Command::new(client);
+    command.args(args);
+    let status = palmtop_host::run_client(&mut command, storage, router)?;
+    // A client killed by a signal exits like it would from a shell.
+    Ok(status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or_default()))
+}
+
+/// Fails, as the client program is spawned with the oracle channels on unix
+/// file descriptors.
+#[cfg(not(unix))]
+fn exec(
+    _client: &Path,
+    _args: &[String],
+    _storage: Arc<SplitKeyValueStore>,
+    _router: Arc<HintHandler>,
+) -> Result<i32> {
+    eyre::bail!("--exec is only supported on unix")
+}
+
+/// Serves the oracle channels on the file descriptors inherited from the process
+/// that launched the host.
+#[cfg(unix)]
+fn serve_inherited(storage: Arc<SplitKeyValueStore>, router: Arc<HintHandler>) -> Result<()> {
+    // Safety: the oracle channels are inherited from the process that launched
+    // the host and are not opened anywhere else.
+    let (hint_channel, preimage_channel) =
+        unsafe { (fds::hint_channel(), fds::preimage_channel()) };
+    palmtop_host::serve(hint_channel, preimage_channel, storage, router)
+}
+
+/// Fails, as the inherited oracle channels are unix file descriptors.
+#[cfg(not(unix))]
+fn serve_inherited(_storage: Arc<SplitKeyValueStore>, _router: Arc<HintHandler>) -> Result<()> {
+    eyre::bail!("serving inherited oracle channels is only supported on unix")
+}
+
+/// Returns the names of the RPC endpoints set in the config.
+fn configured_endpoints(config: &Config) -> Vec<&'static str> {
+    [
+        ("l1_rpc_url", &config.l1_rpc_url),
+        ("l1_beacon_url", &config.l1_beacon_url),
+        ("l2_rpc_url", &config.l2_rpc_url),
+    ]
+    .into_iter()
+    .filter_map(|(name, url)| url.as_ref().map(|_| name))
+    .collect()
+}
 
-    tracing::info!(target: "palmtop", "Starting Magi...");
+/// The file the prefetch report is written to in the datadir by default.
+const PREFETCH_REPORT: &str = "prefetch.json";
 
-    // let runner = Runner::from_config(config)
-    //     .with_sync_mode(sync_mode)
-    //     .with_checkpoint_hash(checkpoint_hash);
-    //
-    // if let Err(err) = runner.run().await {
-    //     tracing::error!(target: "magi", "{}", err);
-    //     process::exit(1);
-    // }
+/// Runs the client program in-process against the online host, persisting every
+/// fetched preimage to the datadir, and reports the preimages the program read.
+fn prefetch(
+    cli: &Cli,
+    config: &Config,
+    storage: Arc<SplitKeyValueStore>,
+    report: Option<PathBuf>,
+    witness: Option<&Path>,
+) -> Result<()> {
+    let Some(datadir) = config
+        .datadir
+        .as_ref()
+        .filter(|_| !cli.offline && cli.bundle.is_none())
+    else {
+        eyre::bail!("prefetch requires a datadir and RPC endpoints");
+    };
+    let router = cli.fetcher(config, storage.clone()).into_handler();
+    // Only the oracle server reads through the recording store, so the report
+    // holds the keys the program read rather than those the fetcher checked.
+    let recording = Arc::new(RecordingKeyValueStore::new(storage.clone()));
+    let valid = palmtop_host::run_native(program::run, recording.clone(), router)?;
 
+    let read = recording.report();
+    tracing::info!(
+        target: "palmtop",
+        "Prefetched {} preimages ({} bytes) for a run finding the claim {}",
+        read.count,
+        read.bytes,
+        if valid { "valid" } else { "invalid" }
+    );
+    let report = report.unwrap_or_else(|| datadir.join(PREFETCH_REPORT));
+    read.write(&report)?;
+    tracing::info!(target: "palmtop", "Wrote the keys read to {}", report.display());
+    if let Some(witness) = witness {
+        palmtop_host::write_witness(storage.as_ref(), &read.keys, witness)?;
+    }
     Ok(())
 }
 
+#[derive(clap::Subcommand)]
+pub enum HostCommand {
+    /// Run the client program against the online host and persist every preimage
+    /// it needs to the datadir, for a later offline run.
+    Prefetch {
+        /// Path to write the JSON report of the keys read to, `prefetch.json` in
+        /// the datadir by default.
+        #[clap(long)]
+        report: Option<PathBuf>,
+        /// Directory to write a witness store to, holding only the preimages the
+        /// program read, for serving an offline run.
+        #[clap(long)]
+        witness: Option<PathBuf>,
+    },
+    /// Export every preimage of the datadir to a witness bundle.
+    Bundle {
+        /// Path of the bundle file to write.
+        out: PathBuf,
+        /// The compression of the bundle: zlib or brotli.
+        #[clap(long, default_value = "zlib")]
+        compression: BundleCompression,
+    },
+}
+
 #[derive(Parser)]
 pub struct Cli {
-    #[clap(short, long, default_value = "optimism")]
-    network: String,
+    #[clap(subcommand)]
+    command: Option<HostCommand>,
+    /// The network to serve the rollup config of: a preset name or a path to a
+    /// JSON rollup config.
+    #[clap(short, long)]
+    network: Option<String>,
     #[clap(long)]
     l1_rpc_url: Option<String>,
+    /// The L1 beacon node API endpoint blobs are fetched from.
+    #[clap(long)]
+    l1_beacon_url: Option<String>,
     #[clap(long)]
     l2_rpc_url: Option<String>,
     #[clap(long)]
@@ -46,6 +260,15 @@ pub struct Cli {
     jwt_secret: Option<String>,
     #[clap(short = 'v', long)]
     verbose: bool,
+    /// Path to the config file, `~/.palmtop/palmtop.toml` by default.
+    #[clap(long)]
+    config: Option<PathBuf>,
+    /// Print the effective config and exit.
+    #[clap(long)]
+    print_config: bool,
+    /// The address metrics are served on.
+    #[clap(long)]
+    metrics_addr: Option<SocketAddr>,
     #[clap(short = 'p', long)]
     rpc_port: Option<u16>,
     #[clap(long)]
@@ -56,39 +279,202 @@ pub struct Cli {
     checkpoint_hash: Option<String>,
     #[clap(long)]
     checkpoint_sync_url: Option<String>,
+    /// Directory of the preimage store shared between runs.
+    #[clap(long)]
+    datadir: Option<PathBuf>,
+    /// Serve the oracle channels on the inherited file descriptors.
+    #[clap(long, conflicts_with = "exec")]
+    server: bool,
+    /// Spawn the client program with the oracle channels and serve it until it
+    /// exits, exiting with its exit code.
+    #[clap(long)]
+    exec: Option<PathBuf>,
+    /// Run the client program in-process instead of spawning it, exiting with the
+    /// exit code it would have.
+    #[clap(long, conflicts_with_all = ["exec", "server"])]
+    native: bool,
+    /// Arguments passed to the client program, after `--`.
+    #[clap(last = true, requires = "exec")]
+    client_args: Vec<String>,
+    /// Serve the oracle channels only from the datadir, without any RPC endpoints.
+    #[clap(long, conflicts_with_all = ["l1_rpc_url", "l1_beacon_url", "l2_rpc_url"])]
+    offline: bool,
+    /// Serve the oracle channels only from a witness bundle, without any RPC
+    /// endpoints or datadir.
+    #[clap(long, conflicts_with_all = ["datadir", "offline", "l1_rpc_url", "l1_beacon_url", "l2_rpc_url"])]
+    bundle: Option<PathBuf>,
+    /// The L1 head block hash served as boot info.
+    #[clap(long)]
+    l1_head: Option<B256>,
+    /// The hash of the agreed upon L2 head block, whose output is fetched on hints.
+    #[clap(long)]
+    l2_head: Option<B256>,
+    /// The agreed upon L2 output root served as boot info.
+    #[clap(long)]
+    l2_output_root: Option<B256>,
+    /// The disputed L2 output root claim served as boot info.
+    #[clap(long)]
+    l2_claim: Option<B256>,
+    /// The L2 block number of the disputed claim served as boot info.
+    #[clap(long)]
+    l2_block_number: Option<u64>,
+    /// The L2 chain ID served as boot info.
+    #[clap(long)]
+    l2_chain_id: Option<u64>,
+    /// Path to the JSON L2 chain config served as boot info.
+    #[clap(long)]
+    l2_chain_config: Option<PathBuf>,
+    /// Path to the JSON rollup config served as boot info, overriding the network.
+    #[clap(long)]
+    rollup_config: Option<PathBuf>,
+}
+
+impl Cli {
+    /// Returns true if a mode running or serving the client program is given.
+    pub fn has_mode(&self) -> bool {
+        self.exec.is_some() || self.native || self.server || self.offline || self.bundle.is_some()
+    }
+
+    /// Builds the [Fetcher] preparing preimages from the configured endpoints.
+    pub fn fetcher(&self, config: &Config, storage: Arc<SplitKeyValueStore>) -> Fetcher {
+        let mut fetcher = Fetcher::new(storage);
+        if let Some(url) = &config.l1_rpc_url {
+            fetcher = fetcher.with_l1_rpc(url);
+        }
+        if let Some(url) = &config.l1_beacon_url {
+            fetcher = fetcher.with_l1_beacon(url);
+        }
+        if let Some(url) = &config.l2_rpc_url {
+            fetcher = fetcher.with_l2_rpc(url);
+        }
+        if let Some(hash) = self.l2_head {
+            fetcher = fetcher.with_l2_head(hash);
+        }
+        fetcher
+    }
+
+    /// Returns the config values set by flags.
+    pub fn cli_config(&self) -> CliConfig {
+        CliConfig {
+            network: self.network.clone(),
+            l1_rpc_url: self.l1_rpc_url.clone(),
+            l1_beacon_url: self.l1_beacon_url.clone(),
+            l2_rpc_url: self.l2_rpc_url.clone(),
+            datadir: self.datadir.clone(),
+            logs_dir: self.logs_dir.clone(),
+            logs_rotation: self.logs_rotation.clone(),
+            verbose: self.verbose.then_some(true),
+            metrics_addr: self.metrics_addr,
+            rollup_config: self.rollup_config.clone(),
+        }
+    }
+
+    /// Builds the [BootInfoSource] from the boot info flags and the config.
+    pub fn boot_info(&self, config: &Config) -> Result<BootInfoSource> {
+        let rollup_config = config.rollup_config()?;
+        let l2_chain_id = self.l2_chain_id.unwrap_or(rollup_config.l2_chain_id);
+        if l2_chain_id != rollup_config.l2_chain_id {
+            eyre::bail!(
+                "L2 chain ID {} does not match the rollup config's {}",
+                l2_chain_id,
+                rollup_config.l2_chain_id
+            );
+        }
+        // Like op-program, the client only loads the L2 chain config of custom chains.
+        if self.l2_chain_config.is_none() && !RollupConfig::is_preset_chain(l2_chain_id) {
+            eyre::bail!(
+                "the custom L2 chain {} requires --l2-chain-config",
+                l2_chain_id
+            );
+        }
+        let read = |path: &Option<PathBuf>| path.as_ref().map(std::fs::read).transpose();
+        Ok(BootInfoSource {
+            l1_head: self.l1_head,
+            l2_output_root: self.l2_output_root,
+            l2_claim: self.l2_claim,
+            l2_claim_block_number: self.l2_block_number,
+            l2_chain_id: Some(l2_chain_id),
+            l2_chain_config: read(&self.l2_chain_config)?,
+            rollup_config: Some(rollup_config.to_json()),
+        })
+    }
 }
 
-// impl Cli {
-//     pub fn to_config(self) -> Config {
-//         let chain = match self.network.as_str() {
-//             "optimism" => ChainConfig::optimism(),
-//             "optimism-goerli" => ChainConfig::optimism_goerli(),
-//             "optimism-sepolia" => ChainConfig::optimism_sepolia(),
-//             "base" => ChainConfig::base(),
-//             "base-goerli" => ChainConfig::base_goerli(),
-//             file if file.ends_with(".json") => ChainConfig::from_json(file),
-//             _ => panic!(
-//                 "Invalid network name. \\
-//                 Please use one of the following: 'optimism', 'optimism-goerli', 'base-goerli'. \\
-//                 You can also use a JSON file path for custom configuration."
-//             ),
-//         };
-//
-//         let config_path = home_dir().unwrap().join(".magi/magi.toml");
-//         let cli_config = CliConfig::from(self);
-//         Config::new(&config_path, cli_config, chain)
-//     }
-// }
-//
-// impl From<Cli> for CliConfig {
-//     fn from(value: Cli) -> Self {
-//         Self {
-//             l1_rpc_url: value.l1_rpc_url,
-//             l2_rpc_url: value.l2_rpc_url,
-//             l2_engine_url: value.l2_engine_url,
-//             jwt_secret: value.jwt_secret,
-//             checkpoint_sync_url: value.checkpoint_sync_url,
-//             rpc_port: value.rpc_port,
-//         }
-//     }
-// }
+#[cfg(test)]
+#[allow(clippy::result_large_err)]
+mod tests {
+    use super::*;
+    use figment::Jail;
+    use palmtop_client::BootInfo;
+
+    #[test]
+    fn test_host_requires_a_mode() {
+        assert!(!Cli::parse_from(["palmtop-host"]).has_mode());
+        for mode in ["--native", "--server", "--offline"] {
+            assert!(Cli::parse_from(["palmtop-host", mode]).has_mode(), "{mode}");
+        }
+        assert!(Cli::parse_from(["palmtop-host", "--exec", "client"]).has_mode());
+    }
+
+    #[test]
+    fn test_offline_ignores_configured_endpoints() {
+        Jail::expect_with(|jail| {
+            jail.set_env("PALMTOP_L1_RPC_URL", "http://env-l1");
+            let cli = Cli::parse_from(["palmtop-host", "--offline", "--datadir", "data"]);
+            let config = Config::load(None, cli.cli_config()).unwrap();
+            assert_eq!(configured_endpoints(&config), ["l1_rpc_url"]);
+
+            for args in [
+                ["--offline", "--l1-rpc-url"],
+                ["--bundle=witness", "--l2-rpc-url"],
+            ] {
+                let flags = ["palmtop-host", args[0], args[1], "http://flag"];
+                assert!(Cli::try_parse_from(flags).is_err(), "{args:?}");
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_boot_info_from_preset() {
+        Jail::expect_with(|jail| {
+            let hash = |byte: u8| B256::repeat_byte(byte).to_string();
+            let cli = Cli::parse_from([
+                "palmtop-host",
+                "--network",
+                "optimism",
+                "--l1-head",
+                &hash(1),
+                "--l2-output-root",
+                &hash(2),
+                "--l2-claim",
+                &hash(3),
+                "--l2-block-number",
+                "100",
+            ]);
+            let config = Config::load(None, cli.cli_config()).unwrap();
+            let boot_info = cli.boot_info(&config).unwrap();
+            let storage = Arc::new(open_storage(None, &boot_info).unwrap());
+            let loaded = palmtop_host::run_native(
+                |oracle, _| BootInfo::load(oracle),
+                storage,
+                Arc::new(ignore_hint),
+            )
+            .unwrap();
+            assert_eq!(loaded.l2_chain_id(), 10);
+            assert_eq!(loaded.l2_claim(), B256::repeat_byte(3));
+            assert_eq!(loaded.l2_chain_config(), None);
+
+            let custom = Cli::parse_from(["palmtop-host", "--l2-chain-id", "12345"]);
+            let mut rollup = RollupConfig::preset("optimism").unwrap();
+            rollup.l2_chain_id = 12345;
+            let config = Config {
+                network: "custom.json".into(),
+                ..config
+            };
+            jail.create_binary("custom.json", &rollup.to_json())?;
+            assert!(custom.boot_info(&config).is_err());
+            Ok(())
+        });
+    }
+}
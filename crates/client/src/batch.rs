@@ -0,0 +1,744 @@
+use alloy_consensus::{Header, Transaction, TxEnvelope};
+use alloy_primitives::{Bytes, B256};
+use alloy_rlp::{Decodable, Encodable, RlpDecodable, RlpEncodable};
+use eyre::{Result, WrapErr};
+use std::io::Read;
+use std::sync::Arc;
+
+use palmtop_primitives::RollupConfig;
+
+use crate::channel::{ChannelBank, ChannelRead};
+use crate::frame::parse_frames;
+use crate::program::Failure;
+use crate::span_batch::{RawSpanBatch, SpanBatch};
+
+/// The largest decompressed size of a channel's batches before Fjord.
+pub const MAX_RLP_BYTES_PER_CHANNEL: u64 = 10_000_000;
+
+/// The largest decompressed size of a channel's batches from Fjord on.
+pub const MAX_RLP_BYTES_PER_CHANNEL_FJORD: u64 = 100_000_000;
+
+/// The version byte of brotli compressed channels, accepted from Fjord on.
+pub const CHANNEL_VERSION_BROTLI: u8 = 1;
+
+/// The zlib compression methods, in the low nibble of a zlib stream's first byte.
+const ZLIB_METHODS: [u8; 2] = [8, 15];
+
+/// The type of a batch, its first byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum BatchType {
+    /// A batch of a single L2 block.
+    Singular = 0,
+    /// A batch of a span of L2 blocks, accepted from Delta on.
+    Span = 1,
+}
+
+impl TryFrom<u8> for BatchType {
+    type Error = eyre::Report;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Self::Singular),
+            1 => Ok(Self::Span),
+            _ => eyre::bail!("unknown batch type {}", value),
+        }
+    }
+}
+
+/// ## SingularBatch
+///
+/// A SingularBatch holds a single L2 block: its parent hash, L1 origin, timestamp
+/// and EIP-2718 encoded transactions. It is encoded as an RLP list of its fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq, RlpEncodable, RlpDecodable)]
+pub struct SingularBatch {
+    /// The hash of the block's parent.
+    pub parent_hash: B256,
+    /// The number of the block's L1 origin.
+    pub epoch_num: u64,
+    /// The hash of the block's L1 origin.
+    pub epoch_hash: B256,
+    /// The block's timestamp.
+    pub timestamp: u64,
+    /// The block's EIP-2718 encoded transactions.
+    pub transactions: Vec<Bytes>,
+}
+
+/// ## RawBatch
+///
+/// A RawBatch is a batch as it is encoded in a channel: a [SingularBatch] or a
+/// [RawSpanBatch], whose blocks are not derived yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawBatch {
+    /// A batch of a single L2 block.
+    Singular(SingularBatch),
+    /// A batch of a span of L2 blocks, as encoded.
+    Span(RawSpanBatch),
+}
+
+impl RawBatch {
+    /// Decodes a batch: its type byte followed by its content.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let (&batch_type, mut content) = data
+            .split_first()
+            .ok_or_else(|| eyre::eyre!("batch is empty"))?;
+        match BatchType::try_from(batch_type)? {
+            BatchType::Singular => {
+                let batch = SingularBatch::decode(&mut content)?;
+                if !content.is_empty() {
+                    eyre::bail!("singular batch has {} trailing bytes", content.len());
+                }
+                Ok(Self::Singular(batch))
+            }
+            BatchType::Span => Ok(Self::Span(RawSpanBatch::decode(&mut content)?)),
+        }
+    }
+
+    /// Derives the blocks of a span batch of the given rollup.
+    pub fn derive(self, config: &RollupConfig) -> Result<Batch> {
+        match self {
+            Self::Singular(batch) => Ok(Batch::Singular(batch)),
+            Self::Span(raw) => Ok(Batch::Span(raw.derive(
+                config.block_time,
+                config.genesis.l2_time,
+                config.l2_chain_id,
+            )?)),
+        }
+    }
+}
+
+/// ## Batch
+///
+/// A Batch is a batch read from a channel: a [SingularBatch] or a [SpanBatch],
+/// whose blocks are derived from its [RawBatch].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Batch {
+    /// A batch of a single L2 block.
+    Singular(SingularBatch),
+    /// A batch of a span of L2 blocks.
+    Span(SpanBatch),
+}
+
+impl Batch {
+    /// Returns the type of the batch.
+    pub fn batch_type(&self) -> BatchType {
+        match self {
+            Self::Singular(_) => BatchType::Singular,
+            Self::Span(_) => BatchType::Span,
+        }
+    }
+
+    /// Returns the timestamp of the batch's first block.
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            Self::Singular(batch) => batch.timestamp,
+            Self::Span(batch) => batch.elements.first().map_or(0, |e| e.timestamp),
+        }
+    }
+
+    /// Decodes a batch of the given rollup: its type byte followed by its content.
+    pub fn decode(data: &[u8], config: &RollupConfig) -> Result<Self> {
+        RawBatch::decode(data)?.derive(config)
+    }
+
+    /// Encodes the batch for the given rollup: its type byte followed by its content.
+    pub fn encode(&self, config: &RollupConfig) -> Result<Vec<u8>> {
+        let mut out = vec![self.batch_type() as u8];
+        match self {
+            Self::Singular(batch) => batch.encode(&mut out),
+            Self::Span(batch) => out.extend(
+                batch
+                    .to_raw(
+                        config.block_time,
+                        config.genesis.l2_time,
+                        config.l2_chain_id,
+                    )?
+                    .encode(),
+            ),
+        }
+        Ok(out)
+    }
+}
+
+/// Decompresses a channel's data, up to the given size.
+///
+/// Channels are zlib compressed, or from Fjord on, brotli compressed behind the
+/// [CHANNEL_VERSION_BROTLI] byte. If decompression fails part way, the data
+/// decompressed so far is returned, so that the batches it holds are still read.
+pub fn decompress(data: &[u8], max_len: u64, fjord: bool) -> Result<Vec<u8>> {
+    let &first = data
+        .first()
+        .ok_or_else(|| eyre::eyre!("channel data is empty"))?;
+    let reader: Box<dyn Read + '_> = if ZLIB_METHODS.contains(&(first & 0x0f)) {
+        Box::new(flate2::read::ZlibDecoder::new(data))
+    } else if first == CHANNEL_VERSION_BROTLI {
+        if !fjord {
+            eyre::bail!("brotli compressed channels are not accepted before Fjord");
+        }
+        Box::new(brotli::Decompressor::new(&data[1..], 4096))
+    } else {
+        eyre::bail!("unknown channel compression {:#04x}", first);
+    };
+    let mut out = Vec::new();
+    if let Err(err) = reader.take(max_len).read_to_end(&mut out) {
+        tracing::warn!(
+            target: "palmtop::batch",
+            "Channel decompression failed after {} bytes: {}",
+            out.len(),
+            err
+        );
+    }
+    Ok(out)
+}
+
+/// Reads the batches of a channel's data for the given rollup, with the L1
+/// origin it was read at.
+///
+/// The decompressed data is a sequence of RLP byte strings, each holding a batch.
+/// Reading stops at the first batch that cannot be decoded, keeping the batches
+/// before it. Span batches are skipped before Delta, and so are span batches
+/// whose blocks cannot be derived.
+pub fn read_batches(data: &[u8], config: &RollupConfig, origin: &Header) -> Vec<Batch> {
+    let (max_len, fjord) = if config.is_fjord_active(origin.timestamp) {
+        (MAX_RLP_BYTES_PER_CHANNEL_FJORD, true)
+    } else {
+        (MAX_RLP_BYTES_PER_CHANNEL, false)
+    };
+    let decompressed = match decompress(data, max_len, fjord) {
+        Ok(decompressed) => decompressed,
+        Err(err) => {
+            tracing::warn!(target: "palmtop::batch", "Dropping channel: {}", err);
+            return Vec::new();
+        }
+    };
+    let mut buf = decompressed.as_slice();
+    let mut batches = Vec::new();
+    while !buf.is_empty() {
+        let raw = Bytes::decode(&mut buf)
+            .wrap_err("invalid batch encoding")
+            .and_then(|data| RawBatch::decode(&data));
+        let raw = match raw {
+            Ok(RawBatch::Span(_)) if !config.is_delta_active(origin.timestamp) => {
+                tracing::warn!(
+                    target: "palmtop::batch",
+                    "Skipping span batch in L1 block {} before Delta",
+                    origin.number
+                );
+                continue;
+            }
+            Ok(raw) => raw,
+            Err(err) => {
+                tracing::warn!(
+                    target: "palmtop::batch",
+                    "Dropping the rest of the channel: {:#}",
+                    err
+                );
+                break;
+            }
+        };
+        match raw.derive(config) {
+            Ok(batch) => batches.push(batch),
+            Err(err) => {
+                tracing::warn!(target: "palmtop::batch", "Skipping span batch: {:#}", err);
+            }
+        }
+    }
+    batches
+}
+
+/// ## BatchDecoder
+///
+/// The BatchDecoder turns the batcher transactions of each L1 block into batches.
+///
+/// Batcher transactions are the ones sent to the rollup's batch inbox by its
+/// batcher. Their calldata holds frames, which are assembled into channels by a
+/// [ChannelBank]. Each channel that becomes ready is decompressed and read into
+/// batches.
+///
+/// The batcher address is taken from the genesis system config, as updates to
+/// the system config are not followed yet. Blob batcher transactions and
+/// Holocene's frame and channel rules are not supported.
+#[derive(Debug)]
+pub struct BatchDecoder {
+    config: Arc<RollupConfig>,
+    bank: ChannelBank,
+}
+
+impl BatchDecoder {
+    /// Creates a new [BatchDecoder] for the given rollup.
+    pub fn new(config: Arc<RollupConfig>) -> Self {
+        Self {
+            bank: ChannelBank::new(config.clone()),
+            config,
+        }
+    }
+
+    /// Returns the channel bank.
+    pub fn bank(&self) -> &ChannelBank {
+        &self.bank
+    }
+
+    /// Adds the next L1 block with its transactions, returning the batches of the
+    /// channels read at it, in order.
+    pub fn add_l1_block(
+        &mut self,
+        origin: &Header,
+        transactions: &[TxEnvelope],
+    ) -> Result<Vec<Batch>> {
+        if self.config.is_holocene_active(origin.timestamp) {
+            return Err(eyre::eyre!(
+                "deriving from L1 block {} after Holocene is not supported yet",
+                origin.number
+            ))
+            .wrap_err(Failure::Unsupported);
+        }
+        let mut batches = Vec::new();
+        for frames in self
+            .batcher_data(transactions)?
+            .iter()
+            .map(|data| parse_frames(data))
+        {
+            let frames = match frames {
+                Ok(frames) => frames,
+                Err(err) => {
+                    tracing::warn!(
+                        target: "palmtop::batch",
+                        "Dropping batcher transaction: {}",
+                        err
+                    );
+                    continue;
+                }
+            };
+            for frame in frames {
+                self.read_channels(origin, &mut batches);
+                self.bank.ingest(origin, frame);
+            }
+        }
+        self.read_channels(origin, &mut batches);
+        Ok(batches)
+    }
+
+    /// Reads the channels that are ready at the given origin into batches, until
+    /// no channel is left to read.
+    fn read_channels(&mut self, origin: &Header, batches: &mut Vec<Batch>) {
+        loop {
+            match self.bank.read(origin) {
+                ChannelRead::Data(data) => {
+                    batches.extend(read_batches(&data, &self.config, origin));
+                }
+                ChannelRead::TimedOut => {}
+                ChannelRead::Empty => break,
+            }
+        }
+    }
+
+    /// Returns the calldata of the batcher transactions among the given ones.
+    fn batcher_data<'a>(&self, transactions: &'a [TxEnvelope]) -> Result<Vec<&'a [u8]>> {
+        let mut data = Vec::new();
+        for tx in transactions {
+            if tx.to().to() != Some(&self.config.batch_inbox_address) {
+                continue;
+            }
+            match tx.recover_signer() {
+                Ok(sender) if sender == self.config.genesis.system_config.batcher_addr => {}
+                Ok(_) => continue,
+                Err(err) => {
+                    tracing::warn!(
+                        target: "palmtop::batch",
+                        "Skipping batch inbox transaction {}: {}",
+                        tx.tx_hash(),
+                        err
+                    );
+                    continue;
+                }
+            }
+            if matches!(tx, TxEnvelope::Eip4844(_)) {
+                return Err(eyre::eyre!(
+                    "blob batcher transaction {} is not supported yet",
+                    tx.tx_hash()
+                ))
+                .wrap_err(Failure::Unsupported);
+            }
+            data.push(tx.input());
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::tests::frame;
+    use crate::frame::{Frame, DERIVATION_VERSION_0};
+    use crate::span_batch::tests::{sign, transactions};
+    use crate::span_batch::SpanBatchElement;
+    use alloy_consensus::TxEip1559;
+    use alloy_eips::eip2718::Encodable2718;
+    use alloy_primitives::{hex, Address, FixedBytes, TxKind};
+    use std::io::Write;
+
+    /// Returns a rollup config with every fork up to Fjord active from genesis,
+    /// and the test key as its batcher.
+    fn config() -> RollupConfig {
+        let mut config = RollupConfig::preset("optimism").unwrap();
+        config.genesis.l2_time = 1000;
+        config.block_time = 2;
+        config.l2_chain_id = 10;
+        for time in [
+            &mut config.regolith_time,
+            &mut config.canyon_time,
+            &mut config.delta_time,
+            &mut config.ecotone_time,
+            &mut config.fjord_time,
+        ] {
+            *time = Some(0);
+        }
+        config.granite_time = None;
+        config.holocene_time = None;
+        config.genesis.system_config.batcher_addr = batcher_tx(vec![], 0).recover_signer().unwrap();
+        config.validate().unwrap();
+        config
+    }
+
+    fn origin(number: u64) -> Header {
+        Header {
+            number,
+            timestamp: number * 12,
+            ..Default::default()
+        }
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn brotli(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![CHANNEL_VERSION_BROTLI];
+        let mut encoder = brotli::CompressorWriter::new(&mut out, 4096, 11, 22);
+        encoder.write_all(data).unwrap();
+        drop(encoder);
+        out
+    }
+
+    /// Encodes batches as the uncompressed data of a channel.
+    fn channel_data(batches: &[Batch], config: &RollupConfig) -> Vec<u8> {
+        let mut out = Vec::new();
+        for batch in batches {
+            Bytes::from(batch.encode(config).unwrap()).encode(&mut out);
+        }
+        out
+    }
+
+    fn singular(timestamp: u64) -> Batch {
+        Batch::Singular(SingularBatch {
+            parent_hash: B256::repeat_byte(1),
+            epoch_num: 2,
+            epoch_hash: B256::repeat_byte(3),
+            timestamp,
+            transactions: vec![Bytes::from_static(&[0x7e, 1, 2])],
+        })
+    }
+
+    fn span(config: &RollupConfig) -> Batch {
+        let txs = transactions(config.l2_chain_id);
+        Batch::Span(SpanBatch {
+            parent_check: FixedBytes::repeat_byte(4),
+            l1_origin_check: FixedBytes::repeat_byte(5),
+            elements: vec![
+                SpanBatchElement {
+                    epoch_num: 1,
+                    timestamp: 1002,
+                    transactions: vec![txs[0].encoded_2718().into()],
+                },
+                SpanBatchElement {
+                    epoch_num: 2,
+                    timestamp: 1004,
+                    transactions: txs[1..].iter().map(|tx| tx.encoded_2718().into()).collect(),
+                },
+            ],
+        })
+    }
+
+    /// Returns an L1 transaction signed by the test key sending data to the given inbox.
+    fn batcher_tx(data: Vec<u8>, nonce: u64) -> TxEnvelope {
+        let inbox = RollupConfig::preset("optimism")
+            .unwrap()
+            .batch_inbox_address;
+        TxEnvelope::Eip1559(sign(
+            TxEip1559 {
+                chain_id: 1,
+                nonce,
+                gas_limit: 100_000,
+                to: TxKind::Call(inbox),
+                input: data.into(),
+                ..Default::default()
+            },
+            None,
+        ))
+    }
+
+    /// Encodes frames as batcher transaction data.
+    fn frames_data(frames: &[Frame]) -> Vec<u8> {
+        let mut data = vec![DERIVATION_VERSION_0];
+        for frame in frames {
+            data.extend(frame.encode());
+        }
+        data
+    }
+
+    #[test]
+    fn test_singular_batch_encoding() {
+        let config = config();
+        let encoded = singular(0x10).encode(&config).unwrap();
+        let expected = [
+            hex!("00 f8 49 a0").as_slice(),
+            &[1; 32],
+            &hex!("02 a0"),
+            &[3; 32],
+            &hex!("10 c4 83 7e0102"),
+        ]
+        .concat();
+        assert_eq!(encoded, expected);
+        assert_eq!(Batch::decode(&encoded, &config).unwrap(), singular(0x10));
+
+        let trailing = [encoded.as_slice(), &[0]].concat();
+        assert!(Batch::decode(&trailing, &config).is_err());
+        assert!(Batch::decode(&[2], &config).is_err());
+        assert!(Batch::decode(&[], &config).is_err());
+    }
+
+    /// Ported from the singular batches of op-node's `TestBatchRoundTrip`
+    /// (op-node/rollup/derive/batch_test.go).
+    #[test]
+    fn test_singular_batch_roundtrip() {
+        let config = config();
+        let mut parent_hash = B256::ZERO;
+        parent_hash[31] = 0x42;
+        for batch in [
+            SingularBatch::default(),
+            SingularBatch {
+                parent_hash,
+                epoch_num: 1,
+                epoch_hash: B256::ZERO,
+                timestamp: 1647026951,
+                transactions: vec![
+                    Bytes::from_static(&[0, 0, 0]),
+                    Bytes::from_static(&[0x76, 0xfd, 0x7c]),
+                ],
+            },
+        ] {
+            let batch = Batch::Singular(batch);
+            let encoded = batch.encode(&config).unwrap();
+            assert_eq!(Batch::decode(&encoded, &config).unwrap(), batch);
+        }
+    }
+
+    #[test]
+    fn test_span_batch_encoding() {
+        let config = config();
+        let batch = span(&config);
+        let encoded = batch.encode(&config).unwrap();
+        assert_eq!(encoded[0], BatchType::Span as u8);
+        assert_eq!(Batch::decode(&encoded, &config).unwrap(), batch);
+        assert_eq!(batch.timestamp(), 1002);
+    }
+
+    #[test]
+    fn test_decompress() {
+        let data = b"palmtop channel data".repeat(10);
+        assert_eq!(decompress(&zlib(&data), 1000, false).unwrap(), data);
+        assert_eq!(decompress(&brotli(&data), 1000, true).unwrap(), data);
+        assert_eq!(decompress(&zlib(&data), 10, false).unwrap(), &data[..10]);
+
+        assert!(decompress(&brotli(&data), 1000, false).is_err());
+        assert!(decompress(&[], 1000, true).is_err());
+        assert!(decompress(&[0x02, 0x00], 1000, true).is_err());
+
+        let compressed = zlib(&data);
+        let truncated = decompress(&compressed[..compressed.len() / 2], 1000, false).unwrap();
+        assert!(data.starts_with(&truncated));
+    }
+
+    /// Returns the config with Delta and the forks after it activated later.
+    fn pre_delta(config: &RollupConfig) -> RollupConfig {
+        let mut config = config.clone();
+        for time in [
+            &mut config.delta_time,
+            &mut config.ecotone_time,
+            &mut config.fjord_time,
+        ] {
+            *time = Some(100);
+        }
+        config
+    }
+
+    #[test]
+    fn test_read_batches() {
+        let config = config();
+        let batches = vec![singular(1002), span(&config), singular(1008)];
+        let data = channel_data(&batches, &config);
+        assert_eq!(read_batches(&zlib(&data), &config, &origin(1)), batches);
+        assert_eq!(read_batches(&brotli(&data), &config, &origin(1)), batches);
+
+        let pre_delta = pre_delta(&config);
+        assert_eq!(
+            read_batches(&zlib(&data), &pre_delta, &origin(1)),
+            [singular(1002), singular(1008)]
+        );
+        assert!(read_batches(&brotli(&data), &pre_delta, &origin(1)).is_empty());
+    }
+
+    #[test]
+    fn test_read_batches_stops_at_invalid_batch() {
+        let config = config();
+        let mut data = channel_data(&[singular(1002)], &config);
+        Bytes::from_static(&[9, 9]).encode(&mut data);
+        data.extend(channel_data(&[singular(1004)], &config));
+        assert_eq!(
+            read_batches(&zlib(&data), &config, &origin(1)),
+            [singular(1002)]
+        );
+
+        let data = channel_data(&[singular(1002), singular(1004)], &config);
+        assert_eq!(
+            read_batches(&zlib(&data[..data.len() - 1]), &config, &origin(1)),
+            [singular(1002)]
+        );
+    }
+
+    #[test]
+    fn test_read_batches_skips_underivable_span_batch() {
+        let config = config();
+        let Batch::Span(batch) = span(&config) else {
+            unreachable!()
+        };
+        let mut raw = batch
+            .to_raw(
+                config.block_time,
+                config.genesis.l2_time,
+                config.l2_chain_id,
+            )
+            .unwrap();
+        // The span batch changes L1 origin once, so its first origin underflows.
+        raw.l1_origin_num = 0;
+        let mut underivable = vec![BatchType::Span as u8];
+        underivable.extend(raw.encode());
+        assert!(RawBatch::decode(&underivable).is_ok());
+
+        let mut data = channel_data(&[singular(1002)], &config);
+        Bytes::from(underivable).encode(&mut data);
+        data.extend(channel_data(&[singular(1004)], &config));
+        assert_eq!(
+            read_batches(&zlib(&data), &config, &origin(1)),
+            [singular(1002), singular(1004)]
+        );
+
+        // Before Delta the span batch is skipped without being derived.
+        let pre_delta = pre_delta(&config);
+        assert_eq!(
+            read_batches(&zlib(&data), &pre_delta, &origin(1)),
+            [singular(1002), singular(1004)]
+        );
+    }
+
+    #[test]
+    fn test_decoder_reads_past_timed_out_channel() {
+        let mut config = config();
+        for time in [
+            &mut config.canyon_time,
+            &mut config.delta_time,
+            &mut config.ecotone_time,
+            &mut config.fjord_time,
+        ] {
+            *time = None;
+        }
+        config.channel_timeout = 2;
+        let config = Arc::new(config);
+        let mut decoder = BatchDecoder::new(config.clone());
+
+        // A channel that never completes, in front of one that is ready at
+        // block 3 and times out at block 5.
+        let compressed = zlib(&channel_data(&[singular(1002)], &config));
+        let txs = [batcher_tx(frames_data(&[frame(1, 0, b"", false)]), 0)];
+        assert!(decoder.add_l1_block(&origin(1), &txs).unwrap().is_empty());
+        let txs = [batcher_tx(
+            frames_data(&[frame(2, 0, &compressed, false)]),
+            1,
+        )];
+        assert!(decoder.add_l1_block(&origin(2), &txs).unwrap().is_empty());
+        let txs = [batcher_tx(frames_data(&[frame(2, 1, b"", true)]), 2)];
+        assert!(decoder.add_l1_block(&origin(3), &txs).unwrap().is_empty());
+
+        // Dropping the first channel at block 4 does not stop the ready one
+        // behind it from being read at the same block.
+        assert_eq!(
+            decoder.add_l1_block(&origin(4), &[]).unwrap(),
+            [singular(1002)]
+        );
+        assert!(decoder.bank().is_empty());
+    }
+
+    #[test]
+    fn test_decoder_reads_batches_from_batcher_transactions() {
+        let config = Arc::new(config());
+        let compressed = zlib(&channel_data(&[singular(1002), singular(1004)], &config));
+        let (first, second) = compressed.split_at(compressed.len() / 2);
+        let mut decoder = BatchDecoder::new(config.clone());
+
+        let txs = [batcher_tx(frames_data(&[frame(1, 0, first, false)]), 0)];
+        assert!(decoder.add_l1_block(&origin(1), &txs).unwrap().is_empty());
+        assert_eq!(decoder.bank().len(), 1);
+
+        let txs = [batcher_tx(frames_data(&[frame(1, 1, second, true)]), 1)];
+        assert_eq!(
+            decoder.add_l1_block(&origin(2), &txs).unwrap(),
+            [singular(1002), singular(1004)]
+        );
+        assert!(decoder.bank().is_empty());
+    }
+
+    #[test]
+    fn test_decoder_ignores_other_transactions() {
+        let mut config = config();
+        let data = frames_data(&[frame(
+            1,
+            0,
+            &zlib(&channel_data(&[singular(1002)], &config)),
+            true,
+        )]);
+        let tx = batcher_tx(data.clone(), 0);
+        let mut invalid = data.clone();
+        invalid[0] = 1;
+
+        let mut decoder = BatchDecoder::new(Arc::new(config.clone()));
+        let txs = [batcher_tx(invalid, 0), tx.clone()];
+        assert_eq!(
+            decoder.add_l1_block(&origin(1), &txs).unwrap(),
+            [singular(1002)]
+        );
+
+        config.batch_inbox_address = Address::repeat_byte(1);
+        let mut decoder = BatchDecoder::new(Arc::new(config.clone()));
+        assert!(decoder
+            .add_l1_block(&origin(1), std::slice::from_ref(&tx))
+            .unwrap()
+            .is_empty());
+
+        config.batch_inbox_address = tx.to().to().copied().unwrap();
+        config.genesis.system_config.batcher_addr = Address::repeat_byte(1);
+        let mut decoder = BatchDecoder::new(Arc::new(config));
+        assert!(decoder.add_l1_block(&origin(1), &[tx]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_decoder_rejects_holocene() {
+        let mut config = config();
+        config.granite_time = Some(0);
+        config.holocene_time = Some(0);
+        let mut decoder = BatchDecoder::new(Arc::new(config));
+        let err = decoder.add_l1_block(&origin(1), &[]).unwrap_err();
+        assert_eq!(Failure::of(&err), Some(Failure::Unsupported));
+    }
+}
@@ -0,0 +1,1945 @@
+use alloy_consensus::Header;
+use alloy_primitives::{Bytes, B256};
+use eyre::{Result, WrapErr};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use palmtop_primitives::rollup::BlockId;
+use palmtop_primitives::RollupConfig;
+
+use crate::batch::{Batch, SingularBatch};
+use crate::program::Failure;
+use crate::span_batch::SpanBatch;
+
+/// The largest number of seconds an L2 block's timestamp may be ahead of its L1
+/// origin's from Fjord on.
+pub const MAX_SEQUENCER_DRIFT_FJORD: u64 = 1800;
+
+/// The type byte of deposit transactions, which batches may not hold.
+const DEPOSIT_TX_TYPE: u8 = 0x7e;
+
+/// The hash, number, parent hash and timestamp of an L1 block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct L1BlockInfo {
+    /// The block hash.
+    pub hash: B256,
+    /// The block number.
+    pub number: u64,
+    /// The hash of the block's parent.
+    pub parent_hash: B256,
+    /// The block's timestamp.
+    pub timestamp: u64,
+}
+
+impl L1BlockInfo {
+    /// Returns the info of the given L1 block header.
+    pub fn from_header(header: &Header) -> Self {
+        Self {
+            hash: header.hash_slow(),
+            number: header.number,
+            parent_hash: header.parent_hash,
+            timestamp: header.timestamp,
+        }
+    }
+
+    /// Returns the hash and number of the block.
+    pub fn id(&self) -> BlockId {
+        BlockId {
+            hash: self.hash,
+            number: self.number,
+        }
+    }
+}
+
+/// The hash, number, timestamp and L1 origin of an L2 block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct L2BlockInfo {
+    /// The block hash.
+    pub hash: B256,
+    /// The block number.
+    pub number: u64,
+    /// The block's timestamp.
+    pub timestamp: u64,
+    /// The L1 block the block was derived from.
+    pub l1_origin: BlockId,
+}
+
+/// ## SafeBlockFetcher
+///
+/// A SafeBlockFetcher serves the safe L2 chain to the batch queue, to check span
+/// batches overlapping blocks that are already safe.
+pub trait SafeBlockFetcher {
+    /// Returns the info of the safe L2 block with the given number.
+    fn block_info(&mut self, number: u64) -> Result<L2BlockInfo>;
+
+    /// Returns the EIP-2718 encoded transactions of the safe L2 block with the
+    /// given number, deposits included.
+    fn transactions(&mut self, number: u64) -> Result<Vec<Bytes>>;
+}
+
+impl<T: SafeBlockFetcher + ?Sized> SafeBlockFetcher for &mut T {
+    fn block_info(&mut self, number: u64) -> Result<L2BlockInfo> {
+        (**self).block_info(number)
+    }
+
+    fn transactions(&mut self, number: u64) -> Result<Vec<Bytes>> {
+        (**self).transactions(number)
+    }
+}
+
+/// The outcome of checking a batch against the safe head.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchValidity {
+    /// The batch is invalid and is dropped.
+    Drop,
+    /// The batch is valid and is the next batch to apply.
+    Accept,
+    /// The batch cannot be checked without more L1 blocks.
+    Undecided,
+    /// The batch is for a later block and is kept for later.
+    Future,
+}
+
+/// A batch with the L1 block it was included in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IncludedBatch {
+    batch: Batch,
+    inclusion: L1BlockInfo,
+}
+
+/// Checks a batch included in the given L1 block against the safe head, with the
+/// L1 blocks from the safe head's epoch on.
+///
+/// Safe blocks overlapped by a span batch are fetched from the fetcher. Unlike
+/// op-node, which retries later, a failed fetch fails the check.
+pub fn check_batch(
+    config: &RollupConfig,
+    l1_blocks: &[L1BlockInfo],
+    safe_head: &L2BlockInfo,
+    batch: &Batch,
+    inclusion: &L1BlockInfo,
+    fetcher: &mut impl SafeBlockFetcher,
+) -> Result<BatchValidity> {
+    match batch {
+        Batch::Singular(batch) => Ok(check_singular_batch(
+            config, l1_blocks, safe_head, batch, inclusion,
+        )),
+        Batch::Span(batch) => {
+            check_span_batch(config, l1_blocks, safe_head, batch, inclusion, fetcher)
+        }
+    }
+}
+
+/// Checks a singular batch against the safe head.
+fn check_singular_batch(
+    config: &RollupConfig,
+    l1_blocks: &[L1BlockInfo],
+    safe_head: &L2BlockInfo,
+    batch: &SingularBatch,
+    inclusion: &L1BlockInfo,
+) -> BatchValidity {
+    let Some(epoch) = l1_blocks.first() else {
+        return BatchValidity::Undecided;
+    };
+    let next_timestamp = safe_head.timestamp + config.block_time;
+    if batch.timestamp > next_timestamp {
+        return BatchValidity::Future;
+    }
+    if batch.timestamp < next_timestamp {
+        return dropped("batch timestamp is before the next block");
+    }
+    if batch.parent_hash != safe_head.hash {
+        return dropped("batch parent hash does not match the safe head");
+    }
+    if batch.epoch_num + config.seq_window_size < inclusion.number {
+        return dropped("batch was included after its sequencing window");
+    }
+
+    let origin = if batch.epoch_num < epoch.number {
+        return dropped("batch epoch is too old");
+    } else if batch.epoch_num == epoch.number {
+        epoch
+    } else if batch.epoch_num == epoch.number + 1 {
+        match l1_blocks.get(1) {
+            Some(next) => next,
+            None => return BatchValidity::Undecided,
+        }
+    } else {
+        return dropped("batch epoch is too far ahead");
+    };
+    if batch.epoch_hash != origin.hash {
+        return dropped("batch epoch hash does not match its L1 origin");
+    }
+    if batch.timestamp < origin.timestamp {
+        return dropped("batch timestamp is before its L1 origin");
+    }
+
+    if batch.timestamp > origin.timestamp + max_sequencer_drift(config, origin) {
+        if !batch.transactions.is_empty() {
+            return dropped("batch with transactions exceeds the sequencer drift");
+        }
+        // An empty batch past the drift keeps the L2 time from falling behind
+        // L1, unless it should have adopted the next L1 origin.
+        if epoch.number == origin.number {
+            match l1_blocks.get(1) {
+                None => return BatchValidity::Undecided,
+                Some(next) if batch.timestamp >= next.timestamp => {
+                    return dropped(
+                        "empty batch past the sequencer drift did not adopt the next L1 origin",
+                    );
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    if let Some(reason) = invalid_transactions(&batch.transactions) {
+        return dropped(reason);
+    }
+    BatchValidity::Accept
+}
+
+/// Checks a span batch against the safe head.
+fn check_span_batch(
+    config: &RollupConfig,
+    l1_blocks: &[L1BlockInfo],
+    safe_head: &L2BlockInfo,
+    batch: &SpanBatch,
+    inclusion: &L1BlockInfo,
+    fetcher: &mut impl SafeBlockFetcher,
+) -> Result<BatchValidity> {
+    let Some(epoch) = l1_blocks.first() else {
+        return Ok(BatchValidity::Undecided);
+    };
+    let (Some(first), Some(last)) = (batch.elements.first(), batch.elements.last()) else {
+        return Ok(dropped("span batch holds no blocks"));
+    };
+    let start_epoch = first.epoch_num;
+    let mut origin = epoch;
+    if start_epoch == epoch.number + 1 {
+        match l1_blocks.get(1) {
+            Some(next) => origin = next,
+            None => return Ok(BatchValidity::Undecided),
+        }
+    }
+    if !config.is_delta_active(origin.timestamp) {
+        return Ok(dropped("span batch L1 origin is before Delta"));
+    }
+
+    let next_timestamp = safe_head.timestamp + config.block_time;
+    if first.timestamp > next_timestamp {
+        return Ok(BatchValidity::Future);
+    }
+    if last.timestamp < next_timestamp {
+        return Ok(dropped("span batch has no blocks after the safe head"));
+    }
+
+    let mut parent_num = safe_head.number;
+    let mut parent = *safe_head;
+    if first.timestamp < next_timestamp {
+        if first.timestamp > safe_head.timestamp {
+            return Ok(dropped(
+                "span batch timestamp is misaligned with the safe chain",
+            ));
+        }
+        let behind = safe_head.timestamp - first.timestamp;
+        if behind % config.block_time != 0 {
+            return Ok(dropped(
+                "span batch timestamp is misaligned with the safe chain",
+            ));
+        }
+        let Some(num) = safe_head.number.checked_sub(behind / config.block_time + 1) else {
+            return Ok(dropped("span batch starts before L2 genesis"));
+        };
+        parent_num = num;
+        parent = fetcher
+            .block_info(parent_num)
+            .wrap_err_with(|| format!("failed to fetch safe L2 block {parent_num}"))?;
+    }
+    if batch.parent_check.as_slice() != &parent.hash[..20] {
+        return Ok(dropped(
+            "span batch parent check does not match its parent block",
+        ));
+    }
+    if start_epoch + config.seq_window_size < inclusion.number {
+        return Ok(dropped(
+            "span batch was included after its sequencing window",
+        ));
+    }
+    if start_epoch > parent.l1_origin.number + 1 {
+        return Ok(dropped("span batch epoch is too far ahead"));
+    }
+
+    let end_epoch = last.epoch_num;
+    match l1_blocks.iter().find(|block| block.number == end_epoch) {
+        Some(block) if batch.l1_origin_check.as_slice() != &block.hash[..20] => {
+            return Ok(dropped(
+                "span batch L1 origin check does not match its L1 origin",
+            ));
+        }
+        Some(_) => {}
+        None => return Ok(BatchValidity::Undecided),
+    }
+    if start_epoch < parent.l1_origin.number {
+        return Ok(dropped("span batch epoch is too old"));
+    }
+
+    let mut origin_index = 0;
+    let mut origin_advanced = start_epoch == parent.l1_origin.number + 1;
+    for (i, element) in batch.elements.iter().enumerate() {
+        if element.timestamp <= safe_head.timestamp {
+            continue;
+        }
+        // Like op-node, a block whose L1 origin is not known is checked against
+        // an empty L1 block.
+        let mut l1_origin = L1BlockInfo::default();
+        if let Some(index) = (origin_index..l1_blocks.len())
+            .find(|&index| l1_blocks[index].number == element.epoch_num)
+        {
+            l1_origin = l1_blocks[index];
+            origin_index = index;
+        }
+        if i > 0 {
+            origin_advanced = element.epoch_num > batch.elements[i - 1].epoch_num;
+        }
+        if element.timestamp < l1_origin.timestamp {
+            return Ok(dropped(
+                "span batch block timestamp is before its L1 origin",
+            ));
+        }
+        if element.timestamp > l1_origin.timestamp + max_sequencer_drift(config, &l1_origin) {
+            if !element.transactions.is_empty() {
+                return Ok(dropped(
+                    "span batch block with transactions exceeds the sequencer drift",
+                ));
+            }
+            if !origin_advanced {
+                match l1_blocks.get(origin_index + 1) {
+                    None => return Ok(BatchValidity::Undecided),
+                    Some(next) if element.timestamp >= next.timestamp => {
+                        return Ok(dropped(
+                            "empty span batch block past the drift did not adopt the next origin",
+                        ));
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        if let Some(reason) = invalid_transactions(&element.transactions) {
+            return Ok(dropped(reason));
+        }
+    }
+
+    if first.timestamp < next_timestamp {
+        for i in 0..safe_head.number - parent_num {
+            let number = parent_num + i + 1;
+            let element = &batch.elements[i as usize];
+            let safe_txs = fetcher
+                .transactions(number)
+                .wrap_err_with(|| format!("failed to fetch safe L2 block {number}"))?;
+            let deposits = safe_txs
+                .iter()
+                .filter(|tx| tx.first() == Some(&DEPOSIT_TX_TYPE))
+                .count();
+            if safe_txs[deposits..] != element.transactions[..] {
+                return Ok(dropped(
+                    "span batch transactions do not match an overlapped safe block",
+                ));
+            }
+            let safe_block = fetcher
+                .block_info(number)
+                .wrap_err_with(|| format!("failed to fetch safe L2 block {number}"))?;
+            if safe_block.l1_origin.number != element.epoch_num {
+                return Ok(dropped(
+                    "span batch L1 origin does not match an overlapped safe block",
+                ));
+            }
+        }
+    }
+    Ok(BatchValidity::Accept)
+}
+
+/// Returns why a batch's transactions are invalid, if they are.
+fn invalid_transactions(transactions: &[Bytes]) -> Option<&'static str> {
+    transactions.iter().find_map(|tx| match tx.first() {
+        None => Some("batch holds an empty transaction"),
+        Some(&DEPOSIT_TX_TYPE) => Some("batch holds a deposit transaction"),
+        Some(_) => None,
+    })
+}
+
+/// Returns the largest number of seconds a block may be ahead of the given L1 origin.
+fn max_sequencer_drift(config: &RollupConfig, origin: &L1BlockInfo) -> u64 {
+    if config.is_fjord_active(origin.timestamp) {
+        MAX_SEQUENCER_DRIFT_FJORD
+    } else {
+        config.max_sequencer_drift
+    }
+}
+
+/// Logs why a batch is dropped.
+fn dropped(reason: &str) -> BatchValidity {
+    tracing::warn!(target: "palmtop::batch_queue", "Dropping batch: {}", reason);
+    BatchValidity::Drop
+}
+
+impl SpanBatch {
+    /// Expands the blocks of the span batch after the safe head into singular
+    /// batches, taking their epoch hashes from the given L1 blocks.
+    ///
+    /// The parent hashes are left unset, as they are only known once each block
+    /// before them has been built.
+    pub fn singular_batches(
+        &self,
+        l1_blocks: &[L1BlockInfo],
+        safe_head: &L2BlockInfo,
+    ) -> Result<Vec<SingularBatch>> {
+        let mut origin_index = 0;
+        let mut batches = Vec::new();
+        for element in &self.elements {
+            if element.timestamp <= safe_head.timestamp {
+                continue;
+            }
+            let index = (origin_index..l1_blocks.len())
+                .find(|&index| l1_blocks[index].number == element.epoch_num)
+                .ok_or_else(|| {
+                    eyre::eyre!("span batch L1 origin {} is not known", element.epoch_num)
+                })?;
+            origin_index = index;
+            batches.push(SingularBatch {
+                parent_hash: B256::ZERO,
+                epoch_num: element.epoch_num,
+                epoch_hash: l1_blocks[index].hash,
+                timestamp: element.timestamp,
+                transactions: element.transactions.clone(),
+            });
+        }
+        Ok(batches)
+    }
+}
+
+/// ## BatchQueue
+///
+/// The BatchQueue orders the batches read from L1 into the singular batch of each
+/// next L2 block, following op-node's pre-Holocene batch queue.
+///
+/// Batches are checked against the safe head as they arrive and again when the
+/// next batch is needed. The first batch, in the order they were included in L1,
+/// that is valid on top of the safe head is the next one. Span batches are
+/// expanded into singular batches, which are returned one block at a time.
+///
+/// When an epoch's sequencing window expires without a valid batch, empty batches
+/// are generated for it up to the next L1 origin's timestamp, and the queue moves
+/// on to the next epoch.
+#[derive(Debug)]
+pub struct BatchQueue<F> {
+    config: Arc<RollupConfig>,
+    fetcher: F,
+    origin: L1BlockInfo,
+    next_origin: L1BlockInfo,
+    pending: VecDeque<Batch>,
+    l1_blocks: Vec<L1BlockInfo>,
+    batches: Vec<IncludedBatch>,
+    next_span: VecDeque<SingularBatch>,
+}
+
+impl<F: SafeBlockFetcher> BatchQueue<F> {
+    /// Creates a new [BatchQueue] for the given rollup that starts reading from
+    /// the given L1 block.
+    pub fn new(config: Arc<RollupConfig>, base: L1BlockInfo, fetcher: F) -> Self {
+        Self {
+            config,
+            fetcher,
+            origin: base,
+            next_origin: base,
+            pending: VecDeque::new(),
+            l1_blocks: vec![base],
+            batches: Vec::new(),
+            next_span: VecDeque::new(),
+        }
+    }
+
+    /// Returns the latest L1 block added to the queue.
+    pub fn l1_head(&self) -> L1BlockInfo {
+        self.next_origin
+    }
+
+    /// Returns the fetcher of the safe L2 chain, which must serve each block
+    /// built from the queue's batches once it is safe.
+    pub fn fetcher_mut(&mut self) -> &mut F {
+        &mut self.fetcher
+    }
+
+    /// Returns the L1 blocks from the current epoch on.
+    pub fn l1_blocks(&self) -> &[L1BlockInfo] {
+        &self.l1_blocks
+    }
+
+    /// Adds the next L1 block with the batches read from it. The batches of the
+    /// previous block must all be taken first, that is [BatchQueue::next_batch]
+    /// must have returned `None`.
+    pub fn add_l1_block(&mut self, block: L1BlockInfo, batches: Vec<Batch>) -> Result<()> {
+        if !self.pending.is_empty() {
+            eyre::bail!(
+                "L1 block {} added before the {} pending batches of L1 block {}",
+                block.number,
+                self.pending.len(),
+                self.next_origin.number
+            );
+        }
+        if block.number != self.next_origin.number + 1 || block.parent_hash != self.next_origin.hash
+        {
+            eyre::bail!(
+                "L1 block {} ({}) does not extend the batch queue origin {} ({})",
+                block.number,
+                block.hash,
+                self.next_origin.number,
+                self.next_origin.hash
+            );
+        }
+        if self.config.is_holocene_active(block.timestamp) {
+            return Err(eyre::eyre!(
+                "the batch queue after Holocene is not supported yet"
+            ))
+            .wrap_err(Failure::Unsupported);
+        }
+        self.next_origin = block;
+        self.pending = batches.into();
+        Ok(())
+    }
+
+    /// Returns the singular batch of the block after the given safe head, with
+    /// whether it is the last batch of its span, or `None` if the next L1 block
+    /// is needed first.
+    pub fn next_batch(&mut self, parent: &L2BlockInfo) -> Result<Option<(SingularBatch, bool)>> {
+        loop {
+            match self.step(parent)? {
+                Step::Batch(batch, last) => return Ok(Some((batch, last))),
+                Step::NotEnoughData => continue,
+                Step::Eof => return Ok(None),
+            }
+        }
+    }
+
+    /// Takes a single step of op-node's batch queue.
+    fn step(&mut self, parent: &L2BlockInfo) -> Result<Step> {
+        if let Some(next) = self.next_span.front() {
+            if next.timestamp == parent.timestamp + self.config.block_time {
+                let batch = self.pop_span_batch(parent);
+                return Ok(Step::Batch(batch, self.next_span.is_empty()));
+            }
+            tracing::warn!(
+                target: "palmtop::batch_queue",
+                "Dropping the rest of a span batch not built on block {}",
+                parent.number
+            );
+            self.next_span.clear();
+        }
+
+        // The epoch only advances once a whole span batch has been applied, as
+        // its blocks are checked against the L1 blocks of its first epoch.
+        if self
+            .l1_blocks
+            .first()
+            .is_some_and(|epoch| parent.l1_origin.number > epoch.number)
+        {
+            if let Some(index) = self
+                .l1_blocks
+                .iter()
+                .position(|block| block.number == parent.l1_origin.number)
+            {
+                self.l1_blocks.drain(..index);
+            }
+        }
+
+        let origin_behind = self.next_origin.number < parent.l1_origin.number;
+        if self.origin != self.next_origin {
+            self.origin = self.next_origin;
+            if origin_behind {
+                self.l1_blocks.clear();
+            } else {
+                self.l1_blocks.push(self.origin);
+            }
+        }
+
+        let out_of_data = match self.pending.pop_front() {
+            Some(batch) => {
+                if !origin_behind {
+                    self.add_batch(batch, parent)?;
+                }
+                false
+            }
+            None => true,
+        };
+        if origin_behind {
+            return Ok(if out_of_data {
+                Step::Eof
+            } else {
+                Step::NotEnoughData
+            });
+        }
+
+        match self.derive_next_batch(out_of_data, parent)? {
+            Some(Batch::Singular(batch)) => Ok(Step::Batch(batch, true)),
+            Some(Batch::Span(batch)) => {
+                self.next_span = batch.singular_batches(&self.l1_blocks, parent)?.into();
+                if self.next_span.is_empty() {
+                    eyre::bail!("accepted span batch has no blocks after the safe head");
+                }
+                let batch = self.pop_span_batch(parent);
+                Ok(Step::Batch(batch, self.next_span.is_empty()))
+            }
+            None if out_of_data => Ok(Step::Eof),
+            None => Ok(Step::NotEnoughData),
+        }
+    }
+
+    /// Takes the next batch of the current span, building it on the given parent.
+    fn pop_span_batch(&mut self, parent: &L2BlockInfo) -> SingularBatch {
+        let mut batch = self
+            .next_span
+            .pop_front()
+            .expect("span batches are not empty");
+        batch.parent_hash = parent.hash;
+        batch
+    }
+
+    /// Keeps a batch read from the current origin unless it is invalid.
+    fn add_batch(&mut self, batch: Batch, parent: &L2BlockInfo) -> Result<()> {
+        if self.l1_blocks.is_empty() {
+            eyre::bail!("cannot add a batch without an L1 origin");
+        }
+        let validity = check_batch(
+            &self.config,
+            &self.l1_blocks,
+            parent,
+            &batch,
+            &self.origin,
+            &mut self.fetcher,
+        )?;
+        if validity != BatchValidity::Drop {
+            self.batches.push(IncludedBatch {
+                batch,
+                inclusion: self.origin,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the next batch to apply on top of the given safe head, if one can
+    /// be derived yet, generating an empty batch if the epoch's sequencing window
+    /// expired.
+    fn derive_next_batch(
+        &mut self,
+        out_of_data: bool,
+        parent: &L2BlockInfo,
+    ) -> Result<Option<Batch>> {
+        let Some(&epoch) = self.l1_blocks.first() else {
+            eyre::bail!("cannot derive a batch without an L1 origin");
+        };
+        if parent.l1_origin != epoch.id() && parent.l1_origin.number + 1 != epoch.number {
+            eyre::bail!(
+                "batch queue epoch {} does not match the safe head's L1 origin {}",
+                epoch.number,
+                parent.l1_origin.number
+            );
+        }
+
+        let mut next = None;
+        let mut remaining = Vec::new();
+        let mut candidates = std::mem::take(&mut self.batches).into_iter();
+        while let Some(candidate) = candidates.next() {
+            let validity = check_batch(
+                &self.config,
+                &self.l1_blocks,
+                parent,
+                &candidate.batch,
+                &candidate.inclusion,
+                &mut self.fetcher,
+            )?;
+            match validity {
+                BatchValidity::Future => remaining.push(candidate),
+                BatchValidity::Drop => {}
+                BatchValidity::Accept => {
+                    next = Some(candidate.batch);
+                    break;
+                }
+                BatchValidity::Undecided => {
+                    remaining.push(candidate);
+                    remaining.extend(candidates);
+                    self.batches = remaining;
+                    return Ok(None);
+                }
+            }
+        }
+        remaining.extend(candidates);
+        self.batches = remaining;
+        if next.is_some() {
+            return Ok(next);
+        }
+
+        let expiry = epoch.number + self.config.seq_window_size;
+        let expired = expiry < self.origin.number || (expiry == self.origin.number && out_of_data);
+        if !expired {
+            return Ok(None);
+        }
+        let Some(&next_epoch) = self.l1_blocks.get(1) else {
+            return Ok(None);
+        };
+        let next_timestamp = parent.timestamp + self.config.block_time;
+        let first_of_epoch = epoch.number == parent.l1_origin.number + 1;
+        if next_timestamp < next_epoch.timestamp || first_of_epoch {
+            tracing::info!(
+                target: "palmtop::batch_queue",
+                "Generating an empty batch for epoch {} at {}",
+                epoch.number,
+                next_timestamp
+            );
+            return Ok(Some(Batch::Singular(SingularBatch {
+                parent_hash: parent.hash,
+                epoch_num: epoch.number,
+                epoch_hash: epoch.hash,
+                timestamp: next_timestamp,
+                transactions: Vec::new(),
+            })));
+        }
+        self.l1_blocks.remove(0);
+        Ok(None)
+    }
+}
+
+/// The outcome of a single step of the batch queue.
+#[derive(Debug)]
+enum Step {
+    /// The next batch, with whether it is the last of its span.
+    Batch(SingularBatch, bool),
+    /// The step made progress without finding a batch.
+    NotEnoughData,
+    /// The queue needs the next L1 block.
+    Eof,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span_batch::SpanBatchElement;
+    use alloy_primitives::{keccak256, FixedBytes};
+    use std::collections::BTreeMap;
+
+    // The tests below are ported case by case from op-node's pre-Holocene
+    // `TestValidBatch` (op-node/rollup/derive/batches_test.go) and batch queue
+    // tests (batch_queue_test.go). The hashes and transactions op-node draws at
+    // random are fixed here. Where op-node leaves a batch undecided because the
+    // safe chain could not be fetched, the check fails instead.
+
+    /// Returns the rollup config of the tests, with Delta at the given time and
+    /// the later forks inactive.
+    fn config(
+        genesis_time: u64,
+        seq_window_size: u64,
+        max_sequencer_drift: u64,
+        delta_time: Option<u64>,
+    ) -> Arc<RollupConfig> {
+        let mut config = RollupConfig::preset("optimism").unwrap();
+        config.genesis.l2_time = genesis_time;
+        config.block_time = 2;
+        config.seq_window_size = seq_window_size;
+        config.max_sequencer_drift = max_sequencer_drift;
+        config.canyon_time = Some(0);
+        config.delta_time = delta_time;
+        config.ecotone_time = None;
+        config.fjord_time = None;
+        config.granite_time = None;
+        config.holocene_time = None;
+        config.validate().unwrap();
+        Arc::new(config)
+    }
+
+    /// A safe L2 chain in memory. Blocks without transactions fail to fetch them.
+    #[derive(Debug, Default)]
+    struct SafeChain {
+        blocks: BTreeMap<u64, L2BlockInfo>,
+        transactions: BTreeMap<u64, Vec<Bytes>>,
+    }
+
+    impl SafeChain {
+        /// Adds a block holding an L1 info deposit and the given transactions.
+        fn insert(&mut self, block: L2BlockInfo, transactions: &[Bytes]) {
+            let mut txs = vec![Bytes::from_static(&[DEPOSIT_TX_TYPE, 0x01])];
+            txs.extend_from_slice(transactions);
+            self.blocks.insert(block.number, block);
+            self.transactions.insert(block.number, txs);
+        }
+    }
+
+    impl SafeBlockFetcher for SafeChain {
+        fn block_info(&mut self, number: u64) -> Result<L2BlockInfo> {
+            self.blocks
+                .get(&number)
+                .copied()
+                .ok_or_else(|| eyre::eyre!("block {number} is not known"))
+        }
+
+        fn transactions(&mut self, number: u64) -> Result<Vec<Bytes>> {
+            self.transactions
+                .get(&number)
+                .cloned()
+                .ok_or_else(|| eyre::eyre!("transactions of block {number} are not known"))
+        }
+    }
+
+    /// Returns the span batch of the given singular batches, like op-node's
+    /// `initializedSpanBatch`.
+    fn span_batch(batches: &[SingularBatch]) -> Batch {
+        let (first, last) = (&batches[0], &batches[batches.len() - 1]);
+        Batch::Span(SpanBatch {
+            parent_check: FixedBytes::from_slice(&first.parent_hash[..20]),
+            l1_origin_check: FixedBytes::from_slice(&last.epoch_hash[..20]),
+            elements: batches
+                .iter()
+                .map(|batch| SpanBatchElement {
+                    epoch_num: batch.epoch_num,
+                    timestamp: batch.timestamp,
+                    transactions: batch.transactions.clone(),
+                })
+                .collect(),
+        })
+    }
+
+    /// Returns a hash op-node draws at random, fixed by the given name.
+    fn random_hash(name: &str) -> B256 {
+        keccak256(name)
+    }
+
+    /// Returns the L1 block the given number of seconds after the given one.
+    fn next_l1(name: &str, parent: &L1BlockInfo, seconds: u64) -> L1BlockInfo {
+        L1BlockInfo {
+            hash: random_hash(name),
+            number: parent.number + 1,
+            parent_hash: parent.hash,
+            timestamp: parent.timestamp + seconds,
+        }
+    }
+
+    /// Returns the L2 block after the given one, on the given L1 origin.
+    fn next_l2(name: &str, parent: &L2BlockInfo, origin: &L1BlockInfo) -> L2BlockInfo {
+        L2BlockInfo {
+            hash: random_hash(name),
+            number: parent.number + 1,
+            timestamp: parent.timestamp + 2,
+            l1_origin: origin.id(),
+        }
+    }
+
+    /// Returns the batch that builds the given block on the given parent hash.
+    fn batch_of(block: &L2BlockInfo, parent_hash: B256, transactions: &[Bytes]) -> SingularBatch {
+        SingularBatch {
+            parent_hash,
+            epoch_num: block.l1_origin.number,
+            epoch_hash: block.l1_origin.hash,
+            timestamp: block.timestamp,
+            transactions: transactions.to_vec(),
+        }
+    }
+
+    /// A case of op-node's `TestValidBatch`, which expects no validity where
+    /// fetching the safe chain fails.
+    struct ValidBatchCase {
+        name: &'static str,
+        l1_blocks: Vec<L1BlockInfo>,
+        safe_head: L2BlockInfo,
+        inclusion: L1BlockInfo,
+        batch: Batch,
+        expected: Option<BatchValidity>,
+    }
+
+    #[test]
+    fn test_valid_batch() {
+        use BatchValidity::*;
+
+        let l1a = L1BlockInfo {
+            hash: random_hash("l1A"),
+            number: 10,
+            parent_hash: random_hash("l1A parent"),
+            timestamp: 1000,
+        };
+        let l1b = next_l1("l1B", &l1a, 7);
+        let l1c = next_l1("l1C", &l1b, 7);
+        let l1d = next_l1("l1D", &l1c, 7);
+        let l1e = next_l1("l1E", &l1d, 7);
+        let l1f = next_l1("l1F", &l1e, 7);
+
+        let l2a0 = L2BlockInfo {
+            hash: random_hash("l2A0"),
+            number: 100,
+            timestamp: l1a.timestamp,
+            l1_origin: l1a.id(),
+        };
+        let l2a1 = next_l2("l2A1", &l2a0, &l1a);
+        let l2a2 = next_l2("l2A2", &l2a1, &l1a);
+        let l2a3 = next_l2("l2A3", &l2a2, &l1a);
+        let l2b0 = next_l2("l2B0", &l2a3, &l1b);
+
+        let l1x = L1BlockInfo {
+            hash: random_hash("l1X"),
+            number: 42,
+            parent_hash: random_hash("l1X parent"),
+            timestamp: 10_000,
+        };
+        let l1y = next_l1("l1Y", &l1x, 12);
+        let l1z = next_l1("l1Z", &l1y, 12);
+        let l2x0 = L2BlockInfo {
+            hash: random_hash("l2X0"),
+            number: 1000,
+            timestamp: 10_000 + 12 + 6 - 1,
+            l1_origin: l1x.id(),
+        };
+        let l2y0 = next_l2("l2Y0", &l2x0, &l1y);
+
+        // 4 * 2 = 8 seconds after l1A: past the sequencer drift, but still
+        // before the next epoch.
+        let l2a4 = next_l2("l2A4", &l2a3, &l1a);
+        let l1b_late = L1BlockInfo {
+            hash: random_hash("l1BLate"),
+            number: l1a.number + 1,
+            parent_hash: l1a.hash,
+            timestamp: l2a4.timestamp + 1,
+        };
+
+        let rand_txs = [Bytes::from_static(&[0x02, 0x72, 0x61, 0x6e, 0x64])];
+        let valid_txs = [
+            Bytes::from_static(&[0x02, 0x42, 0x13, 0x37]),
+            Bytes::from_static(&[0x02, 0xde, 0xad, 0xbe, 0xef]),
+        ];
+
+        let mut chain = SafeChain::default();
+        for block in [&l2a0, &l2a1, &l2a2] {
+            chain.insert(*block, &rand_txs);
+        }
+        // The transactions of l2A3 fail to fetch, and so does block 99 before l2A0.
+        chain.blocks.insert(l2a3.number, l2a3);
+
+        let singular_cases = vec![
+            ValidBatchCase {
+                name: "missing L1 info",
+                l1_blocks: vec![],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: Batch::Singular(batch_of(&l2a1, l2a0.hash, &[])),
+                expected: Some(Undecided),
+            },
+            ValidBatchCase {
+                name: "future timestamp",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: Batch::Singular(SingularBatch {
+                    timestamp: l2a1.timestamp + 1,
+                    ..batch_of(&l2a1, l2a0.hash, &[])
+                }),
+                expected: Some(Future),
+            },
+            ValidBatchCase {
+                name: "old timestamp",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: Batch::Singular(SingularBatch {
+                    timestamp: l2a0.timestamp,
+                    ..batch_of(&l2a1, l2a0.hash, &[])
+                }),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "misaligned timestamp",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: Batch::Singular(SingularBatch {
+                    timestamp: l2a1.timestamp - 1,
+                    ..batch_of(&l2a1, l2a0.hash, &[])
+                }),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "invalid parent block hash",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: Batch::Singular(batch_of(&l2a1, random_hash("invalid parent"), &[])),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "sequence window expired",
+                l1_blocks: vec![l1a, l1b, l1c, l1d, l1e, l1f],
+                safe_head: l2a0,
+                inclusion: l1f,
+                batch: Batch::Singular(batch_of(&l2a1, l2a0.hash, &[])),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "epoch too old, but good parent hash and timestamp",
+                l1_blocks: vec![l1b, l1c, l1d],
+                safe_head: l2b0,
+                inclusion: l1c,
+                batch: Batch::Singular(SingularBatch {
+                    parent_hash: l2b0.hash,
+                    epoch_num: l2a3.l1_origin.number,
+                    epoch_hash: l2a3.l1_origin.hash,
+                    timestamp: l2b0.timestamp + 2,
+                    transactions: vec![],
+                }),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "insufficient L1 info for eager derivation",
+                l1_blocks: vec![l1a],
+                safe_head: l2a3,
+                inclusion: l1c,
+                batch: Batch::Singular(batch_of(&l2b0, l2a3.hash, &[])),
+                expected: Some(Undecided),
+            },
+            ValidBatchCase {
+                name: "epoch too new",
+                l1_blocks: vec![l1a, l1b, l1c, l1d],
+                safe_head: l2a3,
+                inclusion: l1d,
+                batch: Batch::Singular(SingularBatch {
+                    epoch_num: l1c.number,
+                    epoch_hash: l1c.hash,
+                    ..batch_of(&l2b0, l2a3.hash, &[])
+                }),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "epoch hash wrong",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a3,
+                inclusion: l1c,
+                batch: Batch::Singular(SingularBatch {
+                    epoch_hash: l1a.hash,
+                    ..batch_of(&l2b0, l2a3.hash, &[])
+                }),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on same epoch with non-empty txs",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a3,
+                inclusion: l1b,
+                batch: Batch::Singular(batch_of(&l2a4, l2a3.hash, &rand_txs)),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on changing epoch with non-empty txs",
+                l1_blocks: vec![l1x, l1y, l1z],
+                safe_head: l2x0,
+                inclusion: l1z,
+                batch: Batch::Singular(batch_of(&l2y0, l2x0.hash, &rand_txs)),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on same epoch with empty txs and late next epoch",
+                l1_blocks: vec![l1a, l1b_late],
+                safe_head: l2a3,
+                inclusion: l1b_late,
+                batch: Batch::Singular(batch_of(&l2a4, l2a3.hash, &[])),
+                expected: Some(Accept),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on changing epoch with empty txs",
+                l1_blocks: vec![l1x, l1y, l1z],
+                safe_head: l2x0,
+                inclusion: l1z,
+                batch: Batch::Singular(batch_of(&l2y0, l2x0.hash, &[])),
+                expected: Some(Accept),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on same epoch with empty txs and no next epoch in \
+                       sight yet",
+                l1_blocks: vec![l1a],
+                safe_head: l2a3,
+                inclusion: l1b,
+                batch: Batch::Singular(batch_of(&l2a4, l2a3.hash, &[])),
+                expected: Some(Undecided),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on same epoch with empty txs and but in-sight epoch \
+                       that invalidates it",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a3,
+                inclusion: l1c,
+                batch: Batch::Singular(batch_of(&l2a4, l2a3.hash, &[])),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "empty tx included",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: Batch::Singular(batch_of(&l2a1, l2a0.hash, &[Bytes::new()])),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "deposit tx included",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: Batch::Singular(batch_of(
+                    &l2a1,
+                    l2a0.hash,
+                    &[Bytes::from_static(&[DEPOSIT_TX_TYPE, 0x09, 0x13, 0x37])],
+                )),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "valid batch same epoch",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: Batch::Singular(batch_of(&l2a1, l2a0.hash, &valid_txs)),
+                expected: Some(Accept),
+            },
+            ValidBatchCase {
+                name: "valid batch changing epoch",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a3,
+                inclusion: l1c,
+                batch: Batch::Singular(batch_of(&l2b0, l2a3.hash, &valid_txs)),
+                expected: Some(Accept),
+            },
+            ValidBatchCase {
+                name: "batch with L2 time before L1 time",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a2,
+                inclusion: l1b,
+                batch: Batch::Singular(SingularBatch {
+                    parent_hash: l2a2.hash,
+                    epoch_num: l1b.number,
+                    epoch_hash: l1b.hash,
+                    timestamp: l2a2.timestamp + 2,
+                    transactions: vec![],
+                }),
+                expected: Some(Drop),
+            },
+        ];
+
+        let span_cases = vec![
+            ValidBatchCase {
+                name: "missing L1 info",
+                l1_blocks: vec![],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: span_batch(&[batch_of(&l2a1, l2a0.hash, &[])]),
+                expected: Some(Undecided),
+            },
+            ValidBatchCase {
+                name: "future timestamp",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: span_batch(&[SingularBatch {
+                    timestamp: l2a1.timestamp + 1,
+                    ..batch_of(&l2a1, l2a0.hash, &[])
+                }]),
+                expected: Some(Future),
+            },
+            ValidBatchCase {
+                name: "misaligned timestamp",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: span_batch(&[SingularBatch {
+                    timestamp: l2a1.timestamp - 1,
+                    ..batch_of(&l2a1, l2a0.hash, &[])
+                }]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "invalid parent block hash",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: span_batch(&[batch_of(&l2a1, random_hash("invalid parent"), &[])]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "sequence window expired",
+                l1_blocks: vec![l1a, l1b, l1c, l1d, l1e, l1f],
+                safe_head: l2a0,
+                inclusion: l1f,
+                batch: span_batch(&[batch_of(&l2a1, l2a0.hash, &[])]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "epoch too old, but good parent hash and timestamp",
+                l1_blocks: vec![l1b, l1c, l1d],
+                safe_head: l2b0,
+                inclusion: l1c,
+                batch: span_batch(&[
+                    SingularBatch {
+                        parent_hash: l2b0.hash,
+                        epoch_num: l2a3.l1_origin.number,
+                        epoch_hash: l2a3.l1_origin.hash,
+                        timestamp: l2b0.timestamp + 2,
+                        transactions: vec![],
+                    },
+                    // The epoch hash passes the L1 origin check.
+                    SingularBatch {
+                        epoch_num: l1b.number,
+                        epoch_hash: l1b.hash,
+                        timestamp: l2b0.timestamp + 4,
+                        ..Default::default()
+                    },
+                ]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "insufficient L1 info for eager derivation",
+                l1_blocks: vec![l1a],
+                safe_head: l2a3,
+                inclusion: l1c,
+                batch: span_batch(&[batch_of(&l2b0, l2a3.hash, &[])]),
+                expected: Some(Undecided),
+            },
+            ValidBatchCase {
+                name: "insufficient L1 info for eager derivation - long span",
+                l1_blocks: vec![l1a],
+                safe_head: l2a2,
+                inclusion: l1c,
+                batch: span_batch(&[
+                    batch_of(&l2a3, l2a2.hash, &[]),
+                    batch_of(&l2b0, l2a3.hash, &[]),
+                ]),
+                expected: Some(Undecided),
+            },
+            ValidBatchCase {
+                name: "epoch too new",
+                l1_blocks: vec![l1a, l1b, l1c, l1d],
+                safe_head: l2a3,
+                inclusion: l1d,
+                batch: span_batch(&[SingularBatch {
+                    epoch_num: l1c.number,
+                    epoch_hash: l1c.hash,
+                    ..batch_of(&l2b0, l2a3.hash, &[])
+                }]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "epoch hash wrong",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a3,
+                inclusion: l1c,
+                batch: span_batch(&[SingularBatch {
+                    epoch_hash: l1a.hash,
+                    ..batch_of(&l2b0, l2a3.hash, &[])
+                }]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "epoch hash wrong - long span",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a2,
+                inclusion: l1c,
+                batch: span_batch(&[
+                    batch_of(&l2a3, l2a2.hash, &[]),
+                    SingularBatch {
+                        epoch_hash: l1a.hash,
+                        ..batch_of(&l2b0, l2a3.hash, &[])
+                    },
+                ]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on same epoch with non-empty txs",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a3,
+                inclusion: l1b,
+                batch: span_batch(&[batch_of(&l2a4, l2a3.hash, &rand_txs)]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on same epoch with non-empty txs - long span",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a2,
+                inclusion: l1b,
+                batch: span_batch(&[
+                    batch_of(&l2a3, l2a2.hash, &rand_txs),
+                    batch_of(&l2a4, l2a3.hash, &rand_txs),
+                ]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on changing epoch with non-empty txs",
+                l1_blocks: vec![l1x, l1y, l1z],
+                safe_head: l2x0,
+                inclusion: l1z,
+                batch: span_batch(&[batch_of(&l2y0, l2x0.hash, &rand_txs)]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on same epoch with empty txs and late next epoch",
+                l1_blocks: vec![l1a, l1b_late],
+                safe_head: l2a3,
+                inclusion: l1b_late,
+                batch: span_batch(&[batch_of(&l2a4, l2a3.hash, &[])]),
+                expected: Some(Accept),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on changing epoch with empty txs",
+                l1_blocks: vec![l1x, l1y, l1z],
+                safe_head: l2x0,
+                inclusion: l1z,
+                batch: span_batch(&[batch_of(&l2y0, l2x0.hash, &[])]),
+                expected: Some(Accept),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on same epoch with empty txs and no next epoch in \
+                       sight yet",
+                l1_blocks: vec![l1a],
+                safe_head: l2a3,
+                inclusion: l1b,
+                batch: span_batch(&[batch_of(&l2a4, l2a3.hash, &[])]),
+                expected: Some(Undecided),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on same epoch with empty txs and no next epoch in \
+                       sight yet - long span",
+                l1_blocks: vec![l1a],
+                safe_head: l2a2,
+                inclusion: l1b,
+                batch: span_batch(&[
+                    batch_of(&l2a3, l2a2.hash, &[]),
+                    batch_of(&l2a4, l2a3.hash, &[]),
+                ]),
+                expected: Some(Undecided),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on same epoch with empty txs and but in-sight epoch \
+                       that invalidates it",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a3,
+                inclusion: l1c,
+                batch: span_batch(&[batch_of(&l2a4, l2a3.hash, &[])]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "sequencer time drift on same epoch with empty txs and but in-sight epoch \
+                       that invalidates it - long span",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a2,
+                inclusion: l1c,
+                batch: span_batch(&[
+                    batch_of(&l2a3, l2a2.hash, &[]),
+                    batch_of(&l2a4, l2a3.hash, &[]),
+                ]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "empty tx included",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: span_batch(&[batch_of(&l2a1, l2a0.hash, &[Bytes::new()])]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "deposit tx included",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: span_batch(&[batch_of(
+                    &l2a1,
+                    l2a0.hash,
+                    &[Bytes::from_static(&[DEPOSIT_TX_TYPE, 0x09, 0x13, 0x37])],
+                )]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "valid batch same epoch",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: span_batch(&[batch_of(&l2a1, l2a0.hash, &valid_txs)]),
+                expected: Some(Accept),
+            },
+            ValidBatchCase {
+                name: "valid batch changing epoch",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a3,
+                inclusion: l1c,
+                batch: span_batch(&[batch_of(&l2b0, l2a3.hash, &valid_txs)]),
+                expected: Some(Accept),
+            },
+            ValidBatchCase {
+                name: "batch with L2 time before L1 time",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a2,
+                inclusion: l1b,
+                batch: span_batch(&[SingularBatch {
+                    parent_hash: l2a2.hash,
+                    epoch_num: l1b.number,
+                    epoch_hash: l1b.hash,
+                    timestamp: l2a2.timestamp + 2,
+                    transactions: vec![],
+                }]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "batch with L2 time before L1 time - long span",
+                l1_blocks: vec![l1a, l1b, l1c],
+                safe_head: l2a1,
+                inclusion: l1b,
+                batch: span_batch(&[
+                    batch_of(&l2a2, l2a1.hash, &[]),
+                    SingularBatch {
+                        epoch_num: l1b.number,
+                        epoch_hash: l1b.hash,
+                        timestamp: l2a2.timestamp + 2,
+                        ..Default::default()
+                    },
+                ]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "valid overlapping batch",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a2,
+                inclusion: l1b,
+                batch: span_batch(&[
+                    batch_of(&l2a2, l2a1.hash, &rand_txs),
+                    batch_of(&l2a3, l2a2.hash, &rand_txs),
+                ]),
+                expected: Some(Accept),
+            },
+            ValidBatchCase {
+                name: "longer overlapping batch",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a2,
+                inclusion: l1b,
+                batch: span_batch(&[
+                    batch_of(&l2a1, l2a0.hash, &rand_txs),
+                    batch_of(&l2a2, l2a1.hash, &rand_txs),
+                    batch_of(&l2a3, l2a2.hash, &rand_txs),
+                ]),
+                expected: Some(Accept),
+            },
+            ValidBatchCase {
+                name: "fully overlapping batch",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a2,
+                inclusion: l1b,
+                batch: span_batch(&[
+                    batch_of(&l2a1, l2a0.hash, &rand_txs),
+                    batch_of(&l2a2, l2a1.hash, &rand_txs),
+                ]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "overlapping batch with invalid parent hash",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a2,
+                inclusion: l1b,
+                batch: span_batch(&[
+                    batch_of(&l2a2, l2a0.hash, &rand_txs),
+                    batch_of(&l2a3, l2a2.hash, &rand_txs),
+                ]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "overlapping batch with invalid origin number",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a2,
+                inclusion: l1b,
+                batch: span_batch(&[
+                    SingularBatch {
+                        epoch_num: l2a2.l1_origin.number + 1,
+                        ..batch_of(&l2a2, l2a1.hash, &rand_txs)
+                    },
+                    batch_of(&l2a3, l2a2.hash, &rand_txs),
+                ]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "overlapping batch with invalid tx",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a2,
+                inclusion: l1b,
+                batch: span_batch(&[
+                    batch_of(&l2a2, l2a1.hash, &valid_txs),
+                    batch_of(&l2a3, l2a2.hash, &rand_txs),
+                ]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "overlapping batch l2 fetcher error",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a1,
+                inclusion: l1b,
+                batch: span_batch(&[
+                    batch_of(&l2a0, random_hash("l2A0 parent"), &rand_txs),
+                    batch_of(&l2a1, l2a0.hash, &rand_txs),
+                    batch_of(&l2a2, l2a1.hash, &rand_txs),
+                ]),
+                expected: None,
+            },
+            ValidBatchCase {
+                name: "short block time",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: span_batch(&[
+                    SingularBatch {
+                        timestamp: l2a0.timestamp + 1,
+                        ..batch_of(&l2a1, l2a0.hash, &rand_txs)
+                    },
+                    SingularBatch {
+                        timestamp: l2a1.timestamp + 1,
+                        ..batch_of(&l2a2, l2a1.hash, &rand_txs)
+                    },
+                ]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "misaligned batch",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a0,
+                inclusion: l1b,
+                batch: span_batch(&[
+                    SingularBatch {
+                        timestamp: l2a0.timestamp - 1,
+                        ..batch_of(&l2a1, l2a0.hash, &rand_txs)
+                    },
+                    batch_of(&l2a2, l2a1.hash, &rand_txs),
+                ]),
+                expected: Some(Drop),
+            },
+            ValidBatchCase {
+                name: "failed to fetch overlapping block payload",
+                l1_blocks: vec![l1a, l1b],
+                safe_head: l2a3,
+                inclusion: l1b,
+                batch: span_batch(&[
+                    batch_of(&l2a3, l2a2.hash, &rand_txs),
+                    batch_of(&l2b0, l2a3.hash, &rand_txs),
+                ]),
+                expected: None,
+            },
+        ];
+
+        // The cases checking whether Delta is active, with Delta at the given time.
+        let fork_cases = [
+            (
+                "singular batch before hard fork",
+                l1b.timestamp,
+                Batch::Singular(batch_of(&l2a1, l2a0.hash, &rand_txs)),
+                Accept,
+            ),
+            (
+                "span batch before hard fork",
+                l1b.timestamp,
+                span_batch(&[batch_of(&l2a1, l2a0.hash, &rand_txs)]),
+                Drop,
+            ),
+            (
+                "singular batch after hard fork",
+                l1a.timestamp,
+                Batch::Singular(batch_of(&l2a1, l2a0.hash, &rand_txs)),
+                Accept,
+            ),
+            (
+                "span batch after hard fork",
+                l1a.timestamp,
+                span_batch(&[batch_of(&l2a1, l2a0.hash, &rand_txs)]),
+                Accept,
+            ),
+        ];
+
+        let tables = [(None, singular_cases), (Some(0), span_cases)];
+        for (delta_time, cases) in tables {
+            let config = config(31, 4, 6, delta_time);
+            for case in cases {
+                let validity = check_batch(
+                    &config,
+                    &case.l1_blocks,
+                    &case.safe_head,
+                    &case.batch,
+                    &case.inclusion,
+                    &mut chain,
+                );
+                assert_eq!(validity.ok(), case.expected, "{}", case.name);
+            }
+        }
+        for (name, delta_time, batch, expected) in fork_cases {
+            let config = config(31, 4, 6, Some(delta_time));
+            let validity =
+                check_batch(&config, &[l1a, l1b], &l2a0, &batch, &l1b, &mut chain).unwrap();
+            assert_eq!(validity, expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_check_batch_fjord_sequencer_drift() {
+        let l1 = l1_chain(&[1000, 1007]);
+        let safe_head = L2BlockInfo {
+            hash: mock_hash(1006, 2),
+            number: 3,
+            timestamp: 1006,
+            l1_origin: l1[0].id(),
+        };
+        let batch = Batch::Singular(b(1008, &l1[0]));
+        let mut config = config(1000, 4, 6, Some(0));
+        let check = |config: &RollupConfig| {
+            check_batch(
+                config,
+                &l1,
+                &safe_head,
+                &batch,
+                &l1[1],
+                &mut SafeChain::default(),
+            )
+            .unwrap()
+        };
+        assert_eq!(check(&config), BatchValidity::Drop);
+        let forks = Arc::make_mut(&mut config);
+        forks.ecotone_time = Some(0);
+        forks.fjord_time = Some(0);
+        forks.validate().unwrap();
+        assert_eq!(check(&config), BatchValidity::Accept);
+    }
+
+    /// Returns op-node's mock hash of the block at the given time on the given
+    /// layer, 1 for L1 and 2 for L2.
+    fn mock_hash(time: u64, layer: u8) -> B256 {
+        let mut hash = B256::ZERO;
+        hash[..8].copy_from_slice(&time.to_le_bytes());
+        hash[31] = layer;
+        hash
+    }
+
+    /// Returns L1 blocks at the given times, numbered from 0 like op-node's
+    /// `L1Chain`.
+    fn l1_chain(times: &[u64]) -> Vec<L1BlockInfo> {
+        let mut parent_hash = B256::ZERO;
+        (0..)
+            .zip(times)
+            .map(|(number, &timestamp)| {
+                let hash = mock_hash(timestamp, 1);
+                let block = L1BlockInfo {
+                    hash,
+                    number,
+                    parent_hash,
+                    timestamp,
+                };
+                parent_hash = hash;
+                block
+            })
+            .collect()
+    }
+
+    /// Returns the batch of the block at the given time on the given epoch, like
+    /// op-node's `b`.
+    fn b(timestamp: u64, epoch: &L1BlockInfo) -> SingularBatch {
+        SingularBatch {
+            parent_hash: mock_hash(timestamp - 2, 2),
+            epoch_num: epoch.number,
+            epoch_hash: epoch.hash,
+            timestamp,
+            transactions: vec![Bytes::from(vec![0x02, timestamp as u8])],
+        }
+    }
+
+    /// Returns the given batches as singular batches, or as span batches of the
+    /// given numbers of blocks like op-node's `buildSpanBatches`.
+    fn batches(batches: &[SingularBatch], span_counts: Option<&[usize]>) -> Vec<Batch> {
+        let Some(counts) = span_counts else {
+            return batches.iter().cloned().map(Batch::Singular).collect();
+        };
+        let mut start = 0;
+        counts
+            .iter()
+            .map(|count| {
+                start += count;
+                span_batch(&batches[start - count..start])
+            })
+            .collect()
+    }
+
+    /// Returns the genesis block at the given time, on the given L1 origin.
+    fn genesis(time: u64, l1_origin: &L1BlockInfo) -> L2BlockInfo {
+        L2BlockInfo {
+            hash: mock_hash(time, 2),
+            number: 0,
+            timestamp: time,
+            l1_origin: l1_origin.id(),
+        }
+    }
+
+    /// Returns a batch queue reset to the given L1 block, that first reads the
+    /// given batches from it like op-node's fake batch queue input does.
+    fn reset(
+        config: Arc<RollupConfig>,
+        base: L1BlockInfo,
+        batches: Vec<Batch>,
+    ) -> BatchQueue<SafeChain> {
+        let mut queue = BatchQueue::new(config, base, SafeChain::default());
+        queue.pending = batches.into();
+        queue
+    }
+
+    /// Returns the next batch of the queue, building its block on the safe head.
+    fn next_batch(
+        queue: &mut BatchQueue<SafeChain>,
+        safe_head: &mut L2BlockInfo,
+    ) -> Option<SingularBatch> {
+        let (batch, _) = queue.next_batch(safe_head).unwrap()?;
+        *safe_head = L2BlockInfo {
+            hash: mock_hash(batch.timestamp, 2),
+            number: safe_head.number + 1,
+            timestamp: batch.timestamp,
+            l1_origin: BlockId {
+                hash: batch.epoch_hash,
+                number: batch.epoch_num,
+            },
+        };
+        queue.fetcher_mut().insert(*safe_head, &batch.transactions);
+        Some(batch)
+    }
+
+    fn batch_queue_new_origin(delta_time: Option<u64>) {
+        let l1 = l1_chain(&[10, 15, 20, 25]);
+        let safe_head = L2BlockInfo {
+            timestamp: 20,
+            l1_origin: l1[2].id(),
+            ..genesis(10, &l1[0])
+        };
+        let mut queue = reset(config(10, 2, 600, delta_time), l1[0], vec![]);
+        assert_eq!(queue.l1_blocks(), [l1[0]]);
+
+        // The safe head's origin is ahead: there is no data, and the origin stays.
+        assert_eq!(queue.next_batch(&safe_head).unwrap(), None);
+        assert_eq!(queue.l1_blocks(), [l1[0]]);
+        assert_eq!(queue.origin, l1[0]);
+
+        // Still behind the safe head's origin, the L1 blocks are wiped.
+        queue.add_l1_block(l1[1], vec![]).unwrap();
+        assert_eq!(queue.next_batch(&safe_head).unwrap(), None);
+        assert!(queue.l1_blocks().is_empty());
+        assert_eq!(queue.origin, l1[1]);
+
+        // At the safe head's origin, it is the first L1 block.
+        queue.add_l1_block(l1[2], vec![]).unwrap();
+        assert_eq!(queue.next_batch(&safe_head).unwrap(), None);
+        assert_eq!(queue.l1_blocks(), [l1[2]]);
+        assert_eq!(queue.origin, l1[2]);
+    }
+
+    fn batch_queue_eager(span_counts: Option<&[usize]>) {
+        let l1 = l1_chain(&[10, 20, 30]);
+        let mut safe_head = genesis(10, &l1[0]);
+        let expected: Vec<_> = (12..=22).step_by(2).map(|time| b(time, &l1[0])).collect();
+        let config = config(10, 30, 600, span_counts.map(|_| 0));
+        let mut queue = reset(config, l1[0], vec![]);
+        queue
+            .add_l1_block(l1[1], batches(&expected, span_counts))
+            .unwrap();
+
+        for batch in expected {
+            assert_eq!(next_batch(&mut queue, &mut safe_head), Some(batch));
+        }
+        assert_eq!(next_batch(&mut queue, &mut safe_head), None);
+    }
+
+    fn batch_queue_invalid_internal_advance(delta_time: Option<u64>) {
+        let l1 = l1_chain(&[5, 10, 15, 20, 25, 30]);
+        let mut safe_head = genesis(10, &l1[0]);
+        let input = [b(12, &l1[0]), b(14, &l1[0]), b(16, &l1[0])];
+        let config = config(10, 2, 600, delta_time);
+        let mut queue = reset(config, l1[0], batches(&input, None));
+
+        // The continuous batches of epoch 0 are derived as they are read.
+        for batch in input {
+            assert_eq!(next_batch(&mut queue, &mut safe_head), Some(batch));
+        }
+        assert_eq!(next_batch(&mut queue, &mut safe_head), None);
+
+        // Origin 1 is not enough to move past epoch 0 yet.
+        queue.add_l1_block(l1[1], vec![]).unwrap();
+        assert_eq!(next_batch(&mut queue, &mut safe_head), None);
+
+        // At origin 2 the sequencing window of epoch 0 expires, which moves the
+        // queue on to epoch 1 first.
+        queue.add_l1_block(l1[2], vec![]).unwrap();
+        assert_eq!(next_batch(&mut queue, &mut safe_head), None);
+
+        // Each next origin generates one empty batch for the next epoch.
+        for (origin, epoch) in [(3, 1), (4, 2)] {
+            queue.add_l1_block(l1[origin], vec![]).unwrap();
+            let batch = next_batch(&mut queue, &mut safe_head).unwrap();
+            assert_eq!(batch.epoch_num, epoch);
+            assert!(batch.transactions.is_empty());
+            assert_eq!(next_batch(&mut queue, &mut safe_head), None);
+        }
+    }
+
+    fn batch_queue_missing(span_counts: Option<&[usize]>) {
+        let l1 = l1_chain(&[10, 15, 20, 25]);
+        let mut safe_head = genesis(10, &l1[0]);
+        // The batches at 12, 14 and 18 are missing.
+        let input = [b(16, &l1[0]), b(22, &l1[1])];
+        let config = config(10, 2, 600, span_counts.map(|_| 0));
+        let mut queue = reset(config, l1[0], batches(&input, span_counts));
+
+        // Both batches are for later blocks.
+        assert_eq!(next_batch(&mut queue, &mut safe_head), None);
+
+        // Origin 1 is not enough to fill the gap yet.
+        queue.add_l1_block(l1[1], vec![]).unwrap();
+        assert_eq!(next_batch(&mut queue, &mut safe_head), None);
+
+        // At origin 2 the sequencing window of epoch 0 expires, so empty batches
+        // fill the gap before the batch at 16.
+        queue.add_l1_block(l1[2], vec![]).unwrap();
+        for timestamp in [12, 14] {
+            let batch = next_batch(&mut queue, &mut safe_head).unwrap();
+            assert_eq!(batch.timestamp, timestamp);
+            assert_eq!(batch.epoch_num, 0);
+            assert!(batch.transactions.is_empty());
+        }
+        assert_eq!(
+            next_batch(&mut queue, &mut safe_head),
+            Some(input[0].clone())
+        );
+
+        // At origin 3 the queue first moves on to epoch 1, which then gets an
+        // empty batch at 18, as the batch at 22 is still ahead.
+        queue.add_l1_block(l1[3], vec![]).unwrap();
+        assert_eq!(next_batch(&mut queue, &mut safe_head), None);
+        let batch = next_batch(&mut queue, &mut safe_head).unwrap();
+        assert_eq!(batch.timestamp, 18);
+        assert_eq!(batch.epoch_num, 1);
+        assert!(batch.transactions.is_empty());
+    }
+
+    fn batch_queue_shuffle(span_counts: Option<&[usize]>, order: &[usize]) {
+        let l1 = l1_chain(&[10, 20, 30]);
+        let mut safe_head = genesis(10, &l1[0]);
+        let mut expected: Vec<_> = (12..=22).step_by(2).map(|time| b(time, &l1[0])).collect();
+        expected.push(b(24, &l1[1]));
+        let input = batches(&expected, span_counts);
+        let config = config(10, 30, 600, span_counts.map(|_| 0));
+        let mut queue = reset(config, l1[0], vec![]);
+        queue
+            .add_l1_block(l1[1], order.iter().map(|&i| input[i].clone()).collect())
+            .unwrap();
+
+        for batch in expected {
+            assert_eq!(next_batch(&mut queue, &mut safe_head), Some(batch));
+        }
+        assert_eq!(next_batch(&mut queue, &mut safe_head), None);
+    }
+
+    #[test]
+    fn test_batch_queue() {
+        for delta_time in [None, Some(0)] {
+            batch_queue_new_origin(delta_time);
+            batch_queue_invalid_internal_advance(delta_time);
+        }
+        batch_queue_eager(None);
+        batch_queue_eager(Some(&[1, 2, 3]));
+        batch_queue_missing(None);
+        batch_queue_missing(Some(&[1, 1]));
+        // op-node shuffles the batches at random; they are read here in a fixed
+        // shuffled order.
+        batch_queue_shuffle(None, &[5, 1, 6, 0, 3, 2, 4]);
+        batch_queue_shuffle(Some(&[2, 2, 3]), &[2, 0, 1]);
+    }
+
+    #[test]
+    fn test_batch_queue_overlapping_span_batch() {
+        let l1 = l1_chain(&[10, 20, 30]);
+        let mut safe_head = genesis(10, &l1[0]);
+        let expected: Vec<_> = (12..=22).step_by(2).map(|time| b(time, &l1[0])).collect();
+        // [12, 14, 16], [14, 16, 18], [16, 18, 20] and [18, 20, 22], each after
+        // the first overlapping two blocks of the one before.
+        let input = expected.windows(3).map(span_batch).collect();
+        let mut queue = reset(config(10, 30, 600, Some(0)), l1[0], vec![]);
+        queue.add_l1_block(l1[1], input).unwrap();
+
+        for batch in expected {
+            assert_eq!(next_batch(&mut queue, &mut safe_head), Some(batch));
+        }
+        assert_eq!(next_batch(&mut queue, &mut safe_head), None);
+    }
+
+    #[test]
+    fn test_span_batch_expansion() {
+        let l1 = l1_chain(&[90, 102]);
+        let batch = SpanBatch {
+            elements: (0..4)
+                .map(|i| SpanBatchElement {
+                    epoch_num: i / 2,
+                    timestamp: 100 + 2 * i,
+                    transactions: vec![],
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let safe_head = L2BlockInfo {
+            number: 50,
+            ..genesis(100, &l1[0])
+        };
+        let singular = batch.singular_batches(&l1, &safe_head).unwrap();
+        let expanded: Vec<_> = singular
+            .iter()
+            .map(|batch| (batch.epoch_num, batch.epoch_hash, batch.timestamp))
+            .collect();
+        assert_eq!(
+            expanded,
+            [
+                (0, l1[0].hash, 102),
+                (1, l1[1].hash, 104),
+                (1, l1[1].hash, 106)
+            ]
+        );
+        assert!(batch.singular_batches(&l1[..1], &safe_head).is_err());
+    }
+
+    #[test]
+    fn test_batch_queue_rejects_gaps_in_l1() {
+        let l1 = l1_chain(&[100, 110, 120]);
+        let mut queue = reset(config(100, 4, 6, None), l1[0], vec![]);
+        assert!(queue.add_l1_block(l1[2], vec![]).is_err());
+        queue.add_l1_block(l1[1], vec![]).unwrap();
+        assert_eq!(queue.l1_head(), l1[1]);
+    }
+
+    #[test]
+    fn test_batch_queue_rejects_l1_block_with_pending_batches() {
+        let l1 = l1_chain(&[100, 110, 120]);
+        let mut queue = reset(config(100, 4, 6, None), l1[0], vec![]);
+        let batch = Batch::Singular(SingularBatch::default());
+        queue.add_l1_block(l1[1], vec![batch]).unwrap();
+        assert!(queue.add_l1_block(l1[2], vec![]).is_err());
+        assert_eq!(queue.l1_head(), l1[1]);
+    }
+
+    #[test]
+    fn test_batch_queue_rejects_holocene() {
+        let l1 = l1_chain(&[100, 110]);
+        let mut config = config(100, 4, 6, None);
+        let forks = Arc::make_mut(&mut config);
+        for time in [
+            &mut forks.delta_time,
+            &mut forks.ecotone_time,
+            &mut forks.fjord_time,
+            &mut forks.granite_time,
+        ] {
+            *time = Some(0);
+        }
+        forks.holocene_time = Some(110);
+        forks.validate().unwrap();
+        let mut queue = reset(config, l1[0], vec![]);
+        let err = queue.add_l1_block(l1[1], vec![]).unwrap_err();
+        assert_eq!(err.downcast_ref::<Failure>(), Some(&Failure::Unsupported));
+    }
+}
@@ -0,0 +1,252 @@
+use alloy_primitives::B256;
+use eyre::Result;
+use serde_json::Value;
+
+use palmtop_preimage::client::OracleClient;
+use palmtop_primitives::{BootKey, Preimage, RollupConfig};
+
+/// ## BootInfo
+///
+/// BootInfo holds the trusted inputs of the program. Every field is loaded from
+/// the local preimage key of its [BootKey] and validated before use.
+///
+/// Like op-program, the L2 chain config is only loaded for custom chains: the
+/// chains of the rollup config presets need none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootInfo {
+    l1_head: B256,
+    l2_output_root: B256,
+    l2_claim: B256,
+    l2_claim_block_number: u64,
+    l2_chain_id: u64,
+    l2_chain_config: Option<Value>,
+    rollup_config: RollupConfig,
+}
+
+impl BootInfo {
+    /// Loads the [BootInfo] from the local preimage keys of the given oracle.
+    pub fn load(oracle: &mut impl OracleClient) -> Result<Self> {
+        let mut fetch = |key: BootKey| -> Result<Preimage> {
+            oracle
+                .get(key.key())
+                .map_err(|e| e.wrap_err(format!("failed to load boot key {key:?}")))
+        };
+        let l2_chain_id = parse_u64(BootKey::L2ChainId, fetch(BootKey::L2ChainId)?)?;
+        let l2_chain_config = match RollupConfig::is_preset_chain(l2_chain_id) {
+            true => None,
+            false => Some(parse_json(
+                BootKey::L2ChainConfig,
+                fetch(BootKey::L2ChainConfig)?,
+            )?),
+        };
+        let boot_info = Self {
+            l1_head: parse_hash(BootKey::L1Head, fetch(BootKey::L1Head)?)?,
+            l2_output_root: parse_hash(BootKey::L2OutputRoot, fetch(BootKey::L2OutputRoot)?)?,
+            l2_claim: parse_hash(BootKey::L2Claim, fetch(BootKey::L2Claim)?)?,
+            l2_claim_block_number: parse_u64(
+                BootKey::L2ClaimBlockNumber,
+                fetch(BootKey::L2ClaimBlockNumber)?,
+            )?,
+            l2_chain_id,
+            l2_chain_config,
+            rollup_config: RollupConfig::from_json(&fetch(BootKey::RollupConfig)?)
+                .map_err(|e| e.wrap_err("failed to parse boot key RollupConfig"))?,
+        };
+        boot_info.validate()?;
+        tracing::info!(target: "palmtop::boot", "Loaded boot info: {:?}", boot_info);
+        Ok(boot_info)
+    }
+
+    /// Checks that the loaded values are consistent with each other.
+    fn validate(&self) -> Result<()> {
+        if self.l1_head.is_zero() {
+            eyre::bail!("boot info L1 head must not be zero");
+        }
+        if self.l2_chain_id == 0 {
+            eyre::bail!("boot info L2 chain ID must not be zero");
+        }
+        let chain_id = self
+            .l2_chain_config
+            .as_ref()
+            .and_then(|config| config.get("chainId"));
+        if let Some(chain_id) = chain_id {
+            if chain_id.as_u64() != Some(self.l2_chain_id) {
+                eyre::bail!(
+                    "L2 chain config chain ID {} does not match boot info L2 chain ID {}",
+                    chain_id,
+                    self.l2_chain_id
+                );
+            }
+        }
+        if self.rollup_config.l2_chain_id != self.l2_chain_id {
+            eyre::bail!(
+                "rollup config L2 chain ID {} does not match boot info L2 chain ID {}",
+                self.rollup_config.l2_chain_id,
+                self.l2_chain_id
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the L1 head block hash.
+    pub fn l1_head(&self) -> B256 {
+        self.l1_head
+    }
+
+    /// Returns the agreed upon L2 output root.
+    pub fn l2_output_root(&self) -> B256 {
+        self.l2_output_root
+    }
+
+    /// Returns the disputed L2 output root claim.
+    pub fn l2_claim(&self) -> B256 {
+        self.l2_claim
+    }
+
+    /// Returns the L2 block number of the disputed claim.
+    pub fn l2_claim_block_number(&self) -> u64 {
+        self.l2_claim_block_number
+    }
+
+    /// Returns the L2 chain ID.
+    pub fn l2_chain_id(&self) -> u64 {
+        self.l2_chain_id
+    }
+
+    /// Returns the L2 chain config, which is only loaded for custom chains.
+    pub fn l2_chain_config(&self) -> Option<&Value> {
+        self.l2_chain_config.as_ref()
+    }
+
+    /// Returns the rollup config.
+    pub fn rollup_config(&self) -> &RollupConfig {
+        &self.rollup_config
+    }
+}
+
+/// Parses a 32 byte hash boot value.
+fn parse_hash(key: BootKey, value: Preimage) -> Result<B256> {
+    if value.len() != 32 {
+        eyre::bail!("boot key {key:?} must be 32 bytes, got {}", value.len());
+    }
+    Ok(B256::from_slice(&value))
+}
+
+/// Parses a big-endian u64 boot value.
+fn parse_u64(key: BootKey, value: Preimage) -> Result<u64> {
+    let bytes: [u8; 8] = value
+        .as_slice()
+        .try_into()
+        .map_err(|_| eyre::eyre!("boot key {key:?} must be 8 bytes, got {}", value.len()))?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// Parses a JSON object boot value.
+fn parse_json(key: BootKey, value: Preimage) -> Result<Value> {
+    let json: Value = serde_json::from_slice(&value)
+        .map_err(|e| eyre::eyre!("boot key {key:?} is not valid JSON: {e}"))?;
+    if !json.is_object() {
+        eyre::bail!("boot key {key:?} must be a JSON object");
+    }
+    Ok(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use palmtop_primitives::PreimageKey;
+    use std::collections::HashMap;
+
+    struct MapOracle(HashMap<PreimageKey, Preimage>);
+
+    impl OracleClient for MapOracle {
+        fn get(&mut self, key: PreimageKey) -> Result<Preimage> {
+            self.0
+                .get(&key)
+                .cloned()
+                .ok_or_else(|| eyre::eyre!("missing key"))
+        }
+    }
+
+    fn rollup_config(preset: &str) -> Vec<u8> {
+        RollupConfig::preset(preset).unwrap().to_json()
+    }
+
+    fn oracle() -> MapOracle {
+        let values = [
+            (BootKey::L1Head, [1u8; 32].to_vec()),
+            (BootKey::L2OutputRoot, [2u8; 32].to_vec()),
+            (BootKey::L2Claim, [3u8; 32].to_vec()),
+            (BootKey::L2ClaimBlockNumber, 100u64.to_be_bytes().to_vec()),
+            (BootKey::L2ChainId, 10u64.to_be_bytes().to_vec()),
+            (BootKey::RollupConfig, rollup_config("optimism")),
+        ];
+        MapOracle(values.into_iter().map(|(k, v)| (k.key(), v)).collect())
+    }
+
+    #[test]
+    fn test_load_boot_info() {
+        let boot_info = BootInfo::load(&mut oracle()).expect("Should not error");
+        assert_eq!(boot_info.l1_head(), B256::repeat_byte(1));
+        assert_eq!(boot_info.l2_output_root(), B256::repeat_byte(2));
+        assert_eq!(boot_info.l2_claim(), B256::repeat_byte(3));
+        assert_eq!(boot_info.l2_claim_block_number(), 100);
+        assert_eq!(boot_info.l2_chain_id(), 10);
+        assert_eq!(boot_info.rollup_config().l2_chain_id, 10);
+    }
+
+    #[test]
+    fn test_boot_info_rejects_malformed_values() {
+        let mut oracle = oracle();
+        oracle.0.insert(BootKey::L2Claim.key(), vec![3u8; 31]);
+        assert!(BootInfo::load(&mut oracle).is_err());
+    }
+
+    #[test]
+    fn test_boot_info_rejects_mismatched_chain_id() {
+        let mut oracle = oracle();
+        oracle
+            .0
+            .insert(BootKey::RollupConfig.key(), rollup_config("base"));
+        assert!(BootInfo::load(&mut oracle).is_err());
+
+        oracle.0.insert(
+            BootKey::RollupConfig.key(),
+            br#"{"l2_chain_id":10}"#.to_vec(),
+        );
+        assert!(BootInfo::load(&mut oracle).is_err());
+    }
+
+    #[test]
+    fn test_boot_info_missing_key() {
+        let mut oracle = oracle();
+        oracle.0.remove(&BootKey::L2Claim.key());
+        assert!(BootInfo::load(&mut oracle).is_err());
+    }
+
+    #[test]
+    fn test_boot_info_chain_config_only_for_custom_chains() {
+        let mut oracle = oracle();
+        assert_eq!(BootInfo::load(&mut oracle).unwrap().l2_chain_config(), None);
+
+        let mut custom = RollupConfig::preset("optimism").unwrap();
+        custom.l2_chain_id = 12345;
+        oracle
+            .0
+            .insert(BootKey::RollupConfig.key(), custom.to_json());
+        oracle
+            .0
+            .insert(BootKey::L2ChainId.key(), 12345u64.to_be_bytes().to_vec());
+        assert!(BootInfo::load(&mut oracle).is_err());
+
+        oracle
+            .0
+            .insert(BootKey::L2ChainConfig.key(), br#"{"chainId":10}"#.to_vec());
+        assert!(BootInfo::load(&mut oracle).is_err());
+
+        let chain_config = br#"{"chainId":12345}"#.to_vec();
+        oracle.0.insert(BootKey::L2ChainConfig.key(), chain_config);
+        let boot_info = BootInfo::load(&mut oracle).unwrap();
+        assert_eq!(boot_info.l2_chain_config().unwrap()["chainId"], 12345);
+    }
+}
@@ -0,0 +1,577 @@
+use alloy_consensus::Header;
+use eyre::Result;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+
+use palmtop_primitives::RollupConfig;
+
+use crate::frame::{ChannelId, Frame};
+
+/// The largest total size of the channels in the bank before Fjord.
+pub const MAX_CHANNEL_BANK_SIZE: usize = 100_000_000;
+
+/// The largest total size of the channels in the bank from Fjord on.
+pub const MAX_CHANNEL_BANK_SIZE_FJORD: usize = 1_000_000_000;
+
+/// The number of L1 blocks a channel stays open for from Granite on.
+pub const CHANNEL_TIMEOUT_GRANITE: u64 = 50;
+
+/// ## Channel
+///
+/// A Channel collects the frames sharing a channel id until it is closed by its
+/// last frame and every frame before it has arrived. Its data is then the
+/// concatenation of its frames' data, in frame number order.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    id: ChannelId,
+    open_block: u64,
+    frames: BTreeMap<u16, Frame>,
+    last: Option<u16>,
+    highest: u16,
+    size: usize,
+}
+
+impl Channel {
+    /// Creates a new, empty [Channel] opened in the given L1 block.
+    pub fn new(id: ChannelId, open_block: u64) -> Self {
+        Self {
+            id,
+            open_block,
+            frames: BTreeMap::new(),
+            last: None,
+            highest: 0,
+            size: 0,
+        }
+    }
+
+    /// Returns the id of the channel.
+    pub fn id(&self) -> ChannelId {
+        self.id
+    }
+
+    /// Returns the number of the L1 block the channel's first frame was included in.
+    pub fn open_block(&self) -> u64 {
+        self.open_block
+    }
+
+    /// Returns the size the channel counts for in the channel bank.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Adds a frame to the channel.
+    ///
+    /// Frames of another channel, duplicate frames, a second last frame and frames
+    /// past the last frame are rejected. A last frame drops any frames already
+    /// received past it.
+    pub fn add_frame(&mut self, frame: Frame) -> Result<()> {
+        if frame.id != self.id {
+            eyre::bail!("frame does not belong to channel {}", hex(&self.id));
+        }
+        if frame.is_last && self.last.is_some() {
+            eyre::bail!("channel {} is already closed", hex(&self.id));
+        }
+        if self.frames.contains_key(&frame.number) {
+            eyre::bail!(
+                "duplicate frame {} in channel {}",
+                frame.number,
+                hex(&self.id)
+            );
+        }
+        if let Some(last) = self.last.filter(|&last| frame.number >= last) {
+            eyre::bail!(
+                "frame {} is past the last frame {} of channel {}",
+                frame.number,
+                last,
+                hex(&self.id)
+            );
+        }
+        if frame.is_last {
+            self.last = Some(frame.number);
+            if frame.number < self.highest {
+                // Like op-node, every frame's size is taken off the channel's
+                // size, not only the size of the frames that are dropped.
+                self.size = 0;
+                self.frames.retain(|&number, _| number < frame.number);
+                self.highest = frame.number;
+            }
+        }
+        self.highest = self.highest.max(frame.number);
+        self.size += frame.size();
+        self.frames.insert(frame.number, frame);
+        Ok(())
+    }
+
+    /// Returns true if the channel is closed and holds every frame up to its last.
+    pub fn is_ready(&self) -> bool {
+        self.last
+            .is_some_and(|last| self.frames.len() == last as usize + 1)
+    }
+
+    /// Returns the channel's data.
+    pub fn data(&self) -> Vec<u8> {
+        self.frames
+            .values()
+            .flat_map(|frame| frame.data.iter().copied())
+            .collect()
+    }
+}
+
+/// The outcome of a [ChannelBank::read].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelRead {
+    /// The data of a ready channel.
+    Data(Vec<u8>),
+    /// The oldest channel timed out and was dropped, so another read may succeed.
+    TimedOut,
+    /// No channel is ready to be read.
+    Empty,
+}
+
+/// ## ChannelBank
+///
+/// The ChannelBank assembles frames into channels. Channels are kept in the order
+/// their first frame was seen, up to a total size after which the oldest
+/// channels are pruned. A channel times out once the current L1 origin is more
+/// than the channel timeout past the block it was opened in, and its frames are
+/// ignored from then on.
+///
+/// Before Canyon, only the oldest channel can be read, so a channel that is never
+/// completed holds back every channel after it until it times out. From Canyon
+/// on, the oldest channel that is ready is read.
+#[derive(Debug)]
+pub struct ChannelBank {
+    config: Arc<RollupConfig>,
+    channels: HashMap<ChannelId, Channel>,
+    queue: VecDeque<ChannelId>,
+}
+
+impl ChannelBank {
+    /// Creates a new, empty [ChannelBank] for the given rollup.
+    pub fn new(config: Arc<RollupConfig>) -> Self {
+        Self {
+            config,
+            channels: HashMap::new(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Returns the number of channels in the bank.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns true if the bank holds no channels.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Returns the total size of the channels in the bank.
+    pub fn size(&self) -> usize {
+        self.channels.values().map(Channel::size).sum()
+    }
+
+    /// Adds a frame included in the given L1 origin block, opening its channel if
+    /// it is the channel's first frame. Invalid frames are dropped.
+    pub fn ingest(&mut self, origin: &Header, frame: Frame) {
+        let timeout = self.channel_timeout(origin);
+        let channel = self.channels.entry(frame.id).or_insert_with(|| {
+            self.queue.push_back(frame.id);
+            Channel::new(frame.id, origin.number)
+        });
+        if channel.open_block + timeout < origin.number {
+            tracing::warn!(
+                target: "palmtop::channel",
+                "Ignoring frame {} of timed out channel {}",
+                frame.number,
+                hex(&frame.id)
+            );
+            return;
+        }
+        if let Err(err) = channel.add_frame(frame) {
+            tracing::warn!(target: "palmtop::channel", "Ignoring frame: {}", err);
+            return;
+        }
+        self.prune(origin);
+    }
+
+    /// Reads the data of the next ready channel at the given L1 origin, removing
+    /// it from the bank.
+    ///
+    /// If the oldest channel timed out, it is removed and nothing is read, so
+    /// each timed out channel takes a read of its own to drop. Like op-node,
+    /// reading should go on until the bank is [ChannelRead::Empty].
+    pub fn read(&mut self, origin: &Header) -> ChannelRead {
+        let Some(&first) = self.queue.front() else {
+            return ChannelRead::Empty;
+        };
+        if self.timed_out(&self.channels[&first], origin) {
+            tracing::warn!(
+                target: "palmtop::channel",
+                "Dropping timed out channel {}",
+                hex(&first)
+            );
+            self.queue.pop_front();
+            self.channels.remove(&first);
+            return ChannelRead::TimedOut;
+        }
+        let data = if self.config.is_canyon_active(origin.timestamp) {
+            (0..self.queue.len()).find_map(|index| self.read_at(index, origin))
+        } else {
+            self.read_at(0, origin)
+        };
+        data.map_or(ChannelRead::Empty, ChannelRead::Data)
+    }
+
+    /// Reads the channel at the given position in the queue, if it is ready.
+    fn read_at(&mut self, index: usize, origin: &Header) -> Option<Vec<u8>> {
+        let channel = &self.channels[&self.queue[index]];
+        if self.timed_out(channel, origin) || !channel.is_ready() {
+            return None;
+        }
+        let id = self.queue.remove(index)?;
+        let channel = self.channels.remove(&id)?;
+        Some(channel.data())
+    }
+
+    /// Drops the oldest channels until the bank fits its size limit.
+    fn prune(&mut self, origin: &Header) {
+        let max_size = if self.config.is_fjord_active(origin.timestamp) {
+            MAX_CHANNEL_BANK_SIZE_FJORD
+        } else {
+            MAX_CHANNEL_BANK_SIZE
+        };
+        let mut size = self.size();
+        while size > max_size {
+            let Some(id) = self.queue.pop_front() else {
+                break;
+            };
+            let channel = self
+                .channels
+                .remove(&id)
+                .expect("queued channels are banked");
+            tracing::warn!(
+                target: "palmtop::channel",
+                "Pruning channel {} from the full channel bank",
+                hex(&id)
+            );
+            size -= channel.size;
+        }
+    }
+
+    /// Returns the number of L1 blocks a channel stays open for at the given origin.
+    fn channel_timeout(&self, origin: &Header) -> u64 {
+        if self.config.is_granite_active(origin.timestamp) {
+            CHANNEL_TIMEOUT_GRANITE
+        } else {
+            self.config.channel_timeout
+        }
+    }
+
+    /// Returns true if the channel timed out at the given origin.
+    fn timed_out(&self, channel: &Channel, origin: &Header) -> bool {
+        channel.open_block + self.channel_timeout(origin) < origin.number
+    }
+}
+
+/// Encodes a channel id as hex for logs and errors.
+fn hex(id: &ChannelId) -> String {
+    alloy_primitives::hex::encode(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::tests::frame;
+    use crate::frame::MAX_FRAME_LEN;
+
+    fn origin(number: u64, timestamp: u64) -> Header {
+        Header {
+            number,
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    /// Returns a rollup config without any of the forks changing the channel bank.
+    fn config() -> Arc<RollupConfig> {
+        let mut config = RollupConfig::preset("optimism").unwrap();
+        config.channel_timeout = 10;
+        config.canyon_time = None;
+        config.delta_time = None;
+        config.ecotone_time = None;
+        config.fjord_time = None;
+        config.granite_time = None;
+        config.holocene_time = None;
+        config.validate().unwrap();
+        Arc::new(config)
+    }
+
+    #[test]
+    fn test_channel_assembles_frames_in_order() {
+        let mut channel = Channel::new([1; 16], 0);
+        channel.add_frame(frame(1, 2, b"c", true)).unwrap();
+        channel.add_frame(frame(1, 0, b"a", false)).unwrap();
+        assert!(!channel.is_ready());
+        channel.add_frame(frame(1, 1, b"b", false)).unwrap();
+        assert!(channel.is_ready());
+        assert_eq!(channel.data(), b"abc");
+        assert_eq!(channel.size(), 3 * frame(1, 0, b"a", false).size());
+    }
+
+    #[test]
+    fn test_channel_rejects_invalid_frames() {
+        let mut channel = Channel::new([1; 16], 0);
+        assert!(channel.add_frame(frame(2, 0, b"", false)).is_err());
+        channel.add_frame(frame(1, 0, b"a", false)).unwrap();
+        assert!(channel.add_frame(frame(1, 0, b"b", false)).is_err());
+        channel.add_frame(frame(1, 1, b"b", true)).unwrap();
+        assert!(channel.add_frame(frame(1, 2, b"c", false)).is_err());
+        assert!(channel.add_frame(frame(1, 3, b"c", true)).is_err());
+        assert_eq!(channel.data(), b"ab");
+    }
+
+    #[test]
+    fn test_channel_last_frame_drops_later_frames() {
+        let mut channel = Channel::new([1; 16], 0);
+        channel.add_frame(frame(1, 0, b"a", false)).unwrap();
+        channel.add_frame(frame(1, 3, b"d", false)).unwrap();
+        channel.add_frame(frame(1, 1, b"b", true)).unwrap();
+        assert!(channel.is_ready());
+        assert_eq!(channel.data(), b"ab");
+        assert_eq!(channel.size(), frame(1, 1, b"b", true).size());
+    }
+
+    #[test]
+    fn test_bank_reads_first_channel_before_canyon() {
+        let mut bank = ChannelBank::new(config());
+        let origin = origin(1, 0);
+        bank.ingest(&origin, frame(1, 0, b"one", false));
+        bank.ingest(&origin, frame(2, 0, b"two", true));
+        assert_eq!(bank.len(), 2);
+        assert_eq!(bank.read(&origin), ChannelRead::Empty);
+
+        bank.ingest(&origin, frame(1, 1, b"", true));
+        assert_eq!(bank.read(&origin), ChannelRead::Data(b"one".to_vec()));
+        assert_eq!(bank.read(&origin), ChannelRead::Data(b"two".to_vec()));
+        assert_eq!(bank.read(&origin), ChannelRead::Empty);
+        assert!(bank.is_empty());
+    }
+
+    #[test]
+    fn test_bank_reads_any_ready_channel_from_canyon() {
+        let mut config = (*config()).clone();
+        config.canyon_time = Some(0);
+        let mut bank = ChannelBank::new(Arc::new(config));
+        let origin = origin(1, 0);
+        bank.ingest(&origin, frame(1, 0, b"one", false));
+        bank.ingest(&origin, frame(2, 0, b"two", true));
+        assert_eq!(bank.read(&origin), ChannelRead::Data(b"two".to_vec()));
+        assert_eq!(bank.read(&origin), ChannelRead::Empty);
+        assert_eq!(bank.len(), 1);
+    }
+
+    #[test]
+    fn test_bank_times_out_channels() {
+        let mut bank = ChannelBank::new(config());
+        bank.ingest(&origin(1, 0), frame(1, 0, b"one", false));
+        bank.ingest(&origin(11, 0), frame(1, 1, b"", false));
+        assert_eq!(bank.channels[&[1; 16]].frames.len(), 2);
+
+        bank.ingest(&origin(12, 0), frame(1, 2, b"", true));
+        assert_eq!(bank.channels[&[1; 16]].frames.len(), 2);
+        assert_eq!(bank.read(&origin(12, 0)), ChannelRead::TimedOut);
+        assert_eq!(bank.read(&origin(12, 0)), ChannelRead::Empty);
+        assert!(bank.is_empty());
+    }
+
+    #[test]
+    fn test_bank_reads_past_timed_out_channel() {
+        let mut bank = ChannelBank::new(config());
+        bank.ingest(&origin(1, 0), frame(1, 0, b"one", false));
+        bank.ingest(&origin(5, 0), frame(2, 0, b"two", true));
+        assert_eq!(bank.read(&origin(5, 0)), ChannelRead::Empty);
+
+        assert_eq!(bank.read(&origin(12, 0)), ChannelRead::TimedOut);
+        assert_eq!(
+            bank.read(&origin(12, 0)),
+            ChannelRead::Data(b"two".to_vec())
+        );
+        assert_eq!(bank.read(&origin(12, 0)), ChannelRead::Empty);
+    }
+
+    #[test]
+    fn test_bank_uses_granite_channel_timeout() {
+        let mut config = (*config()).clone();
+        config.channel_timeout = 300;
+        for time in [
+            &mut config.canyon_time,
+            &mut config.delta_time,
+            &mut config.ecotone_time,
+            &mut config.fjord_time,
+        ] {
+            *time = Some(0);
+        }
+        config.granite_time = Some(100);
+        let mut bank = ChannelBank::new(Arc::new(config));
+        bank.ingest(&origin(1, 0), frame(1, 0, b"one", true));
+        bank.ingest(&origin(1, 0), frame(2, 0, b"two", true));
+        assert_eq!(
+            bank.read(&origin(100, 99)),
+            ChannelRead::Data(b"one".to_vec())
+        );
+        assert_eq!(bank.read(&origin(100, 100)), ChannelRead::TimedOut);
+        assert_eq!(bank.len(), 0);
+    }
+
+    #[test]
+    fn test_bank_prunes_oldest_channels() {
+        let mut bank = ChannelBank::new(config());
+        let origin = origin(1, 0);
+        let data = vec![0; MAX_FRAME_LEN];
+        for id in 0..(MAX_CHANNEL_BANK_SIZE / MAX_FRAME_LEN) as u8 {
+            bank.ingest(&origin, frame(id, 0, &data, false));
+        }
+        assert!(bank.size() <= MAX_CHANNEL_BANK_SIZE);
+        assert!(!bank.channels.contains_key(&[0; 16]));
+        assert!(bank.channels.contains_key(&[1; 16]));
+    }
+
+    /// Parses op-node's test frame shorthand: `channel:number:data`, where the
+    /// channel id is the name's bytes and a trailing `!` marks the last frame.
+    fn test_frame(frame: &str) -> Frame {
+        let mut parts = frame.split(':');
+        let mut id = [0; 16];
+        let name = parts.next().unwrap().as_bytes();
+        id[..name.len()].copy_from_slice(name);
+        let number = parts.next().unwrap().parse().unwrap();
+        let data = parts.next().unwrap();
+        Frame {
+            id,
+            number,
+            data: data.trim_end_matches('!').as_bytes().to_vec(),
+            is_last: data.ends_with('!'),
+        }
+    }
+
+    /// Ported from the table of op-node's `TestFrameValidity`
+    /// (op-node/rollup/derive/channel_test.go). The sizes pin op-node's
+    /// 200 byte frame overhead.
+    #[test]
+    fn test_channel_frame_validity() {
+        // Each case adds frames, checking whether adding each fails and the
+        // channel's size after it.
+        type Case<'a> = (&'a str, &'a [(&'a str, bool, usize)]);
+        let cases: [Case<'_>; 7] = [
+            ("wrong channel", &[("other:0:", true, 0)]),
+            (
+                "double close",
+                &[("id:2:four!", false, 204), ("id:1:!", true, 204)],
+            ),
+            (
+                "duplicate frame",
+                &[("id:2:four", false, 204), ("id:2:seven__", true, 204)],
+            ),
+            (
+                "duplicate closing frames",
+                &[("id:2:four!", false, 204), ("id:2:seven__!", true, 204)],
+            ),
+            (
+                "frame past closing",
+                &[("id:2:four!", false, 204), ("id:10:seven__", true, 204)],
+            ),
+            (
+                "prune after close frame",
+                &[("id:10:seven__", false, 207), ("id:2:four!", false, 204)],
+            ),
+            (
+                "multiple valid frames",
+                &[("id:10:seven__", false, 207), ("id:2:four", false, 411)],
+            ),
+        ];
+        for (name, frames) in cases {
+            let mut channel = Channel::new(test_frame("id:0:").id, 0);
+            for &(frame, should_err, size) in frames {
+                let result = channel.add_frame(test_frame(frame));
+                assert_eq!(result.is_err(), should_err, "{}: {}", name, frame);
+                assert_eq!(channel.size(), size, "{}: {}", name, frame);
+            }
+        }
+    }
+
+    /// Ingests each frame in turn, checking the data the bank reads after it.
+    fn check_bank(config: Arc<RollupConfig>, steps: &[(&str, &[&str])]) {
+        let mut bank = ChannelBank::new(config);
+        let origin = origin(1, 0);
+        for &(frame, expected) in steps {
+            bank.ingest(&origin, test_frame(frame));
+            let mut read = Vec::new();
+            while let ChannelRead::Data(data) = bank.read(&origin) {
+                read.push(String::from_utf8(data).unwrap());
+            }
+            assert_eq!(read, expected, "after {}", frame);
+        }
+        assert!(bank.is_empty());
+    }
+
+    /// Ported from op-node's `TestChannelBankSimple`
+    /// (op-node/rollup/derive/channel_bank_test.go).
+    #[test]
+    fn test_bank_simple() {
+        check_bank(
+            config(),
+            &[
+                ("a:0:first", &[]),
+                ("a:2:third!", &[]),
+                ("a:1:second", &["firstsecondthird"]),
+            ],
+        );
+    }
+
+    /// Ported from op-node's `TestChannelBankInterleavedPreCanyon` and
+    /// `TestChannelBankInterleaved` (op-node/rollup/derive/channel_bank_test.go).
+    #[test]
+    fn test_bank_interleaved() {
+        check_bank(
+            config(),
+            &[
+                ("a:0:first", &[]),
+                ("b:2:trois!", &[]),
+                ("b:1:deux", &[]),
+                ("a:2:third!", &[]),
+                ("b:0:premiere", &[]),
+                ("a:1:second", &["firstsecondthird", "premieredeuxtrois"]),
+            ],
+        );
+
+        let mut canyon = (*config()).clone();
+        canyon.canyon_time = Some(0);
+        check_bank(
+            Arc::new(canyon),
+            &[
+                ("a:0:first", &[]),
+                ("b:2:trois!", &[]),
+                ("b:1:deux", &[]),
+                ("a:2:third!", &[]),
+                ("b:0:premiere", &["premieredeuxtrois"]),
+                ("a:1:second", &["firstsecondthird"]),
+            ],
+        );
+    }
+
+    /// Ported from op-node's `TestChannelBankDuplicates`
+    /// (op-node/rollup/derive/channel_bank_test.go).
+    #[test]
+    fn test_bank_duplicates() {
+        check_bank(
+            config(),
+            &[
+                ("a:0:first", &[]),
+                ("a:2:third!", &[]),
+                ("a:0:altfirst", &[]),
+                ("a:2:altthird!", &[]),
+                ("a:1:second", &["firstsecondthird"]),
+            ],
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use eyre::Result;
+
+/// The version byte of batcher transaction data holding frames.
+pub const DERIVATION_VERSION_0: u8 = 0;
+
+/// The largest frame data the derivation accepts.
+pub const MAX_FRAME_LEN: usize = 1_000_000;
+
+/// The size a frame counts for in a channel besides its data. Like op-node, it
+/// is larger than the frame's other fields, to bound the number of frames held.
+pub const FRAME_OVERHEAD: usize = 200;
+
+/// The encoded size of a frame's fields besides its data: channel id, frame
+/// number, data length and the is-last flag.
+const FRAME_FIELDS_LEN: usize = 16 + 2 + 4 + 1;
+
+/// The id of a channel.
+pub type ChannelId = [u8; 16];
+
+/// ## Frame
+///
+/// A Frame is a chunk of a channel's compressed data, posted by the batcher in
+/// the calldata of a transaction to the batch inbox.
+///
+/// Frames are encoded as the channel id, the big-endian `u16` frame number,
+/// the big-endian `u32` data length, the data and a byte flagging the last
+/// frame of the channel.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Frame {
+    /// The id of the channel the frame belongs to.
+    pub id: ChannelId,
+    /// The position of the frame in its channel.
+    pub number: u16,
+    /// The frame's chunk of channel data.
+    pub data: Vec<u8>,
+    /// Whether the frame is the last one of its channel.
+    pub is_last: bool,
+}
+
+impl Frame {
+    /// Returns the size the frame counts for in a channel.
+    pub fn size(&self) -> usize {
+        self.data.len() + FRAME_OVERHEAD
+    }
+
+    /// Encodes the frame.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.data.len() + FRAME_FIELDS_LEN);
+        out.extend_from_slice(&self.id);
+        out.extend_from_slice(&self.number.to_be_bytes());
+        out.extend_from_slice(&(self.data.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.data);
+        out.push(self.is_last as u8);
+        out
+    }
+
+    /// Decodes a frame from the front of the buffer, advancing it past the frame.
+    pub fn decode(buf: &mut &[u8]) -> Result<Self> {
+        let id = take(buf, 16)?.try_into().expect("16 bytes");
+        let number = u16::from_be_bytes(take(buf, 2)?.try_into().expect("2 bytes"));
+        let len = u32::from_be_bytes(take(buf, 4)?.try_into().expect("4 bytes")) as usize;
+        if len > MAX_FRAME_LEN {
+            eyre::bail!("frame data length {} exceeds {}", len, MAX_FRAME_LEN);
+        }
+        let data = take(buf, len)?.to_vec();
+        let is_last = match take(buf, 1)?[0] {
+            0 => false,
+            1 => true,
+            flag => eyre::bail!("invalid frame is_last flag {}", flag),
+        };
+        Ok(Self {
+            id,
+            number,
+            data,
+            is_last,
+        })
+    }
+}
+
+/// Parses the frames of a batcher transaction's data.
+///
+/// The data is the derivation version byte followed by one or more frames. A
+/// transaction is all-or-nothing: if any of its frames is invalid, none are used.
+pub fn parse_frames(data: &[u8]) -> Result<Vec<Frame>> {
+    let (&version, mut buf) = data
+        .split_first()
+        .ok_or_else(|| eyre::eyre!("batcher transaction data is empty"))?;
+    if version != DERIVATION_VERSION_0 {
+        eyre::bail!("unsupported derivation version {}", version);
+    }
+    let mut frames = Vec::new();
+    while !buf.is_empty() {
+        frames.push(Frame::decode(&mut buf)?);
+    }
+    if frames.is_empty() {
+        eyre::bail!("batcher transaction data holds no frames");
+    }
+    Ok(frames)
+}
+
+/// Splits the next `len` bytes off the front of the buffer.
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if buf.len() < len {
+        eyre::bail!("frame truncated: need {} bytes, have {}", len, buf.len());
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Ok(head)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Returns the frame of the given number of the channel whose ID repeats
+    /// the given byte.
+    pub(crate) fn frame(id: u8, number: u16, data: &[u8], is_last: bool) -> Frame {
+        Frame {
+            id: [id; 16],
+            number,
+            data: data.to_vec(),
+            is_last,
+        }
+    }
+
+    #[test]
+    fn test_frame_encoding() {
+        let encoded = frame(0xab, 0x0102, b"abc", true).encode();
+        let mut expected = vec![0xab; 16];
+        expected.extend_from_slice(&[0x01, 0x02, 0, 0, 0, 3, b'a', b'b', b'c', 1]);
+        assert_eq!(encoded, expected);
+        assert_eq!(frame(0xab, 0x0102, b"abc", true).size(), 3 + FRAME_OVERHEAD);
+
+        let mut buf = encoded.as_slice();
+        assert_eq!(
+            Frame::decode(&mut buf).unwrap(),
+            frame(0xab, 0x0102, b"abc", true)
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_parse_frames() {
+        let mut data = vec![DERIVATION_VERSION_0];
+        data.extend(frame(0xab, 0, b"first", false).encode());
+        data.extend(frame(0xab, 1, b"", true).encode());
+        assert_eq!(
+            parse_frames(&data).unwrap(),
+            vec![frame(0xab, 0, b"first", false), frame(0xab, 1, b"", true)]
+        );
+    }
+
+    #[test]
+    fn test_parse_frames_is_all_or_nothing() {
+        let valid = frame(0xab, 0, b"data", false).encode();
+
+        assert!(parse_frames(&[]).is_err());
+        assert!(parse_frames(&[DERIVATION_VERSION_0]).is_err());
+        assert!(parse_frames(&[[1].as_slice(), &valid].concat()).is_err());
+
+        let truncated = [[0].as_slice(), &valid, &valid[..valid.len() - 1]].concat();
+        assert!(parse_frames(&truncated).is_err());
+
+        let mut bad_flag = [[0].as_slice(), &valid, &valid].concat();
+        *bad_flag.last_mut().unwrap() = 2;
+        assert!(parse_frames(&bad_flag).is_err());
+    }
+
+    #[test]
+    fn test_frame_rejects_oversized_data() {
+        let mut encoded = frame(0xab, 0, b"", false).encode();
+        encoded[18..22].copy_from_slice(&(MAX_FRAME_LEN as u32 + 1).to_be_bytes());
+        assert!(Frame::decode(&mut encoded.as_slice()).is_err());
+    }
+
+    /// Ported from op-node's `TestFrameUnmarshalNoData`
+    /// (op-node/rollup/derive/frame_test.go).
+    #[test]
+    fn test_frame_without_data() {
+        let mut id = [0; 16];
+        id[0] = 0xff;
+        let frame = Frame {
+            id,
+            number: 42,
+            data: vec![],
+            is_last: true,
+        };
+        let encoded = frame.encode();
+        assert_eq!(encoded.len(), FRAME_FIELDS_LEN);
+        assert_eq!(Frame::decode(&mut encoded.as_slice()).unwrap(), frame);
+    }
+
+    /// Ported from the table of op-node's `TestFrameUnmarshalTruncated`
+    /// (op-node/rollup/derive/frame_test.go).
+    #[test]
+    fn test_frame_rejects_truncations() {
+        let empty = frame(0xab, 0, b"", false).encode();
+        let with_data = frame(0xab, 0, &[0x5a; 100], false).encode();
+        let cases: [(&str, &[u8]); 8] = [
+            ("truncate-channel_id-half", &empty[..8]),
+            ("truncate-frame_number-full", &empty[..16]),
+            ("truncate-frame_number-half", &empty[..17]),
+            ("truncate-data_length-full", &empty[..18]),
+            ("truncate-data_length-half", &empty[..20]),
+            ("truncate-data-full", &with_data[..22]),
+            ("truncate-data-half", &with_data[..22 + 50]),
+            ("truncate-is_last", &with_data[..with_data.len() - 1]),
+        ];
+        for (name, data) in cases {
+            assert!(Frame::decode(&mut &data[..]).is_err(), "{}", name);
+        }
+    }
+}
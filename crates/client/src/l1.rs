@@ -0,0 +1,249 @@
+use alloy_consensus::{Header, ReceiptEnvelope, TxEnvelope};
+use alloy_eips::eip2718::Decodable2718;
+use alloy_primitives::B256;
+use alloy_rlp::Decodable;
+use eyre::Result;
+use std::collections::HashMap;
+
+use palmtop_preimage::client::OracleClient;
+use palmtop_primitives::{HintType, Hinter, TypedHint};
+
+use crate::mpt;
+use crate::oracle::get_keccak;
+
+/// ## OracleL1ChainProvider
+///
+/// The OracleL1ChainProvider serves the L1 chain to the program from the preimage
+/// oracle. Each block's header, transactions and receipts are hinted to the host,
+/// then read as keccak256 preimages and verified against the block hash and the
+/// header's trie roots.
+///
+/// The canonical chain is the one ending at the trusted L1 head, so blocks are
+/// looked up by number by walking back from the head by parent hash. Decoded
+/// headers, transactions and receipts are cached.
+#[derive(Debug)]
+pub struct OracleL1ChainProvider<O, H> {
+    oracle: O,
+    hinter: H,
+    head: B256,
+    headers: HashMap<B256, Header>,
+    canonical: HashMap<u64, B256>,
+    earliest: B256,
+    transactions: HashMap<B256, Vec<TxEnvelope>>,
+    receipts: HashMap<B256, Vec<ReceiptEnvelope>>,
+}
+
+impl<O, H> OracleL1ChainProvider<O, H>
+where
+    O: OracleClient,
+    H: Hinter,
+{
+    /// Creates a new [OracleL1ChainProvider] of the chain ending at the given L1 head.
+    pub fn new(head: B256, oracle: O, hinter: H) -> Self {
+        Self {
+            oracle,
+            hinter,
+            head,
+            headers: HashMap::new(),
+            canonical: HashMap::new(),
+            earliest: head,
+            transactions: HashMap::new(),
+            receipts: HashMap::new(),
+        }
+    }
+
+    /// Returns the header of the L1 head.
+    pub fn head(&mut self) -> Result<Header> {
+        let head = self.header_by_hash(self.head)?;
+        self.canonical.entry(head.number).or_insert(self.head);
+        Ok(head)
+    }
+
+    /// Returns the header of the block with the given hash.
+    pub fn header_by_hash(&mut self, hash: B256) -> Result<Header> {
+        if let Some(header) = self.headers.get(&hash) {
+            return Ok(header.clone());
+        }
+        self.hinter
+            .hint(TypedHint::new(HintType::L1BlockHeader, hash.to_vec()))?;
+        let header = Header::decode(&mut get_keccak(&mut self.oracle, hash)?.as_slice())?;
+        self.headers.insert(hash, header.clone());
+        Ok(header)
+    }
+
+    /// Returns the header of the canonical block with the given number, walking
+    /// back from the earliest block walked to so far.
+    pub fn header_by_number(&mut self, number: u64) -> Result<Header> {
+        if let Some(hash) = self.canonical.get(&number).copied() {
+            return self.header_by_hash(hash);
+        }
+        let mut header = self.head()?;
+        if number > header.number {
+            eyre::bail!("L1 block {} is after the L1 head {}", number, header.number);
+        }
+        header = self.header_by_hash(self.earliest)?;
+        while header.number > number {
+            let parent = header.parent_hash;
+            header = self.header_by_hash(parent)?;
+            self.canonical.insert(header.number, parent);
+            self.earliest = parent;
+        }
+        Ok(header)
+    }
+
+    /// Returns the transactions of the block with the given hash.
+    pub fn transactions(&mut self, hash: B256) -> Result<Vec<TxEnvelope>> {
+        if let Some(transactions) = self.transactions.get(&hash) {
+            return Ok(transactions.clone());
+        }
+        let root = self.header_by_hash(hash)?.transactions_root;
+        self.hinter
+            .hint(TypedHint::new(HintType::L1Transactions, hash.to_vec()))?;
+        let transactions = self
+            .ordered_values(root)?
+            .iter()
+            .map(|tx| Ok(TxEnvelope::decode_2718(&mut tx.as_slice())?))
+            .collect::<Result<Vec<_>>>()?;
+        self.transactions.insert(hash, transactions.clone());
+        Ok(transactions)
+    }
+
+    /// Returns the receipts of the block with the given hash.
+    pub fn receipts(&mut self, hash: B256) -> Result<Vec<ReceiptEnvelope>> {
+        if let Some(receipts) = self.receipts.get(&hash) {
+            return Ok(receipts.clone());
+        }
+        let root = self.header_by_hash(hash)?.receipts_root;
+        self.hinter
+            .hint(TypedHint::new(HintType::L1Receipts, hash.to_vec()))?;
+        let receipts = self
+            .ordered_values(root)?
+            .iter()
+            .map(|receipt| Ok(ReceiptEnvelope::decode_2718(&mut receipt.as_slice())?))
+            .collect::<Result<Vec<_>>>()?;
+        self.receipts.insert(hash, receipts.clone());
+        Ok(receipts)
+    }
+
+    /// Reads the values of the ordered trie with the given root from the oracle.
+    fn ordered_values(&mut self, root: B256) -> Result<Vec<Vec<u8>>> {
+        mpt::ordered_values(root, &mut mpt::oracle_nodes(&mut self.oracle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mpt::tests::ordered_trie;
+    use crate::program::tests::MapOracle;
+    use alloy_consensus::{Receipt, ReceiptWithBloom, SignableTransaction, TxLegacy};
+    use alloy_eips::eip2718::Encodable2718;
+    use alloy_primitives::{Log, TxKind, U256};
+
+    /// Returns an oracle holding a chain of the given length, with transactions
+    /// and receipts in its last block, and the hash of each block.
+    #[allow(deprecated)]
+    fn chain(len: u64) -> (MapOracle, Vec<B256>, Vec<TxEnvelope>, Vec<ReceiptEnvelope>) {
+        let mut oracle = MapOracle::default();
+        let transactions = (0..3)
+            .map(|nonce| {
+                let tx = TxLegacy {
+                    nonce,
+                    gas_limit: 21_000,
+                    to: TxKind::Call(Default::default()),
+                    value: U256::from(nonce),
+                    ..Default::default()
+                };
+                let signature = alloy_primitives::Signature::from_rs_and_parity(
+                    U256::from(1),
+                    U256::from(2),
+                    alloy_primitives::Parity::NonEip155(false),
+                )
+                .unwrap();
+                TxEnvelope::Legacy(tx.into_signed(signature))
+            })
+            .collect::<Vec<_>>();
+        let receipts = (0..3)
+            .map(|i| {
+                let receipt = Receipt {
+                    status: true.into(),
+                    cumulative_gas_used: 21_000 * (i + 1),
+                    logs: vec![Log::empty()],
+                };
+                ReceiptEnvelope::Eip1559(ReceiptWithBloom::from(receipt))
+            })
+            .collect::<Vec<_>>();
+        let mut store_trie = |values: Vec<Vec<u8>>| {
+            let (root, nodes) = ordered_trie(&values);
+            for node in nodes {
+                oracle.insert_keccak(node);
+            }
+            root
+        };
+        let transactions_root =
+            store_trie(transactions.iter().map(|tx| tx.encoded_2718()).collect());
+        let receipts_root = store_trie(receipts.iter().map(|r| r.encoded_2718()).collect());
+
+        let mut hashes = Vec::new();
+        let mut parent_hash = B256::ZERO;
+        for number in 0..len {
+            let mut header = Header {
+                number,
+                parent_hash,
+                ..Default::default()
+            };
+            if number == len - 1 {
+                header.transactions_root = transactions_root;
+                header.receipts_root = receipts_root;
+            }
+            parent_hash = oracle.insert_keccak(alloy_rlp::encode(&header));
+            hashes.push(parent_hash);
+        }
+        (oracle, hashes, transactions, receipts)
+    }
+
+    #[test]
+    fn test_header_by_number_walks_back_from_head() {
+        let (oracle, hashes, _, _) = chain(5);
+        let mut hinter = MapOracle::default();
+        let mut provider = OracleL1ChainProvider::new(hashes[4], oracle, &mut hinter);
+
+        assert_eq!(provider.head().unwrap().number, 4);
+        assert_eq!(provider.header_by_number(2).unwrap().number, 2);
+        assert_eq!(provider.header_by_number(3).unwrap().number, 3);
+        assert_eq!(provider.header_by_number(0).unwrap().number, 0);
+        assert!(provider.header_by_number(5).is_err());
+        drop(provider);
+
+        // Every header is hinted once, walking back from the head.
+        let expected = hashes
+            .iter()
+            .rev()
+            .map(|hash| format!("l1-block-header {hash}"))
+            .collect::<Vec<_>>();
+        assert_eq!(hinter.hints, expected);
+    }
+
+    #[test]
+    fn test_transactions_and_receipts() {
+        let (oracle, hashes, transactions, receipts) = chain(2);
+        let mut hinter = MapOracle::default();
+        let mut provider = OracleL1ChainProvider::new(hashes[1], oracle, &mut hinter);
+
+        assert_eq!(provider.transactions(hashes[1]).unwrap(), transactions);
+        assert_eq!(provider.receipts(hashes[1]).unwrap(), receipts);
+        assert_eq!(provider.transactions(hashes[1]).unwrap(), transactions);
+        assert!(provider.transactions(hashes[0]).unwrap().is_empty());
+        drop(provider);
+
+        assert_eq!(
+            hinter.hints[..3],
+            [
+                format!("l1-block-header {}", hashes[1]),
+                format!("l1-transactions {}", hashes[1]),
+                format!("l1-receipts {}", hashes[1]),
+            ]
+        );
+        assert_eq!(hinter.hints.len(), 5);
+    }
+}
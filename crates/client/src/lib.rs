@@ -0,0 +1,58 @@
+#![cfg_attr(docsrs, feature(doc_cfg))]
+#![doc(
+    html_logo_url = "https://raw.githubusercontent.com/refcell/palmtop/main/extra/palmtop.png",
+    html_favicon_url = "https://avatars0.githubusercontent.com/u/97369466?s=256",
+    issue_tracker_base_url = "https://github.com/refcell/palmtop/issues/"
+)]
+#![warn(missing_docs, unreachable_pub, unused_crate_dependencies)]
+#![deny(unused_must_use, rust_2018_idioms)]
+#![doc(test(
+    no_crate_inject,
+    attr(deny(warnings, rust_2018_idioms), allow(dead_code, unused_variables))
+))]
+
+//! Palmtop Client
+//!
+//! The fault proof program that runs on top of the preimage oracle.
+
+/// Batches decoded from channels.
+pub mod batch;
+pub use batch::{Batch, BatchDecoder, RawBatch, SingularBatch};
+
+/// Ordering and checking batches against the safe head.
+pub mod batch_queue;
+pub use batch_queue::{BatchQueue, L1BlockInfo, L2BlockInfo, SafeBlockFetcher};
+
+/// Program boot info.
+pub mod boot;
+pub use boot::BootInfo;
+
+/// Channels assembled from frames.
+pub mod channel;
+pub use channel::{Channel, ChannelBank, ChannelRead};
+
+/// Batcher transaction frames.
+pub mod frame;
+pub use frame::Frame;
+
+/// Oracle-backed L1 chain provider.
+pub mod l1;
+pub use l1::OracleL1ChainProvider;
+
+/// Merkle-Patricia trie reader.
+pub mod mpt;
+
+/// Reading verified preimages from the oracle.
+mod oracle;
+
+/// Span batches.
+pub mod span_batch;
+pub use span_batch::{RawSpanBatch, SpanBatch};
+
+/// Mutable Merkle-Patricia trie.
+pub mod trie;
+pub use trie::{MutableTrie, OracleNodeProvider};
+
+/// The client program.
+pub mod program;
+pub use program::run;
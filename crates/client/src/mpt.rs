@@ -0,0 +1,506 @@
+use alloy_primitives::{keccak256, B256};
+use alloy_rlp::{Decodable, Header, EMPTY_STRING_CODE};
+use eyre::Result;
+
+use palmtop_preimage::client::OracleClient;
+
+use crate::oracle::get_keccak;
+
+/// The root hash of an empty trie.
+pub const EMPTY_ROOT_HASH: B256 = B256::new([
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+]);
+
+/// ## NodeRef
+///
+/// A reference from a trie node to one of its children. Children whose encoding is
+/// shorter than 32 bytes are inlined into their parent instead of referenced by hash.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum NodeRef {
+    /// No child.
+    #[default]
+    Empty,
+    /// A child referenced by the keccak256 hash of its encoding.
+    Hash(B256),
+    /// A child inlined as its encoding.
+    Inline(Vec<u8>),
+}
+
+impl NodeRef {
+    /// Decodes a reference from its RLP encoding within a node.
+    fn decode(item: &[u8]) -> Result<Self> {
+        let mut buf = item;
+        let header = Header::decode(&mut buf)?;
+        if header.list {
+            return Ok(Self::Inline(item.to_vec()));
+        }
+        match buf.len() {
+            0 => Ok(Self::Empty),
+            32 => Ok(Self::Hash(B256::from_slice(buf))),
+            len => eyre::bail!("trie node reference must be 32 bytes, got {}", len),
+        }
+    }
+}
+
+/// ## TrieNode
+///
+/// A decoded Merkle-Patricia trie node, with its path segments as nibbles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrieNode {
+    /// The empty node of an empty trie.
+    Empty,
+    /// A leaf holding the value at the rest of its path.
+    Leaf {
+        /// The rest of the path to the value, in nibbles.
+        path: Vec<u8>,
+        /// The value.
+        value: Vec<u8>,
+    },
+    /// An extension of the path shared by every key below its child.
+    Extension {
+        /// The shared path segment, in nibbles.
+        path: Vec<u8>,
+        /// The child the path leads to.
+        child: NodeRef,
+    },
+    /// A branch on the next nibble of the path.
+    Branch {
+        /// The child of each nibble.
+        children: Box<[NodeRef; 16]>,
+        /// The value of the key ending at the branch, if any.
+        value: Option<Vec<u8>>,
+    },
+}
+
+impl TrieNode {
+    /// Decodes a trie node from its RLP encoding.
+    pub fn decode(encoded: &[u8]) -> Result<Self> {
+        if encoded == [EMPTY_STRING_CODE] {
+            return Ok(Self::Empty);
+        }
+        let items = list_items(encoded)?;
+        match items.len() {
+            2 => {
+                let (path, is_leaf) = decode_path(decode_bytes(items[0])?)?;
+                if is_leaf {
+                    let value = decode_bytes(items[1])?.to_vec();
+                    return Ok(Self::Leaf { path, value });
+                }
+                let child = NodeRef::decode(items[1])?;
+                Ok(Self::Extension { path, child })
+            }
+            17 => {
+                let mut children: [NodeRef; 16] = Default::default();
+                for (child, item) in children.iter_mut().zip(&items) {
+                    *child = NodeRef::decode(item)?;
+                }
+                let value = decode_bytes(items[16])?;
+                let value = (!value.is_empty()).then(|| value.to_vec());
+                Ok(Self::Branch {
+                    children: Box::new(children),
+                    value,
+                })
+            }
+            len => eyre::bail!("trie node must have 2 or 17 items, got {}", len),
+        }
+    }
+}
+
+/// Returns a function fetching trie nodes by hash as keccak256 preimages from the oracle.
+pub fn oracle_nodes<O: OracleClient>(oracle: &mut O) -> impl FnMut(B256) -> Result<Vec<u8>> + '_ {
+    move |hash| get_keccak(&mut *oracle, hash)
+}
+
+/// Looks up the value of the given key in the trie with the given root, fetching
+/// the nodes referenced by hash with the given function.
+///
+/// Returns `None` only once the nodes on the key's path prove it is absent: the
+/// path reaches an empty child, diverges from an extension or leaf, or ends at a
+/// branch without a value. A node that cannot be fetched is an error.
+pub fn get(
+    root: B256,
+    key: &[u8],
+    fetch: &mut impl FnMut(B256) -> Result<Vec<u8>>,
+) -> Result<Option<Vec<u8>>> {
+    if root == EMPTY_ROOT_HASH {
+        return Ok(None);
+    }
+    let nibbles = unpack_nibbles(key);
+    let mut rest = nibbles.as_slice();
+    let mut node = resolve(&NodeRef::Hash(root), fetch)?;
+    loop {
+        match node {
+            TrieNode::Empty => return Ok(None),
+            TrieNode::Leaf { path, value } => return Ok((path == rest).then_some(value)),
+            TrieNode::Extension { path, child } => {
+                let Some(tail) = rest.strip_prefix(path.as_slice()) else {
+                    return Ok(None);
+                };
+                rest = tail;
+                node = resolve(&child, fetch)?;
+            }
+            TrieNode::Branch { children, value } => {
+                let Some((nibble, tail)) = rest.split_first() else {
+                    return Ok(value);
+                };
+                rest = tail;
+                node = resolve(&children[*nibble as usize], fetch)?;
+            }
+        }
+    }
+}
+
+/// Looks up the value of the given key in a trie keyed by the keccak256 hash of
+/// each key, like the account and storage tries.
+pub fn get_hashed(
+    root: B256,
+    key: &[u8],
+    fetch: &mut impl FnMut(B256) -> Result<Vec<u8>>,
+) -> Result<Option<Vec<u8>>> {
+    get(root, keccak256(key).as_slice(), fetch)
+}
+
+/// Returns every key and value of the trie with the given root, fetching the nodes
+/// referenced by hash with the given function.
+///
+/// Keys are returned as nibbles, in the order of a depth-first walk of the trie.
+pub fn leaves(
+    root: B256,
+    fetch: &mut impl FnMut(B256) -> Result<Vec<u8>>,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let mut leaves = Vec::new();
+    if root != EMPTY_ROOT_HASH {
+        walk(&NodeRef::Hash(root), &mut Vec::new(), fetch, &mut leaves)?;
+    }
+    Ok(leaves)
+}
+
+/// Returns the values of the trie of an ordered list with the given root, like the
+/// transactions or receipts of a block, where each value is keyed by the RLP
+/// encoding of its index.
+pub fn ordered_values(
+    root: B256,
+    fetch: &mut impl FnMut(B256) -> Result<Vec<u8>>,
+) -> Result<Vec<Vec<u8>>> {
+    let mut values = leaves(root, fetch)?
+        .into_iter()
+        .map(|(key, value)| {
+            let index = usize::decode(&mut pack_nibbles(&key)?.as_slice())?;
+            Ok((index, value))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    values.sort_by_key(|(index, _)| *index);
+    for (expected, (index, _)) in values.iter().enumerate() {
+        if *index != expected {
+            eyre::bail!("ordered trie {} is missing index {}", root, expected);
+        }
+    }
+    Ok(values.into_iter().map(|(_, value)| value).collect())
+}
+
+/// Walks the subtrie of the given node, collecting the keys and values below it.
+fn walk(
+    node: &NodeRef,
+    path: &mut Vec<u8>,
+    fetch: &mut impl FnMut(B256) -> Result<Vec<u8>>,
+    leaves: &mut Vec<(Vec<u8>, Vec<u8>)>,
+) -> Result<()> {
+    let node = resolve(node, fetch)?;
+    let depth = path.len();
+    match node {
+        TrieNode::Empty => {}
+        TrieNode::Leaf { path: rest, value } => {
+            path.extend_from_slice(&rest);
+            leaves.push((path.clone(), value));
+        }
+        TrieNode::Extension { path: rest, child } => {
+            path.extend_from_slice(&rest);
+            walk(&child, path, fetch, leaves)?;
+        }
+        TrieNode::Branch { children, value } => {
+            if let Some(value) = value {
+                leaves.push((path.clone(), value));
+            }
+            for (nibble, child) in children.iter().enumerate() {
+                path.push(nibble as u8);
+                walk(child, path, fetch, leaves)?;
+                path.truncate(depth);
+            }
+        }
+    }
+    path.truncate(depth);
+    Ok(())
+}
+
+/// Resolves a reference to the node it refers to, fetching it if it is referenced
+/// by hash. An empty reference resolves to the empty node.
+fn resolve(node: &NodeRef, fetch: &mut impl FnMut(B256) -> Result<Vec<u8>>) -> Result<TrieNode> {
+    match node {
+        NodeRef::Empty => Ok(TrieNode::Empty),
+        NodeRef::Hash(hash) => {
+            let encoded = fetch(*hash)?;
+            if keccak256(&encoded) != *hash {
+                eyre::bail!("trie node does not match its hash {}", hash);
+            }
+            TrieNode::decode(&encoded)
+        }
+        NodeRef::Inline(encoded) => TrieNode::decode(encoded),
+    }
+}
+
+/// Returns the RLP encoding of each item of an RLP list.
+fn list_items(encoded: &[u8]) -> Result<Vec<&[u8]>> {
+    let mut buf = encoded;
+    let header = Header::decode(&mut buf)?;
+    if !header.list || buf.len() != header.payload_length {
+        eyre::bail!("trie node must be a single RLP list");
+    }
+    let mut items = Vec::new();
+    while !buf.is_empty() {
+        let mut rest = buf;
+        let item = Header::decode(&mut rest)?;
+        let len = buf.len() - rest.len() + item.payload_length;
+        if len > buf.len() {
+            eyre::bail!("trie node item is truncated");
+        }
+        items.push(&buf[..len]);
+        buf = &buf[len..];
+    }
+    Ok(items)
+}
+
+/// Decodes an RLP byte string.
+fn decode_bytes(mut item: &[u8]) -> Result<&[u8]> {
+    Ok(Header::decode_bytes(&mut item, false)?)
+}
+
+/// Decodes a hex-prefix encoded path into its nibbles and whether it is a leaf's.
+fn decode_path(encoded: &[u8]) -> Result<(Vec<u8>, bool)> {
+    let Some(first) = encoded.first() else {
+        eyre::bail!("trie node path is empty");
+    };
+    let flag = first >> 4;
+    if flag > 3 {
+        eyre::bail!("invalid trie node path flag {}", flag);
+    }
+    let mut nibbles = Vec::with_capacity(encoded.len() * 2);
+    if flag & 1 == 1 {
+        nibbles.push(first & 0x0f);
+    }
+    for byte in &encoded[1..] {
+        nibbles.push(byte >> 4);
+        nibbles.push(byte & 0x0f);
+    }
+    Ok((nibbles, flag & 2 == 2))
+}
+
+/// Unpacks bytes into nibbles.
+pub(crate) fn unpack_nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+/// Packs nibbles into bytes, failing for an odd number of nibbles.
+fn pack_nibbles(nibbles: &[u8]) -> Result<Vec<u8>> {
+    if nibbles.len() % 2 != 0 {
+        eyre::bail!("trie key has an odd number of nibbles");
+    }
+    Ok(nibbles
+        .chunks_exact(2)
+        .map(|pair| (pair[0] << 4) | pair[1])
+        .collect())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloy_trie::{proof::ProofRetainer, HashBuilder, Nibbles};
+    use std::collections::HashMap;
+
+    /// Builds the trie of the given keys and values, returning its root and the
+    /// encoding of every node referenced by hash on the paths of the given keys.
+    pub(crate) fn trie(
+        entries: &[(Vec<u8>, Vec<u8>)],
+        retained: &[Vec<u8>],
+    ) -> (B256, Vec<Vec<u8>>) {
+        let mut leaves = entries
+            .iter()
+            .map(|(key, value)| (Nibbles::unpack(key), value))
+            .collect::<Vec<_>>();
+        leaves.sort_by(|a, b| a.0.cmp(&b.0));
+        let retainer = ProofRetainer::new(retained.iter().map(Nibbles::unpack).collect());
+        let mut builder = HashBuilder::default().with_proof_retainer(retainer);
+        for (key, value) in &leaves {
+            builder.add_leaf(key.clone(), value);
+        }
+        let root = builder.root();
+        let nodes = builder
+            .take_proofs()
+            .into_iter()
+            .filter(|(path, node)| path.is_empty() || node.len() >= 32)
+            .map(|(_, node)| node.to_vec())
+            .collect();
+        (root, nodes)
+    }
+
+    /// Builds the trie of an ordered list like the host does, returning its root
+    /// and the encoding of every node referenced by hash.
+    pub(crate) fn ordered_trie(values: &[Vec<u8>]) -> (B256, Vec<Vec<u8>>) {
+        if values.is_empty() {
+            return (EMPTY_ROOT_HASH, vec![vec![EMPTY_STRING_CODE]]);
+        }
+        let entries = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (alloy_rlp::encode(index), value.clone()))
+            .collect::<Vec<_>>();
+        let keys = entries
+            .iter()
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        trie(&entries, &keys)
+    }
+
+    fn fetcher(nodes: Vec<Vec<u8>>) -> impl FnMut(B256) -> Result<Vec<u8>> {
+        let nodes = nodes
+            .into_iter()
+            .map(|node| (keccak256(&node), node))
+            .collect::<HashMap<_, _>>();
+        move |hash| {
+            nodes
+                .get(&hash)
+                .cloned()
+                .ok_or_else(|| eyre::eyre!("missing node {}", hash))
+        }
+    }
+
+    #[test]
+    fn test_ordered_values() {
+        for len in [0, 1, 2, 16, 200] {
+            let values = (0..len)
+                .map(|i| vec![i as u8; 40 + i % 3])
+                .collect::<Vec<_>>();
+            let (root, nodes) = ordered_trie(&values);
+            assert_eq!(ordered_values(root, &mut fetcher(nodes)).unwrap(), values);
+        }
+    }
+
+    #[test]
+    fn test_ordered_values_inline_nodes() {
+        // A branch with the leaves of indexes 0 (key 0x80) and 1 (key 0x01)
+        // inlined at nibbles 8 and 0, since they are shorter than 32 bytes.
+        let mut items = vec![vec![EMPTY_STRING_CODE]; 17];
+        items[0] = vec![0xc2, 0x31, 0x0a];
+        items[8] = vec![0xc2, 0x30, 0x0b];
+        let payload = items.concat();
+        let mut branch = Vec::new();
+        Header {
+            list: true,
+            payload_length: payload.len(),
+        }
+        .encode(&mut branch);
+        branch.extend_from_slice(&payload);
+
+        let root = keccak256(&branch);
+        let values = ordered_values(root, &mut fetcher(vec![branch])).unwrap();
+        assert_eq!(values, vec![vec![0x0b], vec![0x0a]]);
+    }
+
+    #[test]
+    fn test_leaves_rejects_invalid_node() {
+        let values = vec![vec![1; 40], vec![2; 40]];
+        let (root, _) = ordered_trie(&values);
+        let mut fetch = |_| Ok(vec![0xc0]);
+        assert!(leaves(root, &mut fetch).is_err());
+    }
+
+    #[test]
+    fn test_decode_path() {
+        assert_eq!(decode_path(&[0x00, 0x12]).unwrap(), (vec![1, 2], false));
+        assert_eq!(decode_path(&[0x1a]).unwrap(), (vec![0xa], false));
+        assert_eq!(decode_path(&[0x20]).unwrap(), (vec![], true));
+        assert_eq!(
+            decode_path(&[0x3f, 0xed]).unwrap(),
+            (vec![0xf, 0xe, 0xd], true)
+        );
+        assert!(decode_path(&[0x40]).is_err());
+    }
+
+    #[test]
+    fn test_get_ordered_trie() {
+        let values = (0..100).map(|i| vec![i as u8; 40]).collect::<Vec<_>>();
+        let (root, nodes) = ordered_trie(&values);
+        let mut fetch = fetcher(nodes);
+        for index in [0usize, 1, 15, 16, 99] {
+            let key = alloy_rlp::encode(index);
+            assert_eq!(
+                get(root, &key, &mut fetch).unwrap(),
+                Some(values[index].clone())
+            );
+        }
+        for index in [100usize, 1000] {
+            assert_eq!(
+                get(root, &alloy_rlp::encode(index), &mut fetch).unwrap(),
+                None
+            );
+        }
+        assert_eq!(get(root, &[], &mut fetch).unwrap(), None);
+        assert_eq!(get(EMPTY_ROOT_HASH, &[1], &mut fetch).unwrap(), None);
+    }
+
+    #[test]
+    fn test_get_hashed_proof_of_absence() {
+        let entries = (0..64u8)
+            .map(|i| (keccak256([i]).to_vec(), vec![i; 40]))
+            .collect::<Vec<_>>();
+        let present = keccak256([7]).to_vec();
+        let absent = keccak256([200]).to_vec();
+        let (root, nodes) = trie(&entries, &[present, absent]);
+
+        // The nodes on the paths of the two keys suffice to look both of them up.
+        let mut fetch = fetcher(nodes);
+        assert_eq!(
+            get_hashed(root, &[7], &mut fetch).unwrap(),
+            Some(vec![7; 40])
+        );
+        assert_eq!(get_hashed(root, &[200], &mut fetch).unwrap(), None);
+        // Any other key needs nodes that were not retained.
+        assert!((0..64u8).any(|i| get_hashed(root, &[i], &mut fetch).is_err()));
+    }
+
+    #[test]
+    fn test_get_inline_nodes() {
+        let mut items = vec![vec![EMPTY_STRING_CODE]; 17];
+        items[0] = vec![0xc2, 0x31, 0x0a];
+        items[16] = vec![0x0c];
+        let payload = items.concat();
+        let mut branch = Vec::new();
+        Header {
+            list: true,
+            payload_length: payload.len(),
+        }
+        .encode(&mut branch);
+        branch.extend_from_slice(&payload);
+        let root = keccak256(&branch);
+
+        let mut fetch = fetcher(vec![branch]);
+        assert_eq!(get(root, &[0x01], &mut fetch).unwrap(), Some(vec![0x0a]));
+        assert_eq!(get(root, &[0x02], &mut fetch).unwrap(), None);
+        assert_eq!(get(root, &[], &mut fetch).unwrap(), Some(vec![0x0c]));
+    }
+
+    #[test]
+    fn test_oracle_nodes() {
+        let mut oracle = crate::program::tests::MapOracle::default();
+        let values = vec![vec![1; 40], vec![2; 40]];
+        let (root, nodes) = ordered_trie(&values);
+        for node in nodes {
+            oracle.insert_keccak(node);
+        }
+        let mut fetch = oracle_nodes(&mut oracle);
+        assert_eq!(ordered_values(root, &mut fetch).unwrap(), values);
+    }
+}
@@ -0,0 +1,17 @@
+use alloy_primitives::{keccak256, B256};
+use eyre::{Result, WrapErr};
+
+use palmtop_preimage::client::OracleClient;
+use palmtop_primitives::{Preimage, PreimageKeyType};
+
+use crate::program::Failure;
+
+/// Fetches the preimage of the given keccak256 hash, checking that it matches.
+pub(crate) fn get_keccak(oracle: &mut impl OracleClient, hash: B256) -> Result<Preimage> {
+    let preimage = oracle.get(PreimageKeyType::Keccak256.key(hash.0))?;
+    if keccak256(&preimage) != hash {
+        return Err(eyre::eyre!("served an invalid preimage for {}", hash))
+            .wrap_err(Failure::Oracle);
+    }
+    Ok(preimage)
+}
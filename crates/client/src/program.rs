@@ -0,0 +1,255 @@
+use alloy_consensus::Header;
+use alloy_primitives::B256;
+use alloy_rlp::Decodable;
+use eyre::{Result, WrapErr};
+use std::fmt;
+
+use palmtop_preimage::client::OracleClient;
+use palmtop_primitives::{HintType, Hinter, TypedHint};
+
+use crate::boot::BootInfo;
+use crate::oracle::get_keccak;
+
+/// The exit code of a run that found the claim valid.
+pub const EXIT_VALID: i32 = 0;
+
+/// The exit code of a run that found the claim invalid.
+pub const EXIT_INVALID: i32 = 1;
+
+/// The exit code of a run that failed before reaching a verdict, for failures
+/// without a more specific exit code.
+pub const EXIT_FAILURE: i32 = 2;
+
+/// The exit code of a run whose boot info is missing or invalid.
+pub const EXIT_BOOT: i32 = 3;
+
+/// The exit code of a run whose oracle channels failed or served an invalid preimage.
+pub const EXIT_ORACLE: i32 = 4;
+
+/// The exit code of a run needing a feature the program does not support yet.
+pub const EXIT_UNSUPPORTED: i32 = 5;
+
+/// ## Failure
+///
+/// The kinds of failures with their own exit codes, attached to a run's error
+/// as its context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// The boot info is missing or invalid.
+    Boot,
+    /// The oracle channels failed or served an invalid preimage.
+    Oracle,
+    /// The run needs a feature the program does not support yet.
+    Unsupported,
+}
+
+impl Failure {
+    /// Returns the kind of failure of the given error, if it has one.
+    ///
+    /// Errors of the oracle channels are oracle failures wherever they occur.
+    pub fn of(err: &eyre::Report) -> Option<Self> {
+        if err.chain().any(|e| e.is::<std::io::Error>()) {
+            return Some(Self::Oracle);
+        }
+        err.downcast_ref::<Self>().copied()
+    }
+
+    /// Returns the exit code of the failure.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Boot => EXIT_BOOT,
+            Self::Oracle => EXIT_ORACLE,
+            Self::Unsupported => EXIT_UNSUPPORTED,
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Boot => write!(f, "invalid boot info"),
+            Self::Oracle => write!(f, "oracle failure"),
+            Self::Unsupported => write!(f, "unsupported"),
+        }
+    }
+}
+
+/// The size of an encoded L2 output: version, state root, message passer
+/// storage root and block hash.
+const OUTPUT_LEN: usize = 128;
+
+/// Runs the program, returning whether the disputed L2 output root claim is valid.
+///
+/// The program loads its boot info and resolves the agreed upon L2 output to its
+/// block. A claim at the agreed block is valid exactly when it matches the agreed
+/// output root.
+///
+/// Claims about later blocks need L2 blocks to be derived from L1 and executed.
+/// The derivation stages are not wired into the program yet and there is no block
+/// executor, so every such claim fails with [Failure::Unsupported] rather than
+/// reaching a verdict.
+pub fn run(oracle: &mut impl OracleClient, hinter: &mut impl Hinter) -> Result<bool> {
+    let boot_info = BootInfo::load(oracle).wrap_err(Failure::Boot)?;
+
+    let agreed_root = boot_info.l2_output_root();
+    hinter.hint(TypedHint::new(HintType::L2Output, agreed_root.to_vec()))?;
+    let output = get_keccak(oracle, agreed_root)?;
+    if output.len() != OUTPUT_LEN {
+        eyre::bail!("L2 output {} must be {} bytes", agreed_root, OUTPUT_LEN);
+    }
+    let agreed_block = B256::from_slice(&output[96..]);
+    hinter.hint(TypedHint::new(
+        HintType::L2BlockHeader,
+        agreed_block.to_vec(),
+    ))?;
+    let header = Header::decode(&mut get_keccak(oracle, agreed_block)?.as_slice())?;
+    tracing::info!(
+        target: "palmtop::program",
+        "Agreed L2 output is at block {} ({})",
+        header.number,
+        agreed_block
+    );
+
+    let claim_block = boot_info.l2_claim_block_number();
+    if claim_block < header.number {
+        eyre::bail!(
+            "claim block {} is before the agreed block {}",
+            claim_block,
+            header.number
+        );
+    }
+    if claim_block > header.number {
+        return Err(eyre::eyre!(
+            "deriving L2 blocks {} to {} is not supported yet",
+            header.number + 1,
+            claim_block
+        ))
+        .wrap_err(Failure::Unsupported);
+    }
+    Ok(boot_info.l2_claim() == agreed_root)
+}
+
+/// Returns the process exit code of a run's result.
+pub fn exit_code(result: &Result<bool>) -> i32 {
+    match result {
+        Ok(true) => EXIT_VALID,
+        Ok(false) => EXIT_INVALID,
+        Err(err) => Failure::of(err).map_or(EXIT_FAILURE, Failure::exit_code),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloy_primitives::keccak256;
+    use palmtop_primitives::{BootKey, Hint, Preimage, PreimageKey, PreimageKeyType, RollupConfig};
+    use std::collections::HashMap;
+
+    /// An oracle serving preimages from a map and recording the hints it is sent.
+    #[derive(Debug, Default)]
+    pub(crate) struct MapOracle {
+        pub(crate) preimages: HashMap<PreimageKey, Preimage>,
+        pub(crate) hints: Vec<String>,
+    }
+
+    impl MapOracle {
+        pub(crate) fn insert_keccak(&mut self, preimage: Vec<u8>) -> B256 {
+            let hash = keccak256(&preimage);
+            self.preimages
+                .insert(PreimageKeyType::Keccak256.key(hash.0), preimage);
+            hash
+        }
+    }
+
+    impl OracleClient for MapOracle {
+        fn get(&mut self, key: PreimageKey) -> Result<Preimage> {
+            self.preimages
+                .get(&key)
+                .cloned()
+                .ok_or_else(|| eyre::eyre!("missing key"))
+        }
+    }
+
+    impl Hinter for MapOracle {
+        fn hint(&mut self, hint: impl Hint) -> Result<()> {
+            self.hints.push(hint.hint());
+            Ok(())
+        }
+    }
+
+    /// Returns an oracle for a claim at the given block, with the agreed output at block 100.
+    fn boot_oracle(claim: B256, claim_block: u64) -> (MapOracle, B256) {
+        let mut oracle = MapOracle::default();
+        let header = Header {
+            number: 100,
+            ..Default::default()
+        };
+        let block_hash = oracle.insert_keccak(alloy_rlp::encode(&header));
+        let output = [[0; 32], [1; 32], [2; 32], block_hash.0].concat();
+        let root = oracle.insert_keccak(output);
+        let claim = if claim.is_zero() { root } else { claim };
+        let boot = [
+            (BootKey::L1Head, [9u8; 32].to_vec()),
+            (BootKey::L2OutputRoot, root.to_vec()),
+            (BootKey::L2Claim, claim.to_vec()),
+            (
+                BootKey::L2ClaimBlockNumber,
+                claim_block.to_be_bytes().to_vec(),
+            ),
+            (BootKey::L2ChainId, 10u64.to_be_bytes().to_vec()),
+            (
+                BootKey::RollupConfig,
+                RollupConfig::preset("optimism").unwrap().to_json(),
+            ),
+        ];
+        for (key, value) in boot {
+            oracle.preimages.insert(key.key(), value);
+        }
+        (oracle, root)
+    }
+
+    #[test]
+    fn test_run_claim_at_agreed_block() {
+        let (mut oracle, root) = boot_oracle(B256::ZERO, 100);
+        let mut hinter = MapOracle::default();
+        assert!(run(&mut oracle, &mut hinter).unwrap());
+        assert_eq!(hinter.hints[0], format!("l2-output {root}"));
+
+        let (mut oracle, _) = boot_oracle(B256::repeat_byte(7), 100);
+        let result = run(&mut oracle, &mut MapOracle::default());
+        assert_eq!(exit_code(&result), EXIT_INVALID);
+    }
+
+    #[test]
+    fn test_run_unsupported_claim_block() {
+        let (mut oracle, _) = boot_oracle(B256::ZERO, 101);
+        let result = run(&mut oracle, &mut MapOracle::default());
+        assert!(format!("{:#}", result.as_ref().unwrap_err()).contains("not supported"));
+        assert_eq!(exit_code(&result), EXIT_UNSUPPORTED);
+
+        let (mut oracle, _) = boot_oracle(B256::ZERO, 99);
+        let result = run(&mut oracle, &mut MapOracle::default());
+        assert_eq!(exit_code(&result), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn test_run_rejects_invalid_preimage() {
+        let (mut oracle, root) = boot_oracle(B256::ZERO, 100);
+        oracle
+            .preimages
+            .insert(PreimageKeyType::Keccak256.key(root.0), vec![0; 128]);
+        let result = run(&mut oracle, &mut MapOracle::default());
+        assert_eq!(exit_code(&result), EXIT_ORACLE);
+    }
+
+    #[test]
+    fn test_failure_exit_codes() {
+        let result = run(&mut MapOracle::default(), &mut MapOracle::default());
+        assert_eq!(exit_code(&result), EXIT_BOOT);
+
+        let closed = std::io::Error::from(std::io::ErrorKind::UnexpectedEof);
+        let err = eyre::Report::from(closed).wrap_err(Failure::Boot);
+        assert_eq!(Failure::of(&err), Some(Failure::Oracle));
+        assert_eq!(Failure::of(&eyre::eyre!("other")), None);
+    }
+}
@@ -0,0 +1,939 @@
+use alloy_consensus::{SignableTransaction, TxEip1559, TxEip2930, TxEnvelope, TxLegacy};
+use alloy_eips::eip2718::{Decodable2718, Encodable2718};
+use alloy_eips::eip2930::AccessList;
+use alloy_primitives::{Address, Bytes, FixedBytes, TxKind, U256};
+use alloy_rlp::{Decodable, Encodable, RlpDecodable, RlpEncodable};
+use eyre::Result;
+
+/// The largest number of blocks, transactions or bits a span batch may hold,
+/// and the largest size of a transaction's data in it.
+pub const MAX_SPAN_BATCH_ELEMENT_COUNT: u64 = 10_000_000;
+
+/// ## RawSpanBatch
+///
+/// A RawSpanBatch is a span batch as it is encoded in a channel: a run of
+/// consecutive L2 blocks, with their fields split into columns so that the
+/// channel compresses well.
+///
+/// The prefix holds the first block's timestamp relative to L2 genesis, the last
+/// block's L1 origin number and the first 20 bytes of the parent block hash and
+/// of the last block's L1 origin hash. The payload holds the block count, a bit
+/// per block flagging an L1 origin change, each block's transaction count and
+/// the transactions. Integers are unsigned varints and bit lists are big-endian
+/// integers, whose lowest bit belongs to the first element.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RawSpanBatch {
+    /// The first block's timestamp minus the L2 genesis timestamp.
+    pub rel_timestamp: u64,
+    /// The L1 origin number of the last block.
+    pub l1_origin_num: u64,
+    /// The first 20 bytes of the first block's parent hash.
+    pub parent_check: FixedBytes<20>,
+    /// The first 20 bytes of the last block's L1 origin hash.
+    pub l1_origin_check: FixedBytes<20>,
+    /// Whether each block's L1 origin is the one after its parent's.
+    pub origin_bits: Vec<bool>,
+    /// The number of transactions in each block.
+    pub block_tx_counts: Vec<u64>,
+    /// The transactions of every block.
+    pub txs: SpanBatchTransactions,
+}
+
+/// ## SpanBatchTransactions
+///
+/// The transactions of a span batch, split into columns. Transaction data holds
+/// the type byte, unless it is a legacy transaction, followed by an RLP list of
+/// the fields that have no column of their own.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanBatchTransactions {
+    /// Whether each transaction creates a contract.
+    pub contract_creation_bits: Vec<bool>,
+    /// The y parity of each transaction's signature.
+    pub y_parity_bits: Vec<bool>,
+    /// The r and s values of each transaction's signature.
+    pub signatures: Vec<(U256, U256)>,
+    /// The recipient of each transaction that does not create a contract.
+    pub tos: Vec<Address>,
+    /// The typed data of each transaction.
+    pub datas: Vec<Bytes>,
+    /// The nonce of each transaction.
+    pub nonces: Vec<u64>,
+    /// The gas limit of each transaction.
+    pub gases: Vec<u64>,
+    /// Whether each legacy transaction is replay protected.
+    pub protected_bits: Vec<bool>,
+}
+
+/// ## SpanBatch
+///
+/// A SpanBatch is a [RawSpanBatch] with the timestamp, L1 origin number and
+/// encoded transactions of each of its blocks derived.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanBatch {
+    /// The first 20 bytes of the first block's parent hash.
+    pub parent_check: FixedBytes<20>,
+    /// The first 20 bytes of the last block's L1 origin hash.
+    pub l1_origin_check: FixedBytes<20>,
+    /// The blocks of the batch.
+    pub elements: Vec<SpanBatchElement>,
+}
+
+/// A block of a [SpanBatch].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanBatchElement {
+    /// The number of the block's L1 origin.
+    pub epoch_num: u64,
+    /// The block's timestamp.
+    pub timestamp: u64,
+    /// The block's EIP-2718 encoded transactions.
+    pub transactions: Vec<Bytes>,
+}
+
+impl RawSpanBatch {
+    /// Decodes a span batch, without its batch type byte.
+    pub fn decode(buf: &mut &[u8]) -> Result<Self> {
+        let rel_timestamp = read_uvarint(buf)?;
+        let l1_origin_num = read_uvarint(buf)?;
+        let parent_check = FixedBytes::from_slice(take(buf, 20)?);
+        let l1_origin_check = FixedBytes::from_slice(take(buf, 20)?);
+
+        let block_count = read_uvarint(buf)?;
+        if block_count == 0 {
+            eyre::bail!("span batch holds no blocks");
+        }
+        if block_count > MAX_SPAN_BATCH_ELEMENT_COUNT {
+            eyre::bail!("span batch holds too many blocks: {}", block_count);
+        }
+        let origin_bits = read_bits(buf, block_count)?;
+        let block_tx_counts = (0..block_count)
+            .map(|_| read_count(buf))
+            .collect::<Result<Vec<_>>>()?;
+        let total = block_tx_counts
+            .iter()
+            .try_fold(0u64, |total, &count| total.checked_add(count))
+            .filter(|&total| total <= MAX_SPAN_BATCH_ELEMENT_COUNT)
+            .ok_or_else(|| eyre::eyre!("span batch holds too many transactions"))?;
+        let txs = SpanBatchTransactions::decode(buf, total)?;
+        Ok(Self {
+            rel_timestamp,
+            l1_origin_num,
+            parent_check,
+            l1_origin_check,
+            origin_bits,
+            block_tx_counts,
+            txs,
+        })
+    }
+
+    /// Encodes the span batch, without its batch type byte.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_uvarint(&mut out, self.rel_timestamp);
+        write_uvarint(&mut out, self.l1_origin_num);
+        out.extend_from_slice(self.parent_check.as_slice());
+        out.extend_from_slice(self.l1_origin_check.as_slice());
+        write_uvarint(&mut out, self.origin_bits.len() as u64);
+        write_bits(&mut out, &self.origin_bits);
+        for &count in &self.block_tx_counts {
+            write_uvarint(&mut out, count);
+        }
+        self.txs.encode(&mut out);
+        out
+    }
+
+    /// Derives the blocks of the span batch for a rollup with the given block
+    /// time, genesis timestamp and chain id.
+    pub fn derive(
+        &self,
+        block_time: u64,
+        genesis_timestamp: u64,
+        chain_id: u64,
+    ) -> Result<SpanBatch> {
+        let block_count = self.origin_bits.len();
+        if block_count == 0 || self.block_tx_counts.len() != block_count {
+            eyre::bail!("span batch block count does not match its transaction counts");
+        }
+        let mut epoch_nums = vec![0; block_count];
+        let mut epoch_num = self.l1_origin_num;
+        for i in (0..block_count).rev() {
+            epoch_nums[i] = epoch_num;
+            if self.origin_bits[i] && i > 0 {
+                epoch_num = epoch_num
+                    .checked_sub(1)
+                    .ok_or_else(|| eyre::eyre!("span batch L1 origin underflows"))?;
+            }
+        }
+
+        let transactions = self.txs.transactions(chain_id)?;
+        let total = self
+            .block_tx_counts
+            .iter()
+            .try_fold(0u64, |total, &count| total.checked_add(count));
+        if total != Some(transactions.len() as u64) {
+            eyre::bail!("span batch transaction counts do not match its transactions");
+        }
+        let mut transactions = transactions.into_iter();
+        let first_timestamp = genesis_timestamp
+            .checked_add(self.rel_timestamp)
+            .ok_or_else(|| eyre::eyre!("span batch timestamp overflows"))?;
+        let elements = (0..block_count)
+            .map(|i| {
+                let timestamp = (i as u64)
+                    .checked_mul(block_time)
+                    .and_then(|offset| first_timestamp.checked_add(offset))
+                    .ok_or_else(|| eyre::eyre!("span batch timestamp overflows"))?;
+                Ok(SpanBatchElement {
+                    epoch_num: epoch_nums[i],
+                    timestamp,
+                    transactions: transactions
+                        .by_ref()
+                        .take(self.block_tx_counts[i] as usize)
+                        .collect(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(SpanBatch {
+            parent_check: self.parent_check,
+            l1_origin_check: self.l1_origin_check,
+            elements,
+        })
+    }
+}
+
+impl SpanBatch {
+    /// Encodes the span batch into its raw form for a rollup with the given block
+    /// time, genesis timestamp and chain id.
+    ///
+    /// The first block's origin bit is left unset, as derivation ignores it.
+    pub fn to_raw(
+        &self,
+        block_time: u64,
+        genesis_timestamp: u64,
+        chain_id: u64,
+    ) -> Result<RawSpanBatch> {
+        let (first, last) = match (self.elements.first(), self.elements.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => eyre::bail!("span batch holds no blocks"),
+        };
+        let rel_timestamp = first
+            .timestamp
+            .checked_sub(genesis_timestamp)
+            .ok_or_else(|| eyre::eyre!("span batch starts before L2 genesis"))?;
+        let mut origin_bits = vec![false];
+        for pair in self.elements.windows(2) {
+            if pair[1].timestamp != pair[0].timestamp + block_time {
+                eyre::bail!("span batch blocks are not consecutive");
+            }
+            match pair[1].epoch_num.checked_sub(pair[0].epoch_num) {
+                Some(0) => origin_bits.push(false),
+                Some(1) => origin_bits.push(true),
+                _ => eyre::bail!("span batch L1 origins must advance one block at a time"),
+            }
+        }
+        let transactions = self
+            .elements
+            .iter()
+            .flat_map(|element| element.transactions.iter())
+            .map(|tx| Ok(TxEnvelope::decode_2718(&mut tx.as_ref())?))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RawSpanBatch {
+            rel_timestamp,
+            l1_origin_num: last.epoch_num,
+            parent_check: self.parent_check,
+            l1_origin_check: self.l1_origin_check,
+            origin_bits,
+            block_tx_counts: self
+                .elements
+                .iter()
+                .map(|element| element.transactions.len() as u64)
+                .collect(),
+            txs: SpanBatchTransactions::from_transactions(&transactions, chain_id)?,
+        })
+    }
+}
+
+impl SpanBatchTransactions {
+    /// Decodes the given number of transactions.
+    pub fn decode(buf: &mut &[u8], count: u64) -> Result<Self> {
+        let contract_creation_bits = read_bits(buf, count)?;
+        let y_parity_bits = read_bits(buf, count)?;
+        let signatures = (0..count)
+            .map(|_| {
+                let r = U256::from_be_slice(take(buf, 32)?);
+                let s = U256::from_be_slice(take(buf, 32)?);
+                Ok((r, s))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let tos = contract_creation_bits
+            .iter()
+            .filter(|&&creation| !creation)
+            .map(|_| Ok(Address::from_slice(take(buf, 20)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let datas = (0..count)
+            .map(|_| read_tx_data(buf))
+            .collect::<Result<Vec<_>>>()?;
+        let nonces = (0..count)
+            .map(|_| read_uvarint(buf))
+            .collect::<Result<Vec<_>>>()?;
+        let gases = (0..count)
+            .map(|_| read_uvarint(buf))
+            .collect::<Result<Vec<_>>>()?;
+        let legacy = datas.iter().filter(|data| is_legacy(data)).count();
+        let protected_bits = read_bits(buf, legacy as u64)?;
+        Ok(Self {
+            contract_creation_bits,
+            y_parity_bits,
+            signatures,
+            tos,
+            datas,
+            nonces,
+            gases,
+            protected_bits,
+        })
+    }
+
+    /// Encodes the transactions.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        write_bits(out, &self.contract_creation_bits);
+        write_bits(out, &self.y_parity_bits);
+        for (r, s) in &self.signatures {
+            out.extend_from_slice(&r.to_be_bytes::<32>());
+            out.extend_from_slice(&s.to_be_bytes::<32>());
+        }
+        for to in &self.tos {
+            out.extend_from_slice(to.as_slice());
+        }
+        for data in &self.datas {
+            out.extend_from_slice(data);
+        }
+        for &nonce in &self.nonces {
+            write_uvarint(out, nonce);
+        }
+        for &gas in &self.gases {
+            write_uvarint(out, gas);
+        }
+        write_bits(out, &self.protected_bits);
+    }
+
+    /// Splits signed transactions into columns. Only legacy, EIP-2930 and EIP-1559
+    /// transactions can be held by a span batch.
+    pub fn from_transactions(transactions: &[TxEnvelope], chain_id: u64) -> Result<Self> {
+        let mut txs = Self::default();
+        for tx in transactions {
+            let (to, signature, nonce, gas_limit, data) = match tx {
+                TxEnvelope::Legacy(signed) => {
+                    let tx = signed.tx();
+                    if tx.chain_id.is_some_and(|id| id != chain_id) {
+                        eyre::bail!("transaction is for chain {:?}", tx.chain_id);
+                    }
+                    txs.protected_bits.push(tx.chain_id.is_some());
+                    let data = TxData::Legacy(LegacyTxData {
+                        value: tx.value,
+                        gas_price: tx.gas_price,
+                        data: tx.input.clone(),
+                    });
+                    (tx.to, signed.signature(), tx.nonce, tx.gas_limit, data)
+                }
+                TxEnvelope::Eip2930(signed) => {
+                    let tx = signed.tx();
+                    let data = TxData::Eip2930(Eip2930TxData {
+                        value: tx.value,
+                        gas_price: tx.gas_price,
+                        data: tx.input.clone(),
+                        access_list: tx.access_list.clone(),
+                    });
+                    (tx.to, signed.signature(), tx.nonce, tx.gas_limit, data)
+                }
+                TxEnvelope::Eip1559(signed) => {
+                    let tx = signed.tx();
+                    let data = TxData::Eip1559(Eip1559TxData {
+                        value: tx.value,
+                        max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+                        max_fee_per_gas: tx.max_fee_per_gas,
+                        data: tx.input.clone(),
+                        access_list: tx.access_list.clone(),
+                    });
+                    (tx.to, signed.signature(), tx.nonce, tx.gas_limit, data)
+                }
+                other => eyre::bail!(
+                    "transaction type {} cannot be held by a span batch",
+                    other.tx_type() as u8
+                ),
+            };
+            txs.contract_creation_bits.push(to.is_create());
+            if let TxKind::Call(to) = to {
+                txs.tos.push(to);
+            }
+            txs.y_parity_bits.push(signature.v().y_parity());
+            txs.signatures.push((signature.r(), signature.s()));
+            txs.datas.push(data.encode().into());
+            txs.nonces.push(nonce);
+            txs.gases.push(
+                gas_limit
+                    .try_into()
+                    .map_err(|_| eyre::eyre!("transaction gas limit overflows"))?,
+            );
+        }
+        Ok(txs)
+    }
+
+    /// Rebuilds the EIP-2718 encoded signed transactions for the given chain id.
+    pub fn transactions(&self, chain_id: u64) -> Result<Vec<Bytes>> {
+        let count = self.datas.len();
+        if self.contract_creation_bits.len() != count
+            || self.y_parity_bits.len() != count
+            || self.signatures.len() != count
+            || self.nonces.len() != count
+            || self.gases.len() != count
+        {
+            eyre::bail!("span batch transaction columns differ in length");
+        }
+        let mut tos = self.tos.iter();
+        let mut protected_bits = self.protected_bits.iter();
+        let mut transactions = Vec::with_capacity(count);
+        for i in 0..count {
+            let to = if self.contract_creation_bits[i] {
+                TxKind::Create
+            } else {
+                TxKind::Call(
+                    *tos.next()
+                        .ok_or_else(|| eyre::eyre!("span batch is missing a recipient"))?,
+                )
+            };
+            let (r, s) = self.signatures[i];
+            let y_parity = self.y_parity_bits[i];
+            let nonce = self.nonces[i];
+            let gas_limit = self.gases[i] as u128;
+            let tx = match TxData::decode(&self.datas[i])? {
+                TxData::Legacy(data) => {
+                    let protected = *protected_bits
+                        .next()
+                        .ok_or_else(|| eyre::eyre!("span batch is missing a protected bit"))?;
+                    let v = if protected {
+                        chain_id * 2 + 35 + y_parity as u64
+                    } else {
+                        27 + y_parity as u64
+                    };
+                    let tx = TxLegacy {
+                        chain_id: protected.then_some(chain_id),
+                        nonce,
+                        gas_price: data.gas_price,
+                        gas_limit,
+                        to,
+                        value: data.value,
+                        input: data.data,
+                    };
+                    TxEnvelope::Legacy(tx.into_signed(signature(r, s, v)?))
+                }
+                TxData::Eip2930(data) => {
+                    let tx = TxEip2930 {
+                        chain_id,
+                        nonce,
+                        gas_price: data.gas_price,
+                        gas_limit,
+                        to,
+                        value: data.value,
+                        access_list: data.access_list,
+                        input: data.data,
+                    };
+                    TxEnvelope::Eip2930(tx.into_signed(signature(r, s, y_parity as u64)?))
+                }
+                TxData::Eip1559(data) => {
+                    let tx = TxEip1559 {
+                        chain_id,
+                        nonce,
+                        gas_limit,
+                        max_fee_per_gas: data.max_fee_per_gas,
+                        max_priority_fee_per_gas: data.max_priority_fee_per_gas,
+                        to,
+                        value: data.value,
+                        access_list: data.access_list,
+                        input: data.data,
+                    };
+                    TxEnvelope::Eip1559(tx.into_signed(signature(r, s, y_parity as u64)?))
+                }
+            };
+            transactions.push(tx.encoded_2718().into());
+        }
+        if tos.next().is_some() || protected_bits.next().is_some() {
+            eyre::bail!("span batch holds unused recipients or protected bits");
+        }
+        Ok(transactions)
+    }
+}
+
+/// The fields of a legacy transaction without a column of their own.
+#[derive(Debug, RlpEncodable, RlpDecodable)]
+struct LegacyTxData {
+    value: U256,
+    gas_price: u128,
+    data: Bytes,
+}
+
+/// The fields of an EIP-2930 transaction without a column of their own.
+#[derive(Debug, RlpEncodable, RlpDecodable)]
+struct Eip2930TxData {
+    value: U256,
+    gas_price: u128,
+    data: Bytes,
+    access_list: AccessList,
+}
+
+/// The fields of an EIP-1559 transaction without a column of their own.
+#[derive(Debug, RlpEncodable, RlpDecodable)]
+struct Eip1559TxData {
+    value: U256,
+    max_priority_fee_per_gas: u128,
+    max_fee_per_gas: u128,
+    data: Bytes,
+    access_list: AccessList,
+}
+
+/// The typed data of a span batch transaction.
+#[derive(Debug)]
+enum TxData {
+    Legacy(LegacyTxData),
+    Eip2930(Eip2930TxData),
+    Eip1559(Eip1559TxData),
+}
+
+impl TxData {
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut buf = data;
+        let tx = match data.first() {
+            Some(&ty) if ty >= 0xc0 => Self::Legacy(LegacyTxData::decode(&mut buf)?),
+            Some(1) => Self::Eip2930(Eip2930TxData::decode(&mut &data[1..])?),
+            Some(2) => Self::Eip1559(Eip1559TxData::decode(&mut &data[1..])?),
+            Some(ty) => eyre::bail!("span batch transaction type {} is not supported", ty),
+            None => eyre::bail!("span batch transaction data is empty"),
+        };
+        Ok(tx)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Self::Legacy(data) => data.encode(&mut out),
+            Self::Eip2930(data) => {
+                out.push(1);
+                data.encode(&mut out);
+            }
+            Self::Eip1559(data) => {
+                out.push(2);
+                data.encode(&mut out);
+            }
+        }
+        out
+    }
+}
+
+/// Returns true if the transaction data is a legacy transaction's.
+fn is_legacy(data: &[u8]) -> bool {
+    data.first().is_some_and(|&ty| ty >= 0xc0)
+}
+
+/// Builds a transaction signature from its values, with `v` being the y parity
+/// for typed transactions and the legacy, possibly EIP-155, `v` otherwise.
+#[allow(deprecated)]
+fn signature(r: U256, s: U256, v: u64) -> Result<alloy_primitives::Signature> {
+    Ok(alloy_primitives::Signature::from_rs_and_parity(r, s, v)?)
+}
+
+/// Reads a transaction's data: an optional type byte followed by an RLP list.
+fn read_tx_data(buf: &mut &[u8]) -> Result<Bytes> {
+    let type_len = match buf.first() {
+        Some(&ty) if ty <= 0x7f => 1,
+        Some(_) => 0,
+        None => eyre::bail!("span batch truncated"),
+    };
+    let mut rlp = &buf[type_len..];
+    let header = alloy_rlp::Header::decode(&mut rlp)?;
+    if !header.list {
+        eyre::bail!("span batch transaction data must be an RLP list");
+    }
+    let header_len = buf.len() - type_len - rlp.len();
+    let len = type_len + header_len + header.payload_length;
+    if len as u64 > MAX_SPAN_BATCH_ELEMENT_COUNT {
+        eyre::bail!("span batch transaction data is too large");
+    }
+    Ok(Bytes::copy_from_slice(take(buf, len)?))
+}
+
+/// Reads a transaction count, which is bounded by the span batch limits.
+fn read_count(buf: &mut &[u8]) -> Result<u64> {
+    let count = read_uvarint(buf)?;
+    if count > MAX_SPAN_BATCH_ELEMENT_COUNT {
+        eyre::bail!("span batch block holds too many transactions: {}", count);
+    }
+    Ok(count)
+}
+
+/// Reads a bit list of the given length.
+fn read_bits(buf: &mut &[u8], len: u64) -> Result<Vec<bool>> {
+    let byte_len = len / 8 + (len % 8 != 0) as u64;
+    if byte_len > MAX_SPAN_BATCH_ELEMENT_COUNT / 8 {
+        eyre::bail!("span batch bit list is too long: {}", len);
+    }
+    let bytes = take(buf, byte_len as usize)?;
+    let bit = |i: u64| (bytes[bytes.len() - 1 - (i / 8) as usize] >> (i % 8)) & 1 == 1;
+    if (len..byte_len * 8).any(bit) {
+        eyre::bail!("span batch bit list has bits past its length {}", len);
+    }
+    Ok((0..len).map(bit).collect())
+}
+
+/// Writes a bit list.
+fn write_bits(out: &mut Vec<u8>, bits: &[bool]) {
+    let mut bytes = vec![0; (bits.len() + 7) / 8];
+    let len = bytes.len();
+    for (i, _) in bits.iter().enumerate().filter(|(_, &bit)| bit) {
+        bytes[len - 1 - i / 8] |= 1 << (i % 8);
+    }
+    out.extend_from_slice(&bytes);
+}
+
+/// Reads an unsigned LEB128 varint.
+fn read_uvarint(buf: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let byte = take(buf, 1)?[0];
+        if i == 9 && byte > 1 {
+            eyre::bail!("span batch varint overflows");
+        }
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte < 0x80 {
+            return Ok(value);
+        }
+    }
+    eyre::bail!("span batch varint overflows")
+}
+
+/// Writes an unsigned LEB128 varint.
+fn write_uvarint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Splits the next `len` bytes off the front of the buffer.
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if buf.len() < len {
+        eyre::bail!(
+            "span batch truncated: need {} bytes, have {}",
+            len,
+            buf.len()
+        );
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Ok(head)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloy_primitives::{address, b256, hex};
+    use k256::ecdsa::SigningKey;
+
+    /// Signs a transaction with a fixed test key.
+    #[allow(deprecated)]
+    pub(crate) fn sign<T: SignableTransaction<alloy_primitives::Signature>>(
+        tx: T,
+        eip155_chain_id: Option<u64>,
+    ) -> alloy_consensus::Signed<T> {
+        let key = SigningKey::from_slice(&[0x42; 32]).unwrap();
+        let (sig, recid) = key
+            .sign_prehash_recoverable(tx.signature_hash().as_slice())
+            .unwrap();
+        let r = U256::from_be_slice(&sig.r().to_bytes());
+        let s = U256::from_be_slice(&sig.s().to_bytes());
+        let y_parity = recid.is_y_odd();
+        let v = match eip155_chain_id {
+            Some(chain_id) => chain_id * 2 + 35 + y_parity as u64,
+            None if tx.chain_id().is_none() => 27 + y_parity as u64,
+            None => y_parity as u64,
+        };
+        tx.into_signed(signature(r, s, v).unwrap())
+    }
+
+    /// Returns one transaction of each type a span batch can hold.
+    pub(crate) fn transactions(chain_id: u64) -> Vec<TxEnvelope> {
+        let to = TxKind::Call(address!("4200000000000000000000000000000000000016"));
+        let legacy = TxLegacy {
+            chain_id: Some(chain_id),
+            nonce: 1,
+            gas_price: 7,
+            gas_limit: 21_000,
+            to,
+            value: U256::from(3),
+            input: Bytes::from_static(b"legacy"),
+        };
+        let unprotected = TxLegacy {
+            chain_id: None,
+            nonce: 2,
+            to: TxKind::Create,
+            ..legacy.clone()
+        };
+        let access_list = AccessList(vec![alloy_eips::eip2930::AccessListItem {
+            address: Address::repeat_byte(1),
+            storage_keys: vec![b256!(
+                "0000000000000000000000000000000000000000000000000000000000000001"
+            )],
+        }]);
+        let eip2930 = TxEip2930 {
+            chain_id,
+            nonce: 3,
+            gas_price: 8,
+            gas_limit: 50_000,
+            to,
+            value: U256::ZERO,
+            access_list: access_list.clone(),
+            input: Bytes::new(),
+        };
+        let eip1559 = TxEip1559 {
+            chain_id,
+            nonce: 4,
+            gas_limit: 1 << 40,
+            max_fee_per_gas: 100,
+            max_priority_fee_per_gas: 2,
+            to: TxKind::Create,
+            value: U256::MAX,
+            access_list,
+            input: Bytes::from_static(&[0xfe; 100]),
+        };
+        vec![
+            TxEnvelope::Legacy(sign(legacy, Some(chain_id))),
+            TxEnvelope::Legacy(sign(unprotected, None)),
+            TxEnvelope::Eip2930(sign(eip2930, None)),
+            TxEnvelope::Eip1559(sign(eip1559, None)),
+        ]
+    }
+
+    #[test]
+    fn test_uvarint_vectors() {
+        for (value, encoded) in [
+            (0, vec![0x00]),
+            (1, vec![0x01]),
+            (127, vec![0x7f]),
+            (128, vec![0x80, 0x01]),
+            (300, vec![0xac, 0x02]),
+            (u64::MAX, [[0xff; 9].as_slice(), &[0x01]].concat()),
+        ] {
+            let mut out = Vec::new();
+            write_uvarint(&mut out, value);
+            assert_eq!(out, encoded);
+            assert_eq!(read_uvarint(&mut encoded.as_slice()).unwrap(), value);
+        }
+        assert!(read_uvarint(&mut [0xff; 9].as_slice()).is_err());
+        assert!(read_uvarint(&mut [[0xff; 9].as_slice(), &[0x02]].concat().as_slice()).is_err());
+        assert!(read_uvarint(&mut [0xff; 11].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_bit_list_vectors() {
+        let bits = [
+            true, false, false, false, false, false, false, false, false, true,
+        ];
+        let mut out = Vec::new();
+        write_bits(&mut out, &bits);
+        assert_eq!(out, [0x02, 0x01]);
+        assert_eq!(read_bits(&mut out.as_slice(), 10).unwrap(), bits);
+
+        assert!(read_bits(&mut [0x04, 0x01].as_slice(), 10).is_err());
+        assert!(read_bits(&mut [0x01].as_slice(), 10).is_err());
+        assert_eq!(
+            read_bits(&mut [].as_slice(), 0).unwrap(),
+            Vec::<bool>::new()
+        );
+    }
+
+    #[test]
+    fn test_raw_span_batch_encoding() {
+        let raw = RawSpanBatch {
+            rel_timestamp: 300,
+            l1_origin_num: 5,
+            parent_check: FixedBytes::repeat_byte(0xaa),
+            l1_origin_check: FixedBytes::repeat_byte(0xbb),
+            origin_bits: vec![false, true],
+            block_tx_counts: vec![0, 1],
+            txs: SpanBatchTransactions {
+                contract_creation_bits: vec![false],
+                y_parity_bits: vec![true],
+                signatures: vec![(U256::from(1), U256::from(2))],
+                tos: vec![Address::repeat_byte(0xcc)],
+                datas: vec![Bytes::from_static(&hex!("c3010203"))],
+                nonces: vec![128],
+                gases: vec![21_000],
+                protected_bits: vec![true],
+            },
+        };
+        let encoded = raw.encode();
+        let expected = [
+            hex!("ac02 05").as_slice(),
+            &[0xaa; 20],
+            &[0xbb; 20],
+            &hex!("02 02 00 01"),
+            &hex!("00 01"),
+            &U256::from(1).to_be_bytes::<32>(),
+            &U256::from(2).to_be_bytes::<32>(),
+            &[0xcc; 20],
+            &hex!("c3010203"),
+            &hex!("8001"),
+            &hex!("88a401"),
+            &hex!("01"),
+        ]
+        .concat();
+        assert_eq!(encoded, expected);
+        assert_eq!(RawSpanBatch::decode(&mut encoded.as_slice()).unwrap(), raw);
+    }
+
+    #[test]
+    fn test_raw_span_batch_rejects_invalid_encodings() {
+        let empty = [hex!("00 00").as_slice(), &[0; 40], &hex!("00")].concat();
+        assert!(RawSpanBatch::decode(&mut empty.as_slice()).is_err());
+
+        let mut too_many = [hex!("00 00").as_slice(), &[0; 40]].concat();
+        write_uvarint(&mut too_many, MAX_SPAN_BATCH_ELEMENT_COUNT + 1);
+        assert!(RawSpanBatch::decode(&mut too_many.as_slice()).is_err());
+
+        let mut truncated = SpanBatch {
+            elements: vec![SpanBatchElement::default()],
+            ..Default::default()
+        }
+        .to_raw(2, 0, 10)
+        .unwrap()
+        .encode();
+        truncated.pop();
+        assert!(RawSpanBatch::decode(&mut truncated.as_slice()).is_err());
+
+        assert!(read_tx_data(&mut hex!("03c0").as_slice()).is_ok());
+        assert!(read_tx_data(&mut hex!("0280").as_slice()).is_err());
+        assert!(TxData::decode(&hex!("03c0")).is_err());
+    }
+
+    /// Ported from op-node's `TestSpanBatchMaxTxData`,
+    /// `TestSpanBatchMaxOriginBitsLength` and
+    /// `TestSpanBatchTotalBlockTxCountNotOverflow`
+    /// (op-node/rollup/derive/span_batch_test.go).
+    #[test]
+    fn test_span_batch_size_limits() {
+        let len = MAX_SPAN_BATCH_ELEMENT_COUNT as usize + 1;
+        let mut too_large = vec![0x02, 0xfa, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+        too_large.resize(too_large.len() + len, 0);
+        assert!(read_tx_data(&mut too_large.as_slice()).is_err());
+
+        assert!(read_bits(&mut [].as_slice(), u64::MAX).is_err());
+
+        let mut counts = Vec::new();
+        write_uvarint(&mut counts, MAX_SPAN_BATCH_ELEMENT_COUNT - 1);
+        write_uvarint(&mut counts, u64::MAX - 1);
+        let mut buf = counts.as_slice();
+        assert_eq!(
+            read_count(&mut buf).unwrap(),
+            MAX_SPAN_BATCH_ELEMENT_COUNT - 1
+        );
+        assert!(read_count(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_span_batch_roundtrip() {
+        let txs: Vec<Bytes> = transactions(10)
+            .iter()
+            .map(|tx| tx.encoded_2718().into())
+            .collect();
+        let batch = SpanBatch {
+            parent_check: FixedBytes::repeat_byte(1),
+            l1_origin_check: FixedBytes::repeat_byte(2),
+            elements: vec![
+                SpanBatchElement {
+                    epoch_num: 7,
+                    timestamp: 102,
+                    transactions: txs[..1].to_vec(),
+                },
+                SpanBatchElement {
+                    epoch_num: 7,
+                    timestamp: 104,
+                    transactions: vec![],
+                },
+                SpanBatchElement {
+                    epoch_num: 8,
+                    timestamp: 106,
+                    transactions: txs[1..].to_vec(),
+                },
+            ],
+        };
+        let raw = batch.to_raw(2, 100, 10).unwrap();
+        assert_eq!(raw.rel_timestamp, 2);
+        assert_eq!(raw.l1_origin_num, 8);
+        assert_eq!(raw.origin_bits, [false, false, true]);
+        assert_eq!(raw.block_tx_counts, [1, 0, 3]);
+        assert_eq!(raw.txs.contract_creation_bits, [false, true, false, true]);
+        assert_eq!(raw.txs.protected_bits, [true, false]);
+        assert_eq!(raw.txs.tos.len(), 2);
+        assert_eq!(raw.txs.gases[3], 1 << 40);
+
+        let decoded = RawSpanBatch::decode(&mut raw.encode().as_slice()).unwrap();
+        assert_eq!(decoded, raw);
+        assert_eq!(decoded.derive(2, 100, 10).unwrap(), batch);
+    }
+
+    #[test]
+    fn test_span_batch_transactions_keep_their_hashes() {
+        let transactions = transactions(10);
+        let txs = SpanBatchTransactions::from_transactions(&transactions, 10).unwrap();
+        for (tx, encoded) in transactions.iter().zip(txs.transactions(10).unwrap()) {
+            let decoded = TxEnvelope::decode_2718(&mut encoded.as_ref()).unwrap();
+            assert_eq!(decoded.tx_hash(), tx.tx_hash());
+            assert_eq!(
+                decoded.recover_signer().unwrap(),
+                tx.recover_signer().unwrap()
+            );
+        }
+        assert!(SpanBatchTransactions::from_transactions(&transactions, 11).is_err());
+    }
+
+    #[test]
+    fn test_span_batch_derives_epochs_backwards() {
+        let raw = RawSpanBatch {
+            rel_timestamp: 10,
+            l1_origin_num: 20,
+            origin_bits: vec![true, true, false, true],
+            block_tx_counts: vec![0; 4],
+            ..Default::default()
+        };
+        let batch = raw.derive(2, 1000, 10).unwrap();
+        let epochs: Vec<_> = batch.elements.iter().map(|e| e.epoch_num).collect();
+        let timestamps: Vec<_> = batch.elements.iter().map(|e| e.timestamp).collect();
+        assert_eq!(epochs, [18, 19, 19, 20]);
+        assert_eq!(timestamps, [1010, 1012, 1014, 1016]);
+
+        let underflow = RawSpanBatch {
+            l1_origin_num: 0,
+            ..raw
+        };
+        assert!(underflow.derive(2, 1000, 10).is_err());
+    }
+
+    #[test]
+    fn test_span_batch_rejects_unrepresentable_batches() {
+        let element = |epoch_num, timestamp| SpanBatchElement {
+            epoch_num,
+            timestamp,
+            transactions: vec![],
+        };
+        let batch = |elements| SpanBatch {
+            elements,
+            ..Default::default()
+        };
+        assert!(batch(vec![]).to_raw(2, 0, 10).is_err());
+        assert!(batch(vec![element(1, 0), element(3, 2)])
+            .to_raw(2, 0, 10)
+            .is_err());
+        assert!(batch(vec![element(1, 0), element(1, 3)])
+            .to_raw(2, 0, 10)
+            .is_err());
+        assert!(batch(vec![element(1, 0)]).to_raw(2, 1, 10).is_err());
+    }
+}
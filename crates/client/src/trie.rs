@@ -0,0 +1,706 @@
+use alloy_primitives::{keccak256, B256};
+use alloy_rlp::{Encodable, Header, EMPTY_STRING_CODE};
+use eyre::Result;
+
+use palmtop_preimage::client::OracleClient;
+use palmtop_primitives::{HintType, Hinter, TypedHint};
+
+use crate::mpt::{unpack_nibbles, NodeRef, TrieNode, EMPTY_ROOT_HASH};
+use crate::oracle::get_keccak;
+
+/// ## NodeProvider
+///
+/// The NodeProvider trait defines the interface for fetching the nodes of a
+/// [MutableTrie] by hash.
+pub trait NodeProvider {
+    /// Returns the encoding of the node with the given hash.
+    fn node(&mut self, hash: B256) -> Result<Vec<u8>>;
+
+    /// Asks for the node with the given hash to be prepared before it is fetched.
+    ///
+    /// Nodes on the path of a key are prepared along with the key, but a deletion
+    /// that collapses a branch also needs the sibling of the deleted key.
+    fn hint_node(&mut self, hash: B256) -> Result<()>;
+}
+
+/// ## OracleNodeProvider
+///
+/// The OracleNodeProvider fetches L2 state trie nodes as keccak256 preimages from
+/// the oracle, hinting `l2-state-node` for the nodes that must be prepared.
+#[derive(Debug)]
+pub struct OracleNodeProvider<O, H> {
+    oracle: O,
+    hinter: H,
+}
+
+impl<O, H> OracleNodeProvider<O, H> {
+    /// Creates a new [OracleNodeProvider] from the given oracle and hinter.
+    pub fn new(oracle: O, hinter: H) -> Self {
+        Self { oracle, hinter }
+    }
+}
+
+impl<O, H> NodeProvider for OracleNodeProvider<O, H>
+where
+    O: OracleClient,
+    H: Hinter,
+{
+    fn node(&mut self, hash: B256) -> Result<Vec<u8>> {
+        get_keccak(&mut self.oracle, hash)
+    }
+
+    fn hint_node(&mut self, hash: B256) -> Result<()> {
+        self.hinter
+            .hint(TypedHint::new(HintType::L2StateNode, hash.to_vec()))
+    }
+}
+
+/// A node of a [MutableTrie], with its path segments as nibbles.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+enum Node {
+    /// No node.
+    #[default]
+    Empty,
+    /// A node that has not been fetched yet, referenced by its hash.
+    Hash(B256),
+    /// A leaf holding the value at the rest of its path.
+    Leaf { path: Vec<u8>, value: Vec<u8> },
+    /// An extension of the path shared by every key below its child.
+    Extension { path: Vec<u8>, child: Box<Node> },
+    /// A branch on the next nibble of the path.
+    Branch {
+        children: Box<[Node; 16]>,
+        value: Option<Vec<u8>>,
+    },
+}
+
+impl Node {
+    /// Converts a decoded trie node, decoding its inlined children.
+    fn from_trie_node(node: TrieNode) -> Result<Self> {
+        Ok(match node {
+            TrieNode::Empty => Self::Empty,
+            TrieNode::Leaf { path, value } => Self::Leaf { path, value },
+            TrieNode::Extension { path, child } => Self::Extension {
+                path,
+                child: Box::new(Self::from_ref(child)?),
+            },
+            TrieNode::Branch { children, value } => {
+                let mut nodes: [Node; 16] = Default::default();
+                let children: [NodeRef; 16] = *children;
+                for (node, child) in nodes.iter_mut().zip(children) {
+                    *node = Self::from_ref(child)?;
+                }
+                Self::Branch {
+                    children: Box::new(nodes),
+                    value,
+                }
+            }
+        })
+    }
+
+    /// Converts a reference to a child, decoding it if it is inlined.
+    fn from_ref(child: NodeRef) -> Result<Self> {
+        match child {
+            NodeRef::Empty => Ok(Self::Empty),
+            NodeRef::Hash(hash) => Ok(Self::Hash(hash)),
+            NodeRef::Inline(encoded) => Self::from_trie_node(TrieNode::decode(&encoded)?),
+        }
+    }
+
+    /// Returns a branch without children or value.
+    fn empty_branch() -> Self {
+        Self::Branch {
+            children: Default::default(),
+            value: None,
+        }
+    }
+
+    /// Returns the RLP encoding of the node.
+    fn encode(&self) -> Vec<u8> {
+        let payload = match self {
+            Self::Empty => return vec![EMPTY_STRING_CODE],
+            Self::Hash(_) => unreachable!("unresolved nodes are referenced by hash"),
+            Self::Leaf { path, value } => {
+                let mut payload = encode_bytes(&encode_path(path, true));
+                payload.extend(encode_bytes(value));
+                payload
+            }
+            Self::Extension { path, child } => {
+                let mut payload = encode_bytes(&encode_path(path, false));
+                payload.extend(child.reference());
+                payload
+            }
+            Self::Branch { children, value } => {
+                let mut payload = children
+                    .iter()
+                    .flat_map(|child| child.reference())
+                    .collect::<Vec<_>>();
+                payload.extend(encode_bytes(value.as_deref().unwrap_or_default()));
+                payload
+            }
+        };
+        let mut encoded = Vec::with_capacity(payload.len() + 3);
+        Header {
+            list: true,
+            payload_length: payload.len(),
+        }
+        .encode(&mut encoded);
+        encoded.extend(payload);
+        encoded
+    }
+
+    /// Returns the encoding of the node as referenced from its parent: inlined if
+    /// its encoding is shorter than 32 bytes, or by its hash otherwise.
+    fn reference(&self) -> Vec<u8> {
+        match self {
+            Self::Empty => vec![EMPTY_STRING_CODE],
+            Self::Hash(hash) => encode_bytes(hash.as_slice()),
+            node => {
+                let encoded = node.encode();
+                match encoded.len() < 32 {
+                    true => encoded,
+                    false => encode_bytes(keccak256(&encoded).as_slice()),
+                }
+            }
+        }
+    }
+}
+
+/// ## MutableTrie
+///
+/// The MutableTrie is a Merkle-Patricia trie that can be read and modified in
+/// place, like the account and storage tries whose post-state root the program
+/// computes after executing a block.
+///
+/// Nodes are fetched from its [NodeProvider] only when an operation touches them,
+/// and every node that is never touched stays referenced by its hash, so that the
+/// root can be recomputed from the modified nodes alone.
+#[derive(Debug)]
+pub struct MutableTrie<P> {
+    root: Node,
+    provider: P,
+}
+
+impl<P: NodeProvider> MutableTrie<P> {
+    /// Creates a new [MutableTrie] with the given root, fetching its nodes from
+    /// the given provider.
+    pub fn new(root: B256, provider: P) -> Self {
+        let root = match root == EMPTY_ROOT_HASH {
+            true => Node::Empty,
+            false => Node::Hash(root),
+        };
+        Self { root, provider }
+    }
+
+    /// Returns the value of the given key, if any.
+    pub fn get(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let path = unpack_nibbles(key);
+        let mut rest = path.as_slice();
+        let mut node = &mut self.root;
+        loop {
+            resolve(&mut self.provider, node)?;
+            match node {
+                Node::Empty | Node::Hash(_) => return Ok(None),
+                Node::Leaf { path, value } => {
+                    return Ok((path.as_slice() == rest).then(|| value.clone()))
+                }
+                Node::Extension { path, child } => {
+                    let Some(tail) = rest.strip_prefix(path.as_slice()) else {
+                        return Ok(None);
+                    };
+                    rest = tail;
+                    node = &mut **child;
+                }
+                Node::Branch { children, value } => {
+                    let Some((nibble, tail)) = rest.split_first() else {
+                        return Ok(value.clone());
+                    };
+                    rest = tail;
+                    node = &mut children[*nibble as usize];
+                }
+            }
+        }
+    }
+
+    /// Sets the value of the given key, which must not be empty.
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Result<()> {
+        if value.is_empty() {
+            eyre::bail!("cannot insert an empty value, remove the key instead");
+        }
+        insert(
+            &mut self.provider,
+            &mut self.root,
+            &unpack_nibbles(key),
+            value,
+        )
+    }
+
+    /// Removes the given key, returning its value if it was present.
+    pub fn remove(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        remove(&mut self.provider, &mut self.root, &unpack_nibbles(key))
+    }
+
+    /// Returns the root hash of the trie.
+    pub fn root(&self) -> B256 {
+        match &self.root {
+            Node::Empty => EMPTY_ROOT_HASH,
+            Node::Hash(hash) => *hash,
+            node => keccak256(node.encode()),
+        }
+    }
+}
+
+/// Fetches the node if it is only referenced by its hash, replacing the reference.
+fn resolve(provider: &mut impl NodeProvider, node: &mut Node) -> Result<()> {
+    if let Node::Hash(hash) = node {
+        let encoded = provider.node(*hash)?;
+        if keccak256(&encoded) != *hash {
+            eyre::bail!("trie node does not match its hash {}", hash);
+        }
+        *node = Node::from_trie_node(TrieNode::decode(&encoded)?)?;
+    }
+    Ok(())
+}
+
+/// Sets the value at the given path below the node.
+fn insert(
+    provider: &mut impl NodeProvider,
+    node: &mut Node,
+    path: &[u8],
+    value: Vec<u8>,
+) -> Result<()> {
+    resolve(provider, node)?;
+    match node {
+        Node::Empty => {
+            *node = Node::Leaf {
+                path: path.to_vec(),
+                value,
+            };
+        }
+        Node::Leaf {
+            path: leaf_path,
+            value: leaf_value,
+        } => {
+            if leaf_path.as_slice() == path {
+                *leaf_value = value;
+                return Ok(());
+            }
+            let common = common_prefix(leaf_path, path);
+            let mut branch = Node::empty_branch();
+            place(
+                &mut branch,
+                &leaf_path[common..],
+                std::mem::take(leaf_value),
+            );
+            place(&mut branch, &path[common..], value);
+            *node = extend(&path[..common], branch);
+        }
+        Node::Extension {
+            path: extension_path,
+            child,
+        } => {
+            let common = common_prefix(extension_path, path);
+            if common == extension_path.len() {
+                return insert(provider, child, &path[common..], value);
+            }
+            // The path diverges within the extension, so split it at a new branch.
+            let mut branch = Node::empty_branch();
+            let Node::Branch { children, .. } = &mut branch else {
+                unreachable!()
+            };
+            let below = std::mem::take(child.as_mut());
+            children[extension_path[common] as usize] =
+                extend(&extension_path[common + 1..], below);
+            place(&mut branch, &path[common..], value);
+            *node = extend(&path[..common], branch);
+        }
+        Node::Branch {
+            children,
+            value: branch_value,
+        } => match path.split_first() {
+            None => *branch_value = Some(value),
+            Some((nibble, rest)) => insert(provider, &mut children[*nibble as usize], rest, value)?,
+        },
+        Node::Hash(_) => unreachable!("resolved above"),
+    }
+    Ok(())
+}
+
+/// Places a value at the given path below a new branch without children there.
+fn place(branch: &mut Node, path: &[u8], value: Vec<u8>) {
+    let Node::Branch {
+        children,
+        value: branch_value,
+    } = branch
+    else {
+        unreachable!("only called on branches")
+    };
+    match path.split_first() {
+        None => *branch_value = Some(value),
+        Some((nibble, rest)) => {
+            children[*nibble as usize] = Node::Leaf {
+                path: rest.to_vec(),
+                value,
+            }
+        }
+    }
+}
+
+/// Returns the node behind an extension of the given path, merging the path into
+/// the node if it is a leaf or an extension itself.
+fn extend(path: &[u8], node: Node) -> Node {
+    if path.is_empty() {
+        return node;
+    }
+    match node {
+        Node::Leaf { path: rest, value } => Node::Leaf {
+            path: [path, &rest].concat(),
+            value,
+        },
+        Node::Extension { path: rest, child } => Node::Extension {
+            path: [path, &rest].concat(),
+            child,
+        },
+        node => Node::Extension {
+            path: path.to_vec(),
+            child: Box::new(node),
+        },
+    }
+}
+
+/// Removes the value at the given path below the node, returning it if present.
+fn remove(
+    provider: &mut impl NodeProvider,
+    node: &mut Node,
+    path: &[u8],
+) -> Result<Option<Vec<u8>>> {
+    resolve(provider, node)?;
+    let removed = match node {
+        Node::Empty => None,
+        Node::Leaf {
+            path: leaf_path,
+            value,
+        } => {
+            if leaf_path.as_slice() != path {
+                return Ok(None);
+            }
+            let value = std::mem::take(value);
+            *node = Node::Empty;
+            return Ok(Some(value));
+        }
+        Node::Extension {
+            path: extension_path,
+            child,
+        } => {
+            let Some(rest) = path.strip_prefix(extension_path.as_slice()) else {
+                return Ok(None);
+            };
+            let removed = remove(provider, child, rest)?;
+            if removed.is_some() {
+                // The child collapsed if its branch was left with a single child.
+                let extension_path = std::mem::take(extension_path);
+                let child = std::mem::take(child.as_mut());
+                *node = extend(&extension_path, child);
+            }
+            return Ok(removed);
+        }
+        Node::Branch { children, value } => match path.split_first() {
+            None => value.take(),
+            Some((nibble, rest)) => remove(provider, &mut children[*nibble as usize], rest)?,
+        },
+        Node::Hash(_) => unreachable!("resolved above"),
+    };
+    if removed.is_some() {
+        collapse(provider, node)?;
+    }
+    Ok(removed)
+}
+
+/// Collapses a branch left with a single child or only a value into the node
+/// that replaces it, fetching the remaining child if it was never touched.
+fn collapse(provider: &mut impl NodeProvider, node: &mut Node) -> Result<()> {
+    let Node::Branch { children, value } = node else {
+        return Ok(());
+    };
+    let mut remaining = children
+        .iter()
+        .enumerate()
+        .filter(|(_, child)| **child != Node::Empty)
+        .map(|(nibble, _)| nibble);
+    match (remaining.next(), remaining.next(), value.is_some()) {
+        (None, _, true) => {
+            *node = Node::Leaf {
+                path: Vec::new(),
+                value: value.take().unwrap_or_default(),
+            };
+        }
+        (None, _, false) => *node = Node::Empty,
+        (Some(nibble), None, false) => {
+            let mut sibling = std::mem::take(&mut children[nibble]);
+            if let Node::Hash(hash) = sibling {
+                tracing::debug!(target: "palmtop::trie", "Fetching sibling node {}", hash);
+                provider.hint_node(hash)?;
+            }
+            resolve(provider, &mut sibling)?;
+            *node = extend(&[nibble as u8], sibling);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Returns the length of the common prefix of two paths.
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// Returns the RLP encoding of a byte string.
+fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(bytes.len() + 3);
+    bytes.encode(&mut encoded);
+    encoded
+}
+
+/// Returns the hex-prefix encoding of a path of a leaf or an extension.
+fn encode_path(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 };
+    let (first, rest) = match nibbles.len() % 2 {
+        1 => (((flag + 1) << 4) | nibbles[0], &nibbles[1..]),
+        _ => (flag << 4, nibbles),
+    };
+    let mut encoded = vec![first];
+    encoded.extend(rest.chunks_exact(2).map(|pair| (pair[0] << 4) | pair[1]));
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mpt::tests::trie;
+    use crate::program::tests::MapOracle;
+    use alloy_trie::{HashBuilder, Nibbles};
+    use std::collections::{BTreeMap, HashMap, HashSet};
+
+    /// A provider serving nodes like a host: every node in `prepared` can be
+    /// fetched, and hinting a node prepares it from `all`.
+    #[derive(Default)]
+    struct TestProvider {
+        all: HashMap<B256, Vec<u8>>,
+        prepared: HashSet<B256>,
+        hints: Vec<B256>,
+    }
+
+    impl TestProvider {
+        fn new(all: Vec<Vec<u8>>, prepared: Vec<Vec<u8>>) -> Self {
+            Self {
+                all: all
+                    .into_iter()
+                    .map(|node| (keccak256(&node), node))
+                    .collect(),
+                prepared: prepared.iter().map(keccak256).collect(),
+                hints: Vec::new(),
+            }
+        }
+    }
+
+    impl NodeProvider for TestProvider {
+        fn node(&mut self, hash: B256) -> Result<Vec<u8>> {
+            match self.prepared.contains(&hash) {
+                true => Ok(self.all[&hash].clone()),
+                false => eyre::bail!("node {} is not prepared", hash),
+            }
+        }
+
+        fn hint_node(&mut self, hash: B256) -> Result<()> {
+            self.hints.push(hash);
+            self.prepared.insert(hash);
+            Ok(())
+        }
+    }
+
+    /// Returns the root of the trie of the given entries, built independently.
+    fn expected_root(entries: &BTreeMap<Vec<u8>, Vec<u8>>) -> B256 {
+        let mut builder = HashBuilder::default();
+        for (key, value) in entries {
+            builder.add_leaf(Nibbles::unpack(key), value);
+        }
+        builder.root()
+    }
+
+    fn hashed_entries(range: std::ops::Range<u8>) -> BTreeMap<Vec<u8>, Vec<u8>> {
+        range
+            .map(|i| (keccak256([i]).to_vec(), vec![i; 40]))
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_update_remove_recomputes_root() {
+        let mut entries = hashed_entries(0..50);
+        let pairs = entries.clone().into_iter().collect::<Vec<_>>();
+        let (root, nodes) = trie(
+            &pairs,
+            &pairs.iter().map(|(k, _)| k.clone()).collect::<Vec<_>>(),
+        );
+        let mut trie = MutableTrie::new(root, TestProvider::new(nodes.clone(), nodes));
+        assert_eq!(trie.root(), root);
+
+        for i in 50..70u8 {
+            let key = keccak256([i]).to_vec();
+            trie.insert(&key, vec![i; 3]).unwrap();
+            entries.insert(key, vec![i; 3]);
+        }
+        for i in (0..70u8).step_by(3) {
+            let key = keccak256([i]).to_vec();
+            trie.insert(&key, vec![0xff; 50]).unwrap();
+            entries.insert(key, vec![0xff; 50]);
+        }
+        assert_eq!(trie.root(), expected_root(&entries));
+
+        for i in (0..70u8).step_by(2) {
+            let key = keccak256([i]).to_vec();
+            assert_eq!(trie.remove(&key).unwrap(), entries.remove(&key));
+            assert_eq!(trie.root(), expected_root(&entries));
+        }
+        assert_eq!(trie.remove(keccak256([0]).as_slice()).unwrap(), None);
+        for (key, value) in &entries {
+            assert_eq!(trie.get(key).unwrap().as_ref(), Some(value));
+        }
+    }
+
+    /// Returns the root of a trie built by inserting the given entries in order.
+    fn inserted_root(entries: &BTreeMap<Vec<u8>, Vec<u8>>) -> B256 {
+        let mut trie = MutableTrie::new(EMPTY_ROOT_HASH, TestProvider::default());
+        for (key, value) in entries {
+            trie.insert(key, value.clone()).unwrap();
+        }
+        trie.root()
+    }
+
+    #[test]
+    fn test_known_roots() {
+        // Vectors of the Ethereum trie tests, with short keys and inline nodes.
+        let puppy = [
+            ("doe", "reindeer"),
+            ("dog", "puppy"),
+            ("dogglesworth", "cat"),
+        ];
+        let dogs = [
+            ("do", "verb"),
+            ("dog", "puppy"),
+            ("doge", "coin"),
+            ("horse", "stallion"),
+        ];
+        for (entries, root) in [
+            (
+                &puppy[..],
+                "0x8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3",
+            ),
+            (
+                &dogs[..],
+                "0x5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84",
+            ),
+        ] {
+            let mut trie = MutableTrie::new(EMPTY_ROOT_HASH, TestProvider::default());
+            for (key, value) in entries.iter().rev() {
+                trie.insert(key.as_bytes(), value.as_bytes().to_vec())
+                    .unwrap();
+            }
+            assert_eq!(trie.root(), root.parse::<B256>().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_short_keys_and_inline_nodes() {
+        let mut trie = MutableTrie::new(EMPTY_ROOT_HASH, TestProvider::default());
+        let mut entries = BTreeMap::new();
+        let keys = [
+            vec![0x12, 0x34],
+            vec![0x12],
+            vec![0x13],
+            vec![0x20],
+            vec![0x21, 0x00],
+            vec![],
+        ];
+        for key in keys.iter().rev() {
+            let value = vec![key.len() as u8 + 1];
+            trie.insert(key, value.clone()).unwrap();
+            entries.insert(key.clone(), value);
+            assert_eq!(trie.root(), inserted_root(&entries));
+        }
+        for key in [&keys[2], &keys[0], &keys[5], &keys[4], &keys[3], &keys[1]] {
+            assert!(trie.remove(key).unwrap().is_some());
+            entries.remove(key);
+            assert_eq!(trie.root(), inserted_root(&entries));
+            for (key, value) in &entries {
+                assert_eq!(trie.get(key).unwrap().as_ref(), Some(value));
+            }
+        }
+        assert_eq!(trie.root(), EMPTY_ROOT_HASH);
+        assert!(trie.insert(&[1], vec![]).is_err());
+    }
+
+    #[test]
+    fn test_branch_values() {
+        let (short, long) = (vec![0x12], vec![0x12, 0x34]);
+        let only = |key: &Vec<u8>| expected_root(&BTreeMap::from([(key.clone(), vec![1; 40])]));
+        for (removed, kept) in [(&short, &long), (&long, &short)] {
+            let mut trie = MutableTrie::new(EMPTY_ROOT_HASH, TestProvider::default());
+            trie.insert(&short, vec![1; 40]).unwrap();
+            trie.insert(&long, vec![1; 40]).unwrap();
+            assert_eq!(trie.get(&short).unwrap(), Some(vec![1; 40]));
+            trie.remove(removed).unwrap();
+            assert_eq!(trie.root(), only(kept));
+        }
+    }
+
+    #[test]
+    fn test_lazily_fetches_touched_nodes() {
+        let entries = hashed_entries(0..100);
+        let pairs = entries.clone().into_iter().collect::<Vec<_>>();
+        let all = pairs.iter().map(|(k, _)| k.clone()).collect::<Vec<_>>();
+        let (root, nodes) = trie(&pairs, &all);
+        let key = keccak256([200]).to_vec();
+        let (_, path_nodes) = trie(&pairs, std::slice::from_ref(&key));
+
+        // Only the nodes on the path of the inserted key are needed.
+        let mut trie = MutableTrie::new(root, TestProvider::new(nodes, path_nodes));
+        trie.insert(&key, vec![1; 40]).unwrap();
+        let mut expected = entries;
+        expected.insert(key, vec![1; 40]);
+        assert_eq!(trie.root(), expected_root(&expected));
+    }
+
+    #[test]
+    fn test_remove_hints_collapsed_sibling() {
+        let entries = hashed_entries(0..2);
+        let pairs = entries.clone().into_iter().collect::<Vec<_>>();
+        let all = pairs.iter().map(|(k, _)| k.clone()).collect::<Vec<_>>();
+        let (root, nodes) = trie(&pairs, &all);
+        let (removed, kept) = (all[0].clone(), all[1].clone());
+        let (_, path_nodes) = trie(&pairs, std::slice::from_ref(&removed));
+
+        let mut trie = MutableTrie::new(root, TestProvider::new(nodes, path_nodes.clone()));
+        trie.remove(&removed).unwrap();
+        let mut expected = entries;
+        expected.remove(&removed);
+        assert_eq!(trie.root(), expected_root(&expected));
+
+        let sibling = trie.provider.hints.clone();
+        assert_eq!(sibling.len(), 1);
+        assert!(!path_nodes.iter().any(|node| keccak256(node) == sibling[0]));
+        assert_eq!(trie.get(&kept).unwrap().as_ref(), expected.get(&kept));
+    }
+
+    #[test]
+    fn test_oracle_node_provider_hints_state_nodes() {
+        let mut oracle = MapOracle::default();
+        let hash = oracle.insert_keccak(vec![0xc0]);
+        let mut hinter = MapOracle::default();
+        let mut provider = OracleNodeProvider::new(oracle, &mut hinter);
+        provider.hint_node(hash).unwrap();
+        assert_eq!(provider.node(hash).unwrap(), vec![0xc0]);
+        assert!(provider.node(B256::ZERO).is_err());
+        drop(provider);
+        assert_eq!(hinter.hints, [format!("l2-state-node {hash}")]);
+    }
+}
@@ -0,0 +1,176 @@
+use alloy_primitives::{Bytes, FixedBytes};
+use eyre::Result;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// The timeout of a single beacon API request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// ## BeaconClient
+///
+/// A blocking client of the beacon node HTTP API, used by the host to fetch the
+/// blob sidecars the L1 execution RPC does not serve.
+#[derive(Debug)]
+pub struct BeaconClient {
+    url: String,
+    agent: ureq::Agent,
+    timing: OnceLock<SlotTiming>,
+}
+
+/// The beacon chain's genesis time and slot duration, in seconds.
+#[derive(Debug, Clone, Copy)]
+struct SlotTiming {
+    genesis_time: u64,
+    seconds_per_slot: u64,
+}
+
+/// A blob sidecar, as returned by the beacon API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlobSidecar {
+    /// The index of the blob in its block.
+    #[serde(deserialize_with = "quoted_u64")]
+    pub index: u64,
+    /// The blob.
+    pub blob: Bytes,
+    /// The KZG commitment of the blob.
+    pub kzg_commitment: FixedBytes<48>,
+    /// The KZG proof of the blob against its commitment.
+    pub kzg_proof: FixedBytes<48>,
+}
+
+impl BeaconClient {
+    /// Creates a new [BeaconClient] for the given beacon node.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into().trim_end_matches('/').to_string(),
+            agent: ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build(),
+            timing: OnceLock::new(),
+        }
+    }
+
+    /// Returns the URL of the beacon node.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the slot of the block with the given timestamp.
+    pub fn slot(&self, timestamp: u64) -> Result<u64> {
+        let timing = self.timing()?;
+        let elapsed = timestamp
+            .checked_sub(timing.genesis_time)
+            .ok_or_else(|| eyre::eyre!("timestamp {} is before the beacon genesis", timestamp))?;
+        Ok(elapsed / timing.seconds_per_slot)
+    }
+
+    /// Returns the blob sidecars of the block with the given timestamp.
+    pub fn blob_sidecars(&self, timestamp: u64) -> Result<Vec<BlobSidecar>> {
+        let slot = self.slot(timestamp)?;
+        self.get(&format!("/eth/v1/beacon/blob_sidecars/{slot}"))
+    }
+
+    /// Returns the slot timing, fetching it on first use.
+    fn timing(&self) -> Result<SlotTiming> {
+        if let Some(timing) = self.timing.get() {
+            return Ok(*timing);
+        }
+        #[derive(Deserialize)]
+        struct Genesis {
+            #[serde(deserialize_with = "quoted_u64")]
+            genesis_time: u64,
+        }
+        #[derive(Deserialize)]
+        struct Spec {
+            #[serde(rename = "SECONDS_PER_SLOT", deserialize_with = "quoted_u64")]
+            seconds_per_slot: u64,
+        }
+        let genesis: Genesis = self.get("/eth/v1/beacon/genesis")?;
+        let spec: Spec = self.get("/eth/v1/config/spec")?;
+        if spec.seconds_per_slot == 0 {
+            eyre::bail!("beacon node reports a zero slot duration");
+        }
+        Ok(*self.timing.get_or_init(|| SlotTiming {
+            genesis_time: genesis.genesis_time,
+            seconds_per_slot: spec.seconds_per_slot,
+        }))
+    }
+
+    /// Fetches the given path and deserializes the `data` of the response.
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        #[derive(Deserialize)]
+        struct Response<T> {
+            data: T,
+        }
+        tracing::debug!(target: "palmtop::beacon", "Fetching {}", path);
+        let response: Response<T> = self
+            .agent
+            .get(&format!("{}{}", self.url, path))
+            .call()
+            .map_err(|e| eyre::eyre!("beacon request {} failed: {}", path, e))?
+            .into_json()
+            .map_err(|e| eyre::eyre!("invalid beacon response to {}: {}", path, e))?;
+        Ok(response.data)
+    }
+}
+
+/// Deserializes a u64 the beacon API encodes as a decimal string.
+fn quoted_u64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::test_utils::MockServer;
+    use serde_json::json;
+
+    /// The genesis time of the mock beacon chain.
+    pub(crate) const GENESIS_TIME: u64 = 1_606_824_023;
+
+    /// Returns a mock beacon node serving the given sidecars at the given slot.
+    pub(crate) fn beacon_server(slot: u64, sidecars: serde_json::Value) -> MockServer {
+        MockServer::http(move |method, path, _| {
+            assert_eq!(method, "GET");
+            let data = match path {
+                "/eth/v1/beacon/genesis" => json!({ "genesis_time": GENESIS_TIME.to_string() }),
+                "/eth/v1/config/spec" => json!({ "SECONDS_PER_SLOT": "12" }),
+                path if path == format!("/eth/v1/beacon/blob_sidecars/{slot}") => sidecars.clone(),
+                _ => {
+                    return (
+                        404,
+                        json!({ "code": 404, "message": "not found" }).to_string(),
+                    )
+                }
+            };
+            (200, json!({ "data": data }).to_string())
+        })
+    }
+
+    #[test]
+    fn test_beacon_slot() {
+        let server = beacon_server(0, json!([]));
+        let client = BeaconClient::new(format!("{}/", server.url()));
+        assert_eq!(client.slot(GENESIS_TIME + 12 * 100 + 5).unwrap(), 100);
+        assert!(client.slot(GENESIS_TIME - 1).is_err());
+    }
+
+    #[test]
+    fn test_beacon_blob_sidecars() {
+        let sidecars = json!([{
+            "index": "1",
+            "blob": "0x0102",
+            "kzg_commitment": format!("0x{}", "aa".repeat(48)),
+            "kzg_proof": format!("0x{}", "bb".repeat(48)),
+            "kzg_commitment_inclusion_proof": [],
+        }]);
+        let server = beacon_server(7, sidecars);
+        let client = BeaconClient::new(server.url());
+        let sidecars = client.blob_sidecars(GENESIS_TIME + 12 * 7).unwrap();
+        assert_eq!(sidecars.len(), 1);
+        assert_eq!(sidecars[0].index, 1);
+        assert_eq!(sidecars[0].blob.as_ref(), &[1, 2]);
+        assert!(client.blob_sidecars(GENESIS_TIME + 12 * 8).is_err());
+    }
+}
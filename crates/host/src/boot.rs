@@ -0,0 +1,106 @@
+use alloy_primitives::B256;
+use eyre::Result;
+
+use palmtop_primitives::{BootKey, Preimage, PreimageGetter, PreimageKey};
+
+/// ## BootInfoSource
+///
+/// BootInfoSource serves the boot info of a run through the local preimage keys
+/// of each [BootKey]. Values are usually taken from the host CLI or config,
+/// and keys without a configured value fail to resolve.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BootInfoSource {
+    /// The L1 head block hash.
+    pub l1_head: Option<B256>,
+    /// The agreed upon L2 output root.
+    pub l2_output_root: Option<B256>,
+    /// The disputed L2 output root claim.
+    pub l2_claim: Option<B256>,
+    /// The L2 block number of the disputed claim.
+    pub l2_claim_block_number: Option<u64>,
+    /// The L2 chain ID.
+    pub l2_chain_id: Option<u64>,
+    /// The JSON-encoded L2 chain config.
+    pub l2_chain_config: Option<Vec<u8>>,
+    /// The JSON-encoded rollup config.
+    pub rollup_config: Option<Vec<u8>>,
+}
+
+impl BootInfoSource {
+    /// Returns the value of the given boot key, if configured.
+    pub fn value(&self, key: BootKey) -> Option<Preimage> {
+        match key {
+            BootKey::L1Head => self.l1_head.map(|h| h.to_vec()),
+            BootKey::L2OutputRoot => self.l2_output_root.map(|h| h.to_vec()),
+            BootKey::L2Claim => self.l2_claim.map(|h| h.to_vec()),
+            BootKey::L2ClaimBlockNumber => {
+                self.l2_claim_block_number.map(|n| n.to_be_bytes().to_vec())
+            }
+            BootKey::L2ChainId => self.l2_chain_id.map(|n| n.to_be_bytes().to_vec()),
+            BootKey::L2ChainConfig => self.l2_chain_config.clone(),
+            BootKey::RollupConfig => self.rollup_config.clone(),
+        }
+    }
+
+    /// Returns the boot keys that have a configured value.
+    pub fn configured(&self) -> Vec<BootKey> {
+        BootKey::ALL
+            .into_iter()
+            .filter(|key| self.value(*key).is_some())
+            .collect()
+    }
+
+    /// Returns the local key and value of every configured boot key.
+    pub fn preimages(&self) -> Vec<(PreimageKey, Preimage)> {
+        BootKey::ALL
+            .into_iter()
+            .filter_map(|key| Some((key.key(), self.value(key)?)))
+            .collect()
+    }
+
+    /// Returns the preimage of the given local key.
+    pub fn get(&self, key: PreimageKey) -> Result<Preimage> {
+        let boot_key = BootKey::from_key(&key)
+            .ok_or_else(|| eyre::eyre!("unknown local key {}", B256::from(key)))?;
+        self.value(boot_key)
+            .ok_or_else(|| eyre::eyre!("boot key {boot_key:?} is not configured"))
+    }
+
+    /// Returns a [PreimageGetter] serving the boot info.
+    pub fn getter(&self) -> PreimageGetter {
+        let source = self.clone();
+        Box::new(move |key| source.get(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use palmtop_primitives::local_key;
+
+    #[test]
+    fn test_boot_info_source() {
+        let source = BootInfoSource {
+            l1_head: Some(B256::repeat_byte(1)),
+            l2_claim_block_number: Some(100),
+            rollup_config: Some(b"{}".to_vec()),
+            ..Default::default()
+        };
+        assert_eq!(
+            source.configured(),
+            vec![
+                BootKey::L1Head,
+                BootKey::L2ClaimBlockNumber,
+                BootKey::RollupConfig
+            ]
+        );
+        let getter = source.getter();
+        assert_eq!(getter(BootKey::L1Head.key()).unwrap(), vec![1u8; 32]);
+        assert_eq!(
+            getter(BootKey::L2ClaimBlockNumber.key()).unwrap(),
+            100u64.to_be_bytes().to_vec()
+        );
+        assert!(getter(BootKey::L2Claim.key()).is_err());
+        assert!(getter(local_key(42)).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use eyre::Result;
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitStatus};
+use std::sync::Arc;
+
+use palmtop_kv::KeyValueStore;
+use palmtop_preimage::fds::{
+    HINT_CLIENT_READ_FD, HINT_CLIENT_WRITE_FD, PREIMAGE_CLIENT_READ_FD, PREIMAGE_CLIENT_WRITE_FD,
+};
+use palmtop_preimage::hints::HintHandler;
+
+use crate::server::serve;
+
+/// The lowest file descriptor the child's channel ends are staged at before they
+/// are moved into place, clear of the oracle channel descriptors.
+const STAGING_FD: RawFd = 10;
+
+/// Spawns the client program with the oracle channels on its inherited file
+/// descriptors and serves both channels until it exits.
+///
+/// Returns the client's exit status. If serving fails, the client is killed,
+/// since it may be blocked on a request that will never be answered.
+pub fn run_client(
+    command: &mut Command,
+    store: Arc<dyn KeyValueStore>,
+    router: Arc<HintHandler>,
+) -> Result<ExitStatus> {
+    let (hint_client_read, hint_host_write) = os_pipe::pipe()?;
+    let (hint_host_read, hint_client_write) = os_pipe::pipe()?;
+    let (preimage_client_read, preimage_host_write) = os_pipe::pipe()?;
+    let (preimage_host_read, preimage_client_write) = os_pipe::pipe()?;
+
+    let mapping = [
+        (hint_client_read.as_raw_fd(), HINT_CLIENT_READ_FD),
+        (hint_client_write.as_raw_fd(), HINT_CLIENT_WRITE_FD),
+        (preimage_client_read.as_raw_fd(), PREIMAGE_CLIENT_READ_FD),
+        (preimage_client_write.as_raw_fd(), PREIMAGE_CLIENT_WRITE_FD),
+    ];
+    // Safety: the closure only makes async-signal-safe calls between fork and exec.
+    unsafe {
+        command.pre_exec(move || {
+            let mut staged = [0; 4];
+            for (staged, (source, _)) in staged.iter_mut().zip(mapping) {
+                *staged = cvt(libc::fcntl(source, libc::F_DUPFD, STAGING_FD))?;
+            }
+            for (staged, (_, target)) in staged.into_iter().zip(mapping) {
+                cvt(libc::dup2(staged, target))?;
+                cvt(libc::close(staged))?;
+            }
+            Ok(())
+        })
+    };
+    let mut child = command.spawn()?;
+    tracing::info!(target: "palmtop::host", "Spawned client process {}", child.id());
+
+    // Close the host's copies of the client's ends, so the channels reach EOF
+    // when the client exits.
+    drop((
+        hint_client_read,
+        hint_client_write,
+        preimage_client_read,
+        preimage_client_write,
+    ));
+
+    let served = serve(
+        (hint_host_read, hint_host_write),
+        (preimage_host_read, preimage_host_write),
+        store,
+        router,
+    );
+    if served.is_err() {
+        _ = child.kill();
+    }
+    let status = child.wait()?;
+    served?;
+    tracing::info!(target: "palmtop::host", "Client exited with {}", status);
+    Ok(status)
+}
+
+/// Converts the return value of a libc call into an [std::io::Result].
+fn cvt(ret: libc::c_int) -> std::io::Result<libc::c_int> {
+    match ret {
+        -1 => Err(std::io::Error::last_os_error()),
+        ret => Ok(ret),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ignore_hint;
+    use palmtop_kv::MemoryKeyValueStore;
+    use palmtop_primitives::PreimageKeyType;
+
+    fn sh(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    #[test]
+    fn test_run_client_serves_channels() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let key = PreimageKeyType::Keccak256.key([0x61; 32]);
+        let store = Arc::new(MemoryKeyValueStore::from_iter([(key, b"palmtop".to_vec())]));
+
+        // Sends a hint and waits for its ack, then requests the preimage of a key
+        // of the form 0x02 followed by 31 'a' bytes.
+        let script = format!(
+            r#"printf '\000\000\000\005hello' >&4 && head -c 1 <&3 >/dev/null &&
+            printf '\002' >&6 && printf 'aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa' >&6 &&
+            head -c 15 <&5 > {} && exit 7"#,
+            out.display()
+        );
+        let hints = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = hints.clone();
+        let router: Arc<HintHandler> = Arc::new(move |hint| {
+            seen.lock().unwrap().push(hint);
+            Ok(())
+        });
+        let status = run_client(&mut sh(&script), store, router).unwrap();
+
+        assert_eq!(status.code(), Some(7));
+        assert_eq!(*hints.lock().unwrap(), ["hello"]);
+        let response = std::fs::read(out).unwrap();
+        assert_eq!(response, [&7u64.to_be_bytes()[..], b"palmtop"].concat());
+    }
+
+    #[test]
+    fn test_run_client_missing_preimage_kills_client() {
+        let store = Arc::new(MemoryKeyValueStore::new());
+        // Requests a missing preimage, then blocks waiting for the response.
+        let script = "printf '\\002' >&6 && printf '%031d' 0 >&6 && head -c 8 <&5 && exit 0";
+        let err = run_client(&mut sh(script), store, Arc::new(ignore_hint)).unwrap_err();
+        assert!(format!("{err:#}").contains("preimage not found"));
+    }
+}
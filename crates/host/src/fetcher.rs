@@ -0,0 +1,1118 @@
+use alloy_consensus::{Header, ReceiptEnvelope, TxEnvelope};
+use alloy_eips::eip2718::Encodable2718;
+use alloy_primitives::{keccak256, Address, Bytes, B256};
+use alloy_rlp::Decodable;
+use eyre::Result;
+use revm_precompile::PrecompileErrors;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::Arc;
+
+use palmtop_kv::KeyValueStore;
+use palmtop_preimage::hints::HintHandler;
+use palmtop_primitives::blob::{self, BYTES_PER_FIELD_ELEMENT, FIELD_ELEMENTS_PER_BLOB};
+use palmtop_primitives::precompile::precompile_key_preimage;
+use palmtop_primitives::{
+    precompile_key, HintType, PrecompileResult, PreimageKey, PreimageKeyType, TypedHint,
+};
+
+use crate::beacon::BeaconClient;
+use crate::rpc::RpcClient;
+use crate::trie::ordered_trie_nodes;
+
+/// ## Fetcher
+///
+/// The Fetcher prepares the preimages requested by hints. It fetches the hinted
+/// data from the configured RPC endpoints, checks it against the hash the client
+/// will request it by, and stores it in the key-value store under that key.
+///
+/// Hints whose preimages are already in the store are acknowledged without
+/// fetching anything, so a datadir reused across runs saves the RPC calls.
+#[derive(Debug)]
+pub struct Fetcher {
+    store: Arc<dyn KeyValueStore>,
+    l1: Option<RpcClient>,
+    l1_beacon: Option<BeaconClient>,
+    l2: Option<RpcClient>,
+    l2_head: Option<B256>,
+}
+
+/// The chain an RPC endpoint serves.
+#[derive(Debug, Clone, Copy)]
+enum Chain {
+    L1,
+    L2,
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::L1 => write!(f, "L1"),
+            Self::L2 => write!(f, "L2"),
+        }
+    }
+}
+
+/// The version byte of EIP-4844 versioned hashes of KZG commitments.
+const VERSIONED_HASH_VERSION_KZG: u8 = 1;
+
+/// The database key prefix of contract code in geth-based L2 nodes.
+const CODE_PREFIX: u8 = b'c';
+
+impl Fetcher {
+    /// Creates a new [Fetcher] storing preimages in the given store.
+    pub fn new(store: Arc<dyn KeyValueStore>) -> Self {
+        Self {
+            store,
+            l1: None,
+            l1_beacon: None,
+            l2: None,
+            l2_head: None,
+        }
+    }
+
+    /// Sets the L1 execution RPC endpoint.
+    pub fn with_l1_rpc(mut self, url: impl Into<String>) -> Self {
+        self.l1 = Some(RpcClient::new(url));
+        self
+    }
+
+    /// Sets the L1 beacon node endpoint blobs are fetched from.
+    pub fn with_l1_beacon(mut self, url: impl Into<String>) -> Self {
+        self.l1_beacon = Some(BeaconClient::new(url));
+        self
+    }
+
+    /// Sets the L2 execution RPC endpoint.
+    pub fn with_l2_rpc(mut self, url: impl Into<String>) -> Self {
+        self.l2 = Some(RpcClient::new(url));
+        self
+    }
+
+    /// Sets the hash of the agreed upon L2 head block, whose output is fetched
+    /// on `l2-output` hints.
+    pub fn with_l2_head(mut self, hash: B256) -> Self {
+        self.l2_head = Some(hash);
+        self
+    }
+
+    /// Returns a [HintHandler] routing hints to the fetcher.
+    pub fn into_handler(self) -> Arc<HintHandler> {
+        Arc::new(move |hint| self.handle_hint(&hint))
+    }
+
+    /// Prepares the preimages requested by the given hint.
+    pub fn handle_hint(&self, hint: &str) -> Result<()> {
+        tracing::debug!(target: "palmtop::fetcher", "Handling hint: {}", hint);
+        let hint = TypedHint::parse(hint)?;
+        match hint.hint_type {
+            HintType::L1BlockHeader => self.fetch_block_header(Chain::L1, hash_data(&hint)?),
+            HintType::L1Transactions => self.fetch_l1_transactions(hash_data(&hint)?),
+            HintType::L1Receipts => self.fetch_l1_receipts(hash_data(&hint)?),
+            HintType::L1Blob => self.fetch_l1_blob(&hint),
+            HintType::L1Precompile => self.execute_l1_precompile(&hint),
+            HintType::L2StateNode => self.fetch_l2_state_node(hash_data(&hint)?),
+            HintType::L2Code => self.fetch_l2_code(hash_data(&hint)?),
+            HintType::L2BlockHeader => self.fetch_block_header(Chain::L2, hash_data(&hint)?),
+            HintType::L2Transactions => self.fetch_l2_transactions(hash_data(&hint)?),
+            HintType::L2Output => self.fetch_l2_output(hash_data(&hint)?),
+        }
+    }
+
+    /// Fetches the block header with the given hash and stores its RLP encoding.
+    fn fetch_block_header(&self, chain: Chain, hash: B256) -> Result<()> {
+        if !self.has_keccak(hash)? {
+            self.header(chain, hash)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the block header with the given hash, fetching it and storing its
+    /// RLP encoding if it is not in the store yet.
+    fn header(&self, chain: Chain, hash: B256) -> Result<Header> {
+        if let Some(encoded) = self.store.get(keccak_key(hash))? {
+            return Ok(Header::decode(&mut encoded.as_slice())?);
+        }
+        let block: Option<Value> = self
+            .rpc(chain)?
+            .call("eth_getBlockByHash", json!([hash, false]))?;
+        let block = block.ok_or_else(|| eyre::eyre!("{} block {} not found", chain, hash))?;
+        let header = parse_header(block)?;
+        let encoded = alloy_rlp::encode(&header);
+        if keccak256(&encoded) != hash {
+            eyre::bail!(
+                "{} block header fetched for {} hashes to {}",
+                chain,
+                hash,
+                keccak256(&encoded)
+            );
+        }
+        self.store.put(keccak_key(hash), encoded)?;
+        Ok(header)
+    }
+
+    /// Fetches the transactions of the L1 block with the given hash and stores the
+    /// nodes of its transactions trie.
+    fn fetch_l1_transactions(&self, hash: B256) -> Result<()> {
+        let header = self.header(Chain::L1, hash)?;
+        if self.has_keccak(header.transactions_root)? {
+            return Ok(());
+        }
+        let block: Option<Value> = self
+            .rpc(Chain::L1)?
+            .call("eth_getBlockByHash", json!([hash, true]))?;
+        let mut block = block.ok_or_else(|| eyre::eyre!("L1 block {} not found", hash))?;
+        let transactions: Vec<alloy_rpc_types_eth::Transaction> =
+            serde_json::from_value(block["transactions"].take())?;
+        let encoded = transactions
+            .into_iter()
+            .map(|tx| Ok(TxEnvelope::try_from(tx)?.encoded_2718()))
+            .collect::<Result<Vec<_>>>()?;
+        self.store_trie(
+            Chain::L1,
+            "transactions",
+            hash,
+            header.transactions_root,
+            &encoded,
+        )
+    }
+
+    /// Fetches the receipts of the L1 block with the given hash and stores the
+    /// nodes of its receipts trie.
+    fn fetch_l1_receipts(&self, hash: B256) -> Result<()> {
+        let header = self.header(Chain::L1, hash)?;
+        if self.has_keccak(header.receipts_root)? {
+            return Ok(());
+        }
+        let receipts: Option<Vec<ReceiptEnvelope>> = self
+            .rpc(Chain::L1)?
+            .call("eth_getBlockReceipts", json!([hash]))?;
+        let receipts = receipts.ok_or_else(|| eyre::eyre!("L1 block {} not found", hash))?;
+        let encoded = receipts
+            .iter()
+            .map(|receipt| receipt.encoded_2718())
+            .collect::<Vec<_>>();
+        self.store_trie(Chain::L1, "receipts", hash, header.receipts_root, &encoded)
+    }
+
+    /// Fetches a blob from the L1 beacon node and stores its commitment under its
+    /// versioned hash and each of its field elements under their blob keys.
+    ///
+    /// The hint data is the blob's versioned hash, followed by its index in the
+    /// block and the block's timestamp as big-endian u64s.
+    fn fetch_l1_blob(&self, hint: &TypedHint) -> Result<()> {
+        if hint.data.len() != 48 {
+            eyre::bail!("{} hint data must be 48 bytes", hint.hint_type);
+        }
+        let versioned_hash = B256::from_slice(&hint.data[..32]);
+        let index = u64::from_be_bytes(hint.data[32..40].try_into()?);
+        let timestamp = u64::from_be_bytes(hint.data[40..].try_into()?);
+        let commitment_key = PreimageKeyType::Sha256.key(versioned_hash.0);
+        if self.store.get(commitment_key)?.is_some() {
+            return Ok(());
+        }
+
+        let beacon = self
+            .l1_beacon
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("no L1 beacon endpoint configured"))?;
+        let sidecar = beacon
+            .blob_sidecars(timestamp)?
+            .into_iter()
+            .find(|sidecar| sidecar.index == index)
+            .ok_or_else(|| eyre::eyre!("blob {} of L1 block at {} not found", index, timestamp))?;
+        let commitment = sidecar.kzg_commitment.0;
+        if kzg_to_versioned_hash(&commitment) != versioned_hash {
+            eyre::bail!(
+                "blob {} of L1 block at {} is not {}",
+                index,
+                timestamp,
+                versioned_hash
+            );
+        }
+        let valid = c_kzg::KzgProof::verify_blob_kzg_proof(
+            &c_kzg::Blob::from_bytes(&sidecar.blob)?,
+            &c_kzg::Bytes48::from(commitment),
+            &c_kzg::Bytes48::from(sidecar.kzg_proof.0),
+            c_kzg::ethereum_kzg_settings(),
+        )?;
+        if !valid {
+            eyre::bail!("KZG proof of blob {} is invalid", versioned_hash);
+        }
+
+        for (i, element) in sidecar.blob.chunks(BYTES_PER_FIELD_ELEMENT).enumerate() {
+            let preimage = blob::blob_key_preimage(&commitment, i).to_vec();
+            let key = blob::blob_key(&commitment, i);
+            self.store.put(keccak_key(keccak256(&preimage)), preimage)?;
+            self.store.put(key, element.to_vec())?;
+        }
+        tracing::debug!(
+            target: "palmtop::fetcher",
+            "Stored {} field elements of blob {}",
+            FIELD_ELEMENTS_PER_BLOB,
+            versioned_hash
+        );
+        self.store.put(commitment_key, commitment.to_vec())
+    }
+
+    /// Executes an L1 precompile call natively and stores its result under the
+    /// call's precompile key.
+    ///
+    /// The hint data is the precompile's address followed by the call input.
+    fn execute_l1_precompile(&self, hint: &TypedHint) -> Result<()> {
+        if hint.data.len() < 20 {
+            eyre::bail!(
+                "{} hint data must start with a 20 byte address",
+                hint.hint_type
+            );
+        }
+        let (address, input) = hint.data.split_at(20);
+        let address = Address::from_slice(address);
+        let key = precompile_key(address, input);
+        if self.store.get(key)?.is_some() {
+            return Ok(());
+        }
+        let precompile = revm_precompile::Precompiles::cancun()
+            .get(&address)
+            .ok_or_else(|| eyre::eyre!("no L1 precompile at {}", address))?;
+        let env = revm_precompile::primitives::Env::default();
+        let result = match precompile.call_ref(&Bytes::copy_from_slice(input), u64::MAX, &env) {
+            Ok(output) => PrecompileResult::Success(output.bytes.to_vec()),
+            Err(PrecompileErrors::Error(e)) => {
+                tracing::debug!(target: "palmtop::fetcher", "L1 precompile {} failed: {}", address, e);
+                PrecompileResult::Failure
+            }
+            Err(PrecompileErrors::Fatal { msg }) => {
+                eyre::bail!("L1 precompile {} failed fatally: {}", address, msg)
+            }
+        };
+        let preimage = precompile_key_preimage(address, input);
+        self.store.put(keccak_key(keccak256(&preimage)), preimage)?;
+        self.store.put(key, result.encode())
+    }
+
+    /// Fetches the L2 state trie node with the given hash from the node's database.
+    fn fetch_l2_state_node(&self, hash: B256) -> Result<()> {
+        if self.has_keccak(hash)? {
+            return Ok(());
+        }
+        let node = self.db_get(hash.as_slice())?;
+        self.store_keccak("state node", hash, node)
+    }
+
+    /// Fetches the L2 contract code with the given hash from the node's database.
+    ///
+    /// Code is looked up under its prefixed database key first and under the bare
+    /// hash second, which is where older nodes store it.
+    fn fetch_l2_code(&self, hash: B256) -> Result<()> {
+        if self.has_keccak(hash)? {
+            return Ok(());
+        }
+        let prefixed = [&[CODE_PREFIX], hash.as_slice()].concat();
+        let code = match self.db_get(&prefixed) {
+            Ok(code) => code,
+            Err(e) => {
+                tracing::debug!(target: "palmtop::fetcher", "Prefixed code lookup failed: {}", e);
+                self.db_get(hash.as_slice())?
+            }
+        };
+        self.store_keccak("code", hash, code)
+    }
+
+    /// Fetches the transactions of the L2 block with the given hash and stores the
+    /// nodes of its transactions trie.
+    ///
+    /// Transactions are fetched in their raw encoding so deposit transactions,
+    /// which are specific to the rollup, are stored as the node serves them.
+    fn fetch_l2_transactions(&self, hash: B256) -> Result<()> {
+        let header = self.header(Chain::L2, hash)?;
+        if self.has_keccak(header.transactions_root)? {
+            return Ok(());
+        }
+        let l2 = self.rpc(Chain::L2)?;
+        let block: Option<Value> = l2.call("eth_getBlockByHash", json!([hash, false]))?;
+        let block = block.ok_or_else(|| eyre::eyre!("L2 block {} not found", hash))?;
+        let count = block["transactions"]
+            .as_array()
+            .map(Vec::len)
+            .ok_or_else(|| eyre::eyre!("L2 block {} has no transactions list", hash))?;
+        let encoded = (0..count)
+            .map(|index| {
+                let tx: Option<Bytes> = l2.call(
+                    "eth_getRawTransactionByBlockHashAndIndex",
+                    json!([hash, format!("{index:#x}")]),
+                )?;
+                let tx = tx.ok_or_else(|| {
+                    eyre::eyre!("transaction {} of L2 block {} not found", index, hash)
+                })?;
+                Ok(tx.to_vec())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.store_trie(
+            Chain::L2,
+            "transactions",
+            hash,
+            header.transactions_root,
+            &encoded,
+        )
+    }
+
+    /// Fetches the output of the agreed upon L2 head block and stores its preimage,
+    /// checking that it commits to the hinted output root.
+    fn fetch_l2_output(&self, root: B256) -> Result<()> {
+        if self.has_keccak(root)? {
+            return Ok(());
+        }
+        let l2_head = self
+            .l2_head
+            .ok_or_else(|| eyre::eyre!("no L2 head configured to fetch outputs at"))?;
+        let header = self.header(Chain::L2, l2_head)?;
+        let output: OutputResponse = self.rpc(Chain::L2)?.call(
+            "optimism_outputAtBlock",
+            json!([format!("{:#x}", header.number)]),
+        )?;
+        if output.block_ref.hash != l2_head {
+            eyre::bail!(
+                "L2 output at block {} is for block {}, expected {}",
+                header.number,
+                output.block_ref.hash,
+                l2_head
+            );
+        }
+        let preimage = [
+            output.version.as_slice(),
+            output.state_root.as_slice(),
+            output.withdrawal_storage_root.as_slice(),
+            output.block_ref.hash.as_slice(),
+        ]
+        .concat();
+        if keccak256(&preimage) != output.output_root {
+            eyre::bail!("L2 output of block {} does not hash to its root", l2_head);
+        }
+        self.store_keccak("output", root, preimage)
+    }
+
+    /// Reads the given key from the L2 node's database.
+    fn db_get(&self, key: &[u8]) -> Result<Vec<u8>> {
+        let value: Option<Bytes> = self
+            .rpc(Chain::L2)?
+            .call("debug_dbGet", json!([Bytes::copy_from_slice(key)]))?;
+        let value = value.ok_or_else(|| {
+            eyre::eyre!(
+                "L2 database key 0x{} not found",
+                alloy_primitives::hex::encode(key)
+            )
+        })?;
+        Ok(value.to_vec())
+    }
+
+    /// Stores the given L2 preimage under its keccak256 key,
+    /// after checking that it hashes to the expected hash.
+    fn store_keccak(&self, name: &str, hash: B256, preimage: Vec<u8>) -> Result<()> {
+        if keccak256(&preimage) != hash {
+            eyre::bail!(
+                "L2 {} fetched for {} hashes to {}",
+                name,
+                hash,
+                keccak256(&preimage)
+            );
+        }
+        self.store.put(keccak_key(hash), preimage)
+    }
+
+    /// Builds the ordered trie of the given values and stores each of its nodes,
+    /// after checking its root against the one committed to by the block header.
+    fn store_trie(
+        &self,
+        chain: Chain,
+        name: &str,
+        block: B256,
+        expected: B256,
+        values: &[Vec<u8>],
+    ) -> Result<()> {
+        let (root, nodes) = ordered_trie_nodes(values);
+        if root != expected {
+            eyre::bail!(
+                "{} {} trie of block {} has root {}, expected {}",
+                chain,
+                name,
+                block,
+                root,
+                expected
+            );
+        }
+        tracing::debug!(
+            target: "palmtop::fetcher",
+            "Storing {} {} trie nodes of {} block {}",
+            nodes.len(),
+            name,
+            chain,
+            block
+        );
+        for node in nodes {
+            self.store.put(keccak_key(keccak256(&node)), node)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the RPC client of the given chain, failing if none is configured.
+    fn rpc(&self, chain: Chain) -> Result<&RpcClient> {
+        let rpc = match chain {
+            Chain::L1 => self.l1.as_ref(),
+            Chain::L2 => self.l2.as_ref(),
+        };
+        rpc.ok_or_else(|| eyre::eyre!("no {} RPC endpoint configured", chain))
+    }
+
+    /// Returns true if the preimage of the given keccak256 hash is already stored.
+    fn has_keccak(&self, hash: B256) -> Result<bool> {
+        Ok(self.store.get(keccak_key(hash))?.is_some())
+    }
+}
+
+/// Returns the keccak256 [PreimageKey] of the given hash.
+pub fn keccak_key(hash: B256) -> PreimageKey {
+    PreimageKeyType::Keccak256.key(hash.0)
+}
+
+/// Returns the EIP-4844 versioned hash of the given KZG commitment.
+pub fn kzg_to_versioned_hash(commitment: &[u8]) -> B256 {
+    let mut hash: [u8; 32] = Sha256::digest(commitment).into();
+    hash[0] = VERSIONED_HASH_VERSION_KZG;
+    B256::from(hash)
+}
+
+/// The output of an L2 block, as returned by `optimism_outputAtBlock`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OutputResponse {
+    version: B256,
+    output_root: B256,
+    block_ref: BlockRef,
+    withdrawal_storage_root: B256,
+    state_root: B256,
+}
+
+/// The block an L2 output was computed at.
+#[derive(Debug, Deserialize)]
+struct BlockRef {
+    hash: B256,
+}
+
+/// Parses the data of a hint as a 32 byte hash.
+fn hash_data(hint: &TypedHint) -> Result<B256> {
+    if hint.data.len() != 32 {
+        eyre::bail!("{} hint data must be a 32 byte hash", hint.hint_type);
+    }
+    Ok(B256::from_slice(&hint.data))
+}
+
+/// Parses the consensus header of a JSON-RPC block.
+fn parse_header(mut block: Value) -> Result<Header> {
+    // Prague nodes return the EIP-7685 requests hash under its final name.
+    if let Some(requests_hash) = block.get("requestsHash").cloned() {
+        block["requestsRoot"] = requests_hash;
+    }
+    let header: alloy_rpc_types_eth::Header = serde_json::from_value(block)?;
+    Ok(header.try_into()?)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::test_utils::MockServer;
+    use alloy_consensus::{Receipt, ReceiptWithBloom, SignableTransaction, TxEip1559, TxLegacy};
+    use alloy_primitives::{Address, TxKind, U256};
+    use palmtop_kv::MemoryKeyValueStore;
+    use palmtop_primitives::Hint;
+
+    /// Returns the JSON-RPC representation of the given header.
+    pub(crate) fn rpc_header(header: &Header) -> Value {
+        let mut block = serde_json::to_value(header).unwrap();
+        let fields = block.as_object_mut().unwrap();
+        let ommers_hash = fields.remove("ommersHash").unwrap();
+        let beneficiary = fields.remove("beneficiary").unwrap();
+        fields.insert("sha3Uncles".into(), ommers_hash);
+        fields.insert("miner".into(), beneficiary);
+        fields.insert("hash".into(), json!(header.hash_slow()));
+        fields.insert("transactions".into(), json!([]));
+        block
+    }
+
+    fn header() -> Header {
+        Header {
+            number: 19_000_000,
+            gas_limit: 30_000_000,
+            timestamp: 1_705_000_000,
+            base_fee_per_gas: Some(7),
+            withdrawals_root: Some(B256::repeat_byte(4)),
+            blob_gas_used: Some(0),
+            excess_blob_gas: Some(0),
+            parent_beacon_block_root: Some(B256::repeat_byte(5)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fetch_l1_block_header() {
+        let header = header();
+        let hash = header.hash_slow();
+        let block = rpc_header(&header);
+        let server = MockServer::json_rpc(move |method, params| {
+            assert_eq!(method, "eth_getBlockByHash");
+            assert_eq!(params[1], json!(false));
+            Ok(block.clone())
+        });
+        let store = Arc::new(MemoryKeyValueStore::new());
+        let handler = Fetcher::new(store.clone())
+            .with_l1_rpc(server.url())
+            .into_handler();
+
+        handler(TypedHint::new(HintType::L1BlockHeader, hash.to_vec()).hint()).unwrap();
+        let stored = store.get(keccak_key(hash)).unwrap().unwrap();
+        assert_eq!(stored, alloy_rlp::encode(&header));
+    }
+
+    #[test]
+    fn test_fetch_l1_block_header_hash_mismatch() {
+        let mut block = rpc_header(&header());
+        block["number"] = json!("0x1");
+        let server = MockServer::json_rpc(move |_, _| Ok(block.clone()));
+        let store = Arc::new(MemoryKeyValueStore::new());
+        let fetcher = Fetcher::new(store.clone()).with_l1_rpc(server.url());
+        let hint = TypedHint::new(HintType::L1BlockHeader, header().hash_slow().to_vec());
+        assert!(fetcher.handle_hint(&hint.hint()).is_err());
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_fetch_l1_block_header_not_found() {
+        let server = MockServer::json_rpc(|_, _| Ok(Value::Null));
+        let fetcher = Fetcher::new(Arc::new(MemoryKeyValueStore::new())).with_l1_rpc(server.url());
+        let hint = TypedHint::new(HintType::L1BlockHeader, [1; 32].to_vec());
+        assert!(fetcher.handle_hint(&hint.hint()).is_err());
+    }
+
+    #[test]
+    fn test_fetch_skips_stored_preimages() {
+        let hash = header().hash_slow();
+        let store = Arc::new(MemoryKeyValueStore::new());
+        store.put(keccak_key(hash), vec![1]).unwrap();
+        // No L1 RPC is configured, so fetching would fail.
+        let fetcher = Fetcher::new(store);
+        let hint = TypedHint::new(HintType::L1BlockHeader, hash.to_vec());
+        fetcher.handle_hint(&hint.hint()).unwrap();
+    }
+
+    /// Returns a few signed transactions with their JSON-RPC representations.
+    // The consensus types still sign with the deprecated signature type.
+    #[allow(deprecated)]
+    fn transactions() -> (Vec<TxEnvelope>, Vec<Value>) {
+        let signature = alloy_primitives::Signature::from_rs_and_parity(
+            U256::from(0x840cfc57u64),
+            U256::from(0x25e7109cu64),
+            false,
+        )
+        .unwrap();
+        let legacy = TxLegacy {
+            nonce: 1,
+            gas_price: 20_000_000_000,
+            gas_limit: 21_000,
+            to: TxKind::Call(Address::repeat_byte(1)),
+            value: U256::from(1),
+            ..Default::default()
+        };
+        let dynamic_fee = TxEip1559 {
+            chain_id: 1,
+            nonce: 2,
+            gas_limit: 50_000,
+            max_fee_per_gas: 30_000_000_000,
+            max_priority_fee_per_gas: 1_000_000_000,
+            to: TxKind::Call(Address::repeat_byte(2)),
+            input: vec![0xab; 100].into(),
+            ..Default::default()
+        };
+        let envelopes = vec![
+            TxEnvelope::Legacy(
+                legacy
+                    .into_signed(signature.with_parity(alloy_primitives::Parity::NonEip155(false))),
+            ),
+            TxEnvelope::Eip1559(dynamic_fee.into_signed(signature)),
+        ];
+        let common = |hash: B256, index: u64| {
+            json!({
+                "hash": hash,
+                "from": Address::repeat_byte(9),
+                "transactionIndex": format!("{index:#x}"),
+                "r": signature.r(),
+                "s": signature.s(),
+            })
+        };
+        let mut legacy = common(*envelopes[0].tx_hash(), 0);
+        legacy.as_object_mut().unwrap().extend(
+            json!({
+                "type": "0x0",
+                "nonce": "0x1",
+                "gasPrice": "0x4a817c800",
+                "gas": "0x5208",
+                "to": Address::repeat_byte(1),
+                "value": "0x1",
+                "input": "0x",
+                "v": "0x1b",
+            })
+            .as_object()
+            .unwrap()
+            .clone(),
+        );
+        let mut dynamic_fee = common(*envelopes[1].tx_hash(), 1);
+        dynamic_fee.as_object_mut().unwrap().extend(
+            json!({
+                "type": "0x2",
+                "chainId": "0x1",
+                "nonce": "0x2",
+                "gas": "0xc350",
+                "maxFeePerGas": "0x6fc23ac00",
+                "maxPriorityFeePerGas": "0x3b9aca00",
+                "to": Address::repeat_byte(2),
+                "value": "0x0",
+                "input": format!("0x{}", "ab".repeat(100)),
+                "accessList": [],
+                "v": "0x0",
+                "yParity": "0x0",
+            })
+            .as_object()
+            .unwrap()
+            .clone(),
+        );
+        (envelopes, vec![legacy, dynamic_fee])
+    }
+
+    /// Returns a few receipts.
+    fn receipts() -> Vec<ReceiptEnvelope> {
+        let log = alloy_primitives::Log::new_unchecked(
+            Address::repeat_byte(3),
+            vec![B256::repeat_byte(4)],
+            vec![5; 64].into(),
+        );
+        let receipt = |cumulative_gas_used, logs: Vec<alloy_primitives::Log>| {
+            let receipt = Receipt {
+                status: true.into(),
+                cumulative_gas_used,
+                logs,
+            };
+            ReceiptWithBloom {
+                logs_bloom: receipt.bloom_slow(),
+                receipt,
+            }
+        };
+        vec![
+            ReceiptEnvelope::Legacy(receipt(21_000, vec![])),
+            ReceiptEnvelope::Eip1559(receipt(71_000, vec![log.clone(), log])),
+        ]
+    }
+
+    /// Returns the header of a block committing to the test transactions and receipts.
+    fn block_header() -> Header {
+        let (transactions, _) = transactions();
+        let transactions = transactions
+            .iter()
+            .map(|tx| tx.encoded_2718())
+            .collect::<Vec<_>>();
+        let receipts = receipts()
+            .iter()
+            .map(|receipt| receipt.encoded_2718())
+            .collect::<Vec<_>>();
+        Header {
+            transactions_root: ordered_trie_nodes(&transactions).0,
+            receipts_root: ordered_trie_nodes(&receipts).0,
+            ..header()
+        }
+    }
+
+    /// Returns a mock L1 RPC serving the test block, its transactions and receipts.
+    fn l1_server() -> MockServer {
+        let header = block_header();
+        MockServer::json_rpc(move |method, params| match method {
+            "eth_getBlockByHash" => {
+                let mut block = rpc_header(&header);
+                if params[1] == json!(true) {
+                    block["transactions"] = json!(transactions().1);
+                }
+                Ok(block)
+            }
+            "eth_getBlockReceipts" => {
+                let receipts = receipts()
+                    .iter()
+                    .enumerate()
+                    .map(|(index, receipt)| {
+                        let mut receipt = serde_json::to_value(receipt).unwrap();
+                        receipt["transactionHash"] = json!(B256::repeat_byte(index as u8));
+                        receipt["transactionIndex"] = json!(format!("{index:#x}"));
+                        receipt["gasUsed"] = json!("0x5208");
+                        receipt["effectiveGasPrice"] = json!("0x1");
+                        receipt["from"] = json!(Address::repeat_byte(9));
+                        receipt["to"] = Value::Null;
+                        receipt
+                    })
+                    .collect::<Vec<_>>();
+                Ok(json!(receipts))
+            }
+            _ => Err(json!({ "code": -32601, "message": "method not found" })),
+        })
+    }
+
+    #[test]
+    fn test_fetch_l1_transactions() {
+        let server = l1_server();
+        let store = Arc::new(MemoryKeyValueStore::new());
+        let fetcher = Fetcher::new(store.clone()).with_l1_rpc(server.url());
+        let header = block_header();
+        let hash = header.hash_slow();
+        let hint = TypedHint::new(HintType::L1Transactions, hash.to_vec());
+        fetcher.handle_hint(&hint.hint()).unwrap();
+
+        let transactions = transactions()
+            .0
+            .iter()
+            .map(|tx| tx.encoded_2718())
+            .collect::<Vec<_>>();
+        for node in ordered_trie_nodes(&transactions).1 {
+            assert_eq!(store.get(keccak_key(keccak256(&node))).unwrap(), Some(node));
+        }
+        assert!(store
+            .get(keccak_key(header.transactions_root))
+            .unwrap()
+            .is_some());
+        assert!(store.get(keccak_key(hash)).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_fetch_l1_receipts() {
+        let server = l1_server();
+        let store = Arc::new(MemoryKeyValueStore::new());
+        let fetcher = Fetcher::new(store.clone()).with_l1_rpc(server.url());
+        let header = block_header();
+        let hint = TypedHint::new(HintType::L1Receipts, header.hash_slow().to_vec());
+        fetcher.handle_hint(&hint.hint()).unwrap();
+        assert!(store
+            .get(keccak_key(header.receipts_root))
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_fetch_l1_receipts_root_mismatch() {
+        let server = l1_server();
+        let header = Header {
+            receipts_root: B256::repeat_byte(7),
+            ..block_header()
+        };
+        let store = Arc::new(MemoryKeyValueStore::new());
+        store
+            .put(keccak_key(header.hash_slow()), alloy_rlp::encode(&header))
+            .unwrap();
+        let fetcher = Fetcher::new(store.clone()).with_l1_rpc(server.url());
+        let hint = TypedHint::new(HintType::L1Receipts, header.hash_slow().to_vec());
+        let err = fetcher.handle_hint(&hint.hint()).unwrap_err();
+        assert!(err.to_string().contains("receipts trie"));
+        assert_eq!(store.len(), 1);
+    }
+
+    /// Returns the raw transactions of the test L2 block, starting with a deposit.
+    fn l2_transactions() -> Vec<Vec<u8>> {
+        vec![[vec![0x7e], vec![0xc0; 40]].concat(), vec![0x02; 120]]
+    }
+
+    /// Returns the header of an L2 block committing to the test transactions.
+    fn l2_header() -> Header {
+        Header {
+            number: 120_000_000,
+            transactions_root: ordered_trie_nodes(&l2_transactions()).0,
+            ..header()
+        }
+    }
+
+    /// Returns the output preimage of the test L2 block.
+    fn l2_output() -> Vec<u8> {
+        [
+            B256::ZERO,
+            B256::repeat_byte(1),
+            B256::repeat_byte(2),
+            l2_header().hash_slow(),
+        ]
+        .concat()
+    }
+
+    /// Returns a mock L2 RPC serving the test block, state and code.
+    fn l2_server() -> MockServer {
+        let header = l2_header();
+        MockServer::json_rpc(move |method, params| match method {
+            "eth_getBlockByHash" => {
+                let mut block = rpc_header(&header);
+                block["transactions"] =
+                    json!(l2_transactions().iter().map(keccak256).collect::<Vec<_>>());
+                Ok(block)
+            }
+            "eth_getRawTransactionByBlockHashAndIndex" => {
+                let index = params[1].as_str().unwrap().trim_start_matches("0x");
+                let index = usize::from_str_radix(index, 16).unwrap();
+                Ok(json!(Bytes::from(l2_transactions()[index].clone())))
+            }
+            "debug_dbGet" => {
+                let key: Bytes = serde_json::from_value(params[0].clone()).unwrap();
+                match key.as_ref() {
+                    // A state node, stored under its hash.
+                    key if key == keccak256([1, 2, 3]).as_slice() => Ok(json!("0x010203")),
+                    // Code, stored under its prefixed hash.
+                    [CODE_PREFIX, hash @ ..] if hash == keccak256([4, 5]).as_slice() => {
+                        Ok(json!("0x0405"))
+                    }
+                    // Legacy code, stored under its bare hash.
+                    key if key == keccak256([6]).as_slice() => Ok(json!("0x06")),
+                    _ => Err(json!({ "code": -32000, "message": "leveldb: not found" })),
+                }
+            }
+            "optimism_outputAtBlock" => {
+                assert_eq!(params[0], json!("0x7270e00"));
+                Ok(json!({
+                    "version": B256::ZERO,
+                    "outputRoot": keccak256(l2_output()),
+                    "blockRef": { "hash": header.hash_slow(), "number": header.number },
+                    "withdrawalStorageRoot": B256::repeat_byte(2),
+                    "stateRoot": B256::repeat_byte(1),
+                }))
+            }
+            _ => Err(json!({ "code": -32601, "message": "method not found" })),
+        })
+    }
+
+    fn l2_fetcher(server: &MockServer) -> (Arc<MemoryKeyValueStore>, Fetcher) {
+        let store = Arc::new(MemoryKeyValueStore::new());
+        let fetcher = Fetcher::new(store.clone())
+            .with_l2_rpc(server.url())
+            .with_l2_head(l2_header().hash_slow());
+        (store, fetcher)
+    }
+
+    #[test]
+    fn test_fetch_l2_state_node_and_code() {
+        let server = l2_server();
+        let (store, fetcher) = l2_fetcher(&server);
+        for (hint_type, preimage) in [
+            (HintType::L2StateNode, vec![1, 2, 3]),
+            (HintType::L2Code, vec![4, 5]),
+            (HintType::L2Code, vec![6]),
+        ] {
+            let hash = keccak256(&preimage);
+            let hint = TypedHint::new(hint_type, hash.to_vec());
+            fetcher.handle_hint(&hint.hint()).unwrap();
+            assert_eq!(store.get(keccak_key(hash)).unwrap(), Some(preimage));
+        }
+
+        let hint = TypedHint::new(HintType::L2StateNode, [9; 32].to_vec());
+        assert!(fetcher.handle_hint(&hint.hint()).is_err());
+    }
+
+    #[test]
+    fn test_fetch_l2_transactions() {
+        let server = l2_server();
+        let (store, fetcher) = l2_fetcher(&server);
+        let header = l2_header();
+        let hint = TypedHint::new(HintType::L2Transactions, header.hash_slow().to_vec());
+        fetcher.handle_hint(&hint.hint()).unwrap();
+        for node in ordered_trie_nodes(&l2_transactions()).1 {
+            assert_eq!(store.get(keccak_key(keccak256(&node))).unwrap(), Some(node));
+        }
+    }
+
+    #[test]
+    fn test_fetch_l2_output() {
+        let server = l2_server();
+        let (store, fetcher) = l2_fetcher(&server);
+        let root = keccak256(l2_output());
+        let hint = TypedHint::new(HintType::L2Output, root.to_vec());
+        fetcher.handle_hint(&hint.hint()).unwrap();
+        assert_eq!(store.get(keccak_key(root)).unwrap(), Some(l2_output()));
+    }
+
+    #[test]
+    fn test_fetch_l2_output_without_head() {
+        let server = l2_server();
+        let fetcher = Fetcher::new(Arc::new(MemoryKeyValueStore::new())).with_l2_rpc(server.url());
+        let hint = TypedHint::new(HintType::L2Output, keccak256(l2_output()).to_vec());
+        let err = fetcher.handle_hint(&hint.hint()).unwrap_err();
+        assert!(err.to_string().contains("no L2 head"));
+    }
+
+    /// Returns a blob with distinct field elements and its commitment and proof.
+    fn blob() -> (Vec<u8>, [u8; 48], [u8; 48]) {
+        let blob = (0..FIELD_ELEMENTS_PER_BLOB)
+            .flat_map(|i| {
+                let mut element = [0; BYTES_PER_FIELD_ELEMENT];
+                element[24..].copy_from_slice(&(i as u64 * 3 + 1).to_be_bytes());
+                element
+            })
+            .collect::<Vec<_>>();
+        let settings = c_kzg::ethereum_kzg_settings();
+        let kzg_blob = c_kzg::Blob::from_bytes(&blob).unwrap();
+        let commitment = c_kzg::KzgCommitment::blob_to_kzg_commitment(&kzg_blob, settings)
+            .unwrap()
+            .to_bytes();
+        let proof = c_kzg::KzgProof::compute_blob_kzg_proof(&kzg_blob, &commitment, settings)
+            .unwrap()
+            .to_bytes();
+        (blob, *commitment, *proof)
+    }
+
+    fn blob_hint(versioned_hash: B256, index: u64, timestamp: u64) -> String {
+        let data = [
+            versioned_hash.as_slice(),
+            &index.to_be_bytes(),
+            &timestamp.to_be_bytes(),
+        ]
+        .concat();
+        TypedHint::new(HintType::L1Blob, data).hint()
+    }
+
+    fn blob_server(blob: &[u8], commitment: &[u8], proof: &[u8]) -> MockServer {
+        let sidecars = json!([{
+            "index": "2",
+            "blob": Bytes::copy_from_slice(blob),
+            "kzg_commitment": Bytes::copy_from_slice(commitment),
+            "kzg_proof": Bytes::copy_from_slice(proof),
+        }]);
+        crate::beacon::tests::beacon_server(1000, sidecars)
+    }
+
+    #[test]
+    fn test_fetch_l1_blob() {
+        let (blob, commitment, proof) = blob();
+        let server = blob_server(&blob, &commitment, &proof);
+        let store = Arc::new(MemoryKeyValueStore::new());
+        let fetcher = Fetcher::new(store.clone()).with_l1_beacon(server.url());
+        let versioned_hash = kzg_to_versioned_hash(&commitment);
+        let timestamp = crate::beacon::tests::GENESIS_TIME + 12 * 1000;
+        fetcher
+            .handle_hint(&blob_hint(versioned_hash, 2, timestamp))
+            .unwrap();
+
+        let commitment_key = PreimageKeyType::Sha256.key(versioned_hash.0);
+        assert_eq!(
+            store.get(commitment_key).unwrap(),
+            Some(commitment.to_vec())
+        );
+        for i in [0, 1, 2048, FIELD_ELEMENTS_PER_BLOB - 1] {
+            let element = store.get(blob::blob_key(&commitment, i)).unwrap().unwrap();
+            assert_eq!(element, &blob[i * 32..(i + 1) * 32]);
+            let preimage = blob::blob_key_preimage(&commitment, i);
+            let key = keccak_key(keccak256(preimage));
+            assert_eq!(store.get(key).unwrap(), Some(preimage.to_vec()));
+        }
+        assert_eq!(store.len(), 2 * FIELD_ELEMENTS_PER_BLOB + 1);
+    }
+
+    #[test]
+    fn test_fetch_l1_blob_rejects_other_commitment() {
+        let (blob, commitment, proof) = blob();
+        let server = blob_server(&blob, &commitment, &proof);
+        let store = Arc::new(MemoryKeyValueStore::new());
+        let fetcher = Fetcher::new(store.clone()).with_l1_beacon(server.url());
+        let timestamp = crate::beacon::tests::GENESIS_TIME + 12 * 1000;
+        let hint = blob_hint(kzg_to_versioned_hash(&[0; 48]), 2, timestamp);
+        assert!(fetcher.handle_hint(&hint).is_err());
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_blob_elements_are_evaluations_at_roots_of_unity() {
+        let (blob, _, _) = blob();
+        let kzg_blob = c_kzg::Blob::from_bytes(&blob).unwrap();
+        for i in [1, 5, 4000] {
+            let root = blob::roots_of_unity()[i].to_be_bytes::<32>();
+            let (_, y) = c_kzg::KzgProof::compute_kzg_proof(
+                &kzg_blob,
+                &c_kzg::Bytes32::from(root),
+                c_kzg::ethereum_kzg_settings(),
+            )
+            .unwrap();
+            assert_eq!(y.as_slice(), &blob[i * 32..(i + 1) * 32]);
+        }
+    }
+
+    fn precompile_hint(address: u8, input: &[u8]) -> String {
+        let data = [Address::with_last_byte(address).as_slice(), input].concat();
+        TypedHint::new(HintType::L1Precompile, data).hint()
+    }
+
+    fn precompile_result(
+        store: &MemoryKeyValueStore,
+        address: u8,
+        input: &[u8],
+    ) -> PrecompileResult {
+        let key = precompile_key(Address::with_last_byte(address), input);
+        PrecompileResult::decode(&store.get(key).unwrap().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_execute_l1_precompile() {
+        let store = Arc::new(MemoryKeyValueStore::new());
+        let fetcher = Fetcher::new(store.clone());
+
+        // SHA-256 succeeds with the digest of its input.
+        fetcher.handle_hint(&precompile_hint(2, b"abc")).unwrap();
+        let digest = Sha256::digest(b"abc").to_vec();
+        assert_eq!(
+            precompile_result(&store, 2, b"abc"),
+            PrecompileResult::Success(digest)
+        );
+        let preimage = precompile_key_preimage(Address::with_last_byte(2), b"abc");
+        assert_eq!(
+            store.get(keccak_key(keccak256(&preimage))).unwrap(),
+            Some(preimage)
+        );
+
+        // The bn256 pairing fails on input that is not a whole number of pairs.
+        fetcher.handle_hint(&precompile_hint(8, &[1; 100])).unwrap();
+        assert_eq!(
+            precompile_result(&store, 8, &[1; 100]),
+            PrecompileResult::Failure
+        );
+
+        assert!(fetcher.handle_hint(&precompile_hint(0x42, &[])).is_err());
+    }
+
+    #[test]
+    fn test_execute_l1_kzg_point_evaluation() {
+        let (blob, commitment, _) = blob();
+        let kzg_blob = c_kzg::Blob::from_bytes(&blob).unwrap();
+        let z = blob::roots_of_unity()[3].to_be_bytes::<32>();
+        let (proof, y) = c_kzg::KzgProof::compute_kzg_proof(
+            &kzg_blob,
+            &c_kzg::Bytes32::from(z),
+            c_kzg::ethereum_kzg_settings(),
+        )
+        .unwrap();
+        let input = [
+            kzg_to_versioned_hash(&commitment).as_slice(),
+            &z,
+            y.as_slice(),
+            &commitment,
+            proof.to_bytes().as_slice(),
+        ]
+        .concat();
+
+        let store = Arc::new(MemoryKeyValueStore::new());
+        let fetcher = Fetcher::new(store.clone());
+        fetcher.handle_hint(&precompile_hint(0x0a, &input)).unwrap();
+        let PrecompileResult::Success(output) = precompile_result(&store, 0x0a, &input) else {
+            panic!("point evaluation should succeed");
+        };
+        assert_eq!(
+            &output[..32],
+            &alloy_primitives::U256::from(FIELD_ELEMENTS_PER_BLOB).to_be_bytes::<32>()
+        );
+        assert_eq!(&output[32..], &blob::BLS_MODULUS.to_be_bytes::<32>());
+    }
+}
@@ -0,0 +1,56 @@
+#![cfg_attr(docsrs, feature(doc_cfg))]
+#![doc(
+    html_logo_url = "https://raw.githubusercontent.com/refcell/palmtop/main/extra/palmtop.png",
+    html_favicon_url = "https://avatars0.githubusercontent.com/u/97369466?s=256",
+    issue_tracker_base_url = "https://github.com/refcell/palmtop/issues/"
+)]
+#![warn(missing_docs, unreachable_pub, unused_crate_dependencies)]
+#![deny(unused_must_use, rust_2018_idioms)]
+#![doc(test(
+    no_crate_inject,
+    attr(deny(warnings, rust_2018_idioms), allow(dead_code, unused_variables))
+))]
+
+//! Palmtop Host
+//!
+//! Host components that prepare and serve preimages to the palmtop program.
+
+/// Boot info served through local preimage keys.
+pub mod boot;
+pub use boot::BootInfoSource;
+
+/// Preimage storage of a run.
+pub mod storage;
+pub use storage::{
+    export_bundle, open_bundle_storage, open_offline_storage, open_storage, write_witness,
+};
+
+/// Serving loops for the oracle channels.
+pub mod server;
+pub use server::serve;
+
+/// Spawning the client with the oracle channels.
+#[cfg(unix)]
+pub mod exec;
+#[cfg(unix)]
+pub use exec::run_client;
+
+/// Running the client program in-process.
+pub mod native;
+pub use native::run_native;
+
+/// JSON-RPC client.
+pub mod rpc;
+
+/// Beacon node API client.
+pub mod beacon;
+
+/// Merkle-Patricia trie construction.
+pub mod trie;
+
+/// Hint handlers fetching preimages from RPC endpoints.
+pub mod fetcher;
+pub use fetcher::Fetcher;
+
+#[cfg(test)]
+mod test_utils;
@@ -0,0 +1,108 @@
+use eyre::Result;
+use std::sync::Arc;
+
+use palmtop_kv::KeyValueStore;
+use palmtop_preimage::client::OracleClientImpl;
+use palmtop_preimage::hints::{HintHandler, HintWriter};
+use palmtop_preimage::pipe::{pipe, PipeReader, PipeWriter};
+
+use crate::server::serve;
+
+/// The oracle client of a program run in-process.
+pub type NativeOracle = OracleClientImpl<PipeReader, PipeWriter>;
+
+/// The hinter of a program run in-process.
+pub type NativeHinter = HintWriter<PipeReader, PipeWriter>;
+
+/// Runs the client program in-process on a dedicated thread, serving its oracle
+/// channels over in-memory pipes until it returns.
+///
+/// The channels carry the same wire protocol as in [crate::run_client], so the
+/// program sees the same hints and preimages as it would in a subprocess.
+pub fn run_native<T, F>(
+    program: F,
+    store: Arc<dyn KeyValueStore>,
+    router: Arc<HintHandler>,
+) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut NativeOracle, &mut NativeHinter) -> Result<T> + Send + 'static,
+{
+    let (hint_client_read, hint_host_write) = pipe();
+    let (hint_host_read, hint_client_write) = pipe();
+    let (preimage_client_read, preimage_host_write) = pipe();
+    let (preimage_host_read, preimage_client_write) = pipe();
+
+    // The program owns the client's ends, so the channels reach end of file once
+    // it returns and the host stops serving.
+    let client = std::thread::Builder::new()
+        .name("palmtop-client".to_string())
+        .spawn(move || {
+            let mut oracle = OracleClientImpl::new(preimage_client_read, preimage_client_write);
+            let mut hinter = HintWriter::new(hint_client_read, hint_client_write);
+            program(&mut oracle, &mut hinter)
+        })?;
+    tracing::info!(target: "palmtop::host", "Running client program in-process");
+
+    // If serving fails, the host's ends of the failed channel are dropped, which
+    // fails the program's pending request instead of leaving it blocked.
+    let served = serve(
+        (hint_host_read, hint_host_write),
+        (preimage_host_read, preimage_host_write),
+        store,
+        router,
+    );
+    let result = client
+        .join()
+        .map_err(|_| eyre::eyre!("client program panicked"))?;
+    served?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ignore_hint;
+    use palmtop_kv::MemoryKeyValueStore;
+    use palmtop_preimage::client::OracleClient;
+    use palmtop_primitives::{Hinter, PreimageKeyType};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_run_native_serves_channels() {
+        let key = PreimageKeyType::Keccak256.key([0x61; 32]);
+        let store = Arc::new(MemoryKeyValueStore::from_iter([(key, b"palmtop".to_vec())]));
+        let hints = Arc::new(Mutex::new(Vec::new()));
+        let seen = hints.clone();
+        let router: Arc<HintHandler> = Arc::new(move |hint| {
+            seen.lock().unwrap().push(hint);
+            Ok(())
+        });
+
+        let preimage = run_native(
+            move |oracle, hinter| {
+                hinter.hint("hello".to_string())?;
+                oracle.get(key)
+            },
+            store,
+            router,
+        )
+        .unwrap();
+
+        assert_eq!(preimage, b"palmtop");
+        assert_eq!(*hints.lock().unwrap(), ["hello"]);
+    }
+
+    #[test]
+    fn test_run_native_missing_preimage() {
+        let store = Arc::new(MemoryKeyValueStore::new());
+        let key = PreimageKeyType::Keccak256.key([0x61; 32]);
+        let err = run_native(
+            move |oracle, _| oracle.get(key),
+            store,
+            Arc::new(ignore_hint),
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("preimage not found"));
+    }
+}
@@ -0,0 +1,75 @@
+use eyre::Result;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// The timeout of a single RPC request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// ## RpcClient
+///
+/// A blocking JSON-RPC client over HTTP, used by the host to fetch the data
+/// behind a hint from an L1 or L2 node.
+#[derive(Debug)]
+pub struct RpcClient {
+    url: String,
+    agent: ureq::Agent,
+    next_id: AtomicU64,
+}
+
+impl RpcClient {
+    /// Creates a new [RpcClient] for the given endpoint.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            agent: ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build(),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Returns the URL of the endpoint.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Calls the given method and deserializes its result.
+    pub fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        tracing::debug!(target: "palmtop::rpc", "Calling {} with {}", method, params);
+        let mut response: Value = self
+            .agent
+            .post(&self.url)
+            .send_json(request)
+            .map_err(|e| eyre::eyre!("{} request to {} failed: {}", method, self.url, e))?
+            .into_json()?;
+        if let Some(error) = response.get("error") {
+            eyre::bail!("{} failed: {}", method, error);
+        }
+        let result = response
+            .get_mut("result")
+            .map(Value::take)
+            .unwrap_or_default();
+        serde_json::from_value(result).map_err(|e| eyre::eyre!("invalid {} result: {}", method, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockServer;
+
+    #[test]
+    fn test_rpc_call() {
+        let server = MockServer::json_rpc(|method, params| match method {
+            "eth_chainId" => Ok(json!("0xa")),
+            _ => Err(json!({ "code": -32601, "message": format!("{method} {params}") })),
+        });
+        let client = RpcClient::new(server.url());
+        let chain_id: String = client.call("eth_chainId", json!([])).unwrap();
+        assert_eq!(chain_id, "0xa");
+        let err = client.call::<Value>("eth_foo", json!([1])).unwrap_err();
+        assert!(err.to_string().contains("eth_foo [1]"));
+    }
+}
@@ -0,0 +1,152 @@
+use eyre::Result;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::sync::{mpsc, Arc};
+
+use palmtop_kv::KeyValueStore;
+use palmtop_preimage::hints::{HintHandler, HintReader};
+use palmtop_preimage::inner::FileReadWriter;
+use palmtop_preimage::server::{OracleServer, OracleServerImpl};
+
+/// Serves preimage requests from the given store until the client closes the channel.
+pub fn serve_preimages<Reader, Writer>(
+    server: &mut OracleServerImpl<Reader, Writer>,
+    store: Arc<dyn KeyValueStore>,
+) -> Result<()>
+where
+    Reader: Read,
+    Writer: Write,
+{
+    loop {
+        match server.next_preimage_request(palmtop_kv::getter(store.clone())) {
+            Ok(()) => {}
+            Err(e) if is_closed(&e) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Routes hints to the given handler until the client closes the channel.
+pub fn serve_hints(reader: &mut HintReader, router: &HintHandler) -> Result<()> {
+    loop {
+        match reader.next_hint(router) {
+            Ok(()) => {}
+            Err(e) if is_closed(&e) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Acknowledges a hint without preparing any preimages, for hosts that serve
+/// only what is already in their store.
+pub fn ignore_hint(hint: String) -> Result<()> {
+    tracing::debug!(target: "palmtop::host", "Ignoring hint: {}", hint);
+    Ok(())
+}
+
+/// Serves the hint and preimage channels of a client concurrently until the
+/// client closes both of them.
+///
+/// Each channel is given as its (reader, writer) pair from the host's side and is
+/// served on its own thread. If either channel fails, the error is returned right
+/// away instead of waiting on a client that may be blocked on the failed channel.
+pub fn serve<HR, HW, PR, PW>(
+    hint_channel: (HR, HW),
+    preimage_channel: (PR, PW),
+    store: Arc<dyn KeyValueStore>,
+    router: Arc<HintHandler>,
+) -> Result<()>
+where
+    HR: Read + Send + 'static,
+    HW: Write + Send + 'static,
+    PR: Read + Send + 'static,
+    PW: Write + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let hint_tx = tx.clone();
+    std::thread::spawn(move || {
+        let (reader, writer) = hint_channel;
+        let inner = FileReadWriter::new(
+            Box::new(BufReader::new(reader)),
+            Box::new(BufWriter::new(writer)),
+        );
+        let res = serve_hints(&mut HintReader::new(Box::new(inner)), router.as_ref());
+        _ = hint_tx.send(("hint", res));
+    });
+    std::thread::spawn(move || {
+        let (reader, writer) = preimage_channel;
+        let mut server = OracleServerImpl::new(BufReader::new(reader), BufWriter::new(writer));
+        _ = tx.send(("preimage", serve_preimages(&mut server, store)));
+    });
+
+    for _ in 0..2 {
+        let (channel, res) = rx
+            .recv()
+            .map_err(|_| eyre::eyre!("oracle server thread panicked"))?;
+        res.map_err(|e| e.wrap_err(format!("{channel} channel failed")))?;
+        tracing::debug!(target: "palmtop::host", "Client closed the {} channel", channel);
+    }
+    Ok(())
+}
+
+/// Returns true if the error was caused by the client closing the channel
+/// before the start of the next request.
+fn is_closed(err: &eyre::Report) -> bool {
+    err.downcast_ref::<io::Error>()
+        .map(|e| e.kind() == io::ErrorKind::UnexpectedEof)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use palmtop_kv::MemoryKeyValueStore;
+    use palmtop_primitives::PreimageKeyType;
+    use std::io::Cursor;
+
+    fn hint_frame(hint: &str) -> Vec<u8> {
+        let mut frame = (hint.len() as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(hint.as_bytes());
+        frame
+    }
+
+    #[test]
+    fn test_serve_until_closed() {
+        let key = PreimageKeyType::Keccak256.key([1; 32]);
+        let store = Arc::new(MemoryKeyValueStore::from_iter([(key, vec![1, 2, 3])]));
+        let requests = [key, key].concat();
+        serve(
+            (Cursor::new(hint_frame("l1-block-header 0x01")), vec![]),
+            (Cursor::new(requests), vec![]),
+            store,
+            Arc::new(ignore_hint),
+        )
+        .expect("Should not error");
+    }
+
+    #[test]
+    fn test_serve_missing_preimage() {
+        let key = PreimageKeyType::Keccak256.key([1; 32]);
+        let err = serve(
+            (Cursor::new(vec![]), vec![]),
+            (Cursor::new(key.to_vec()), vec![]),
+            Arc::new(MemoryKeyValueStore::new()),
+            Arc::new(ignore_hint),
+        )
+        .unwrap_err();
+        let err = format!("{err:#}");
+        assert!(err.contains("keccak256"));
+        assert!(err.contains(&alloy_primitives::hex::encode(key)));
+    }
+
+    #[test]
+    fn test_serve_hint_failure() {
+        let err = serve(
+            (Cursor::new(hint_frame("unknown 0x01")), vec![]),
+            (Cursor::new(vec![]), vec![]),
+            Arc::new(MemoryKeyValueStore::new()),
+            Arc::new(|hint| Err(eyre::eyre!("unsupported hint {hint}"))),
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("unsupported hint"));
+    }
+}
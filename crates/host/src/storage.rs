@@ -0,0 +1,220 @@
+use eyre::Result;
+use std::path::Path;
+use std::sync::Arc;
+
+use palmtop_kv::{
+    BundleCompression, BundleKeyValueStore, KeyValueStore, LogKeyValueStore, MemoryKeyValueStore,
+    ReadReport, SplitKeyValueStore,
+};
+use palmtop_primitives::{PreimageKey, PreimageKeyType};
+
+use crate::BootInfoSource;
+
+/// Opens the preimage storage of a single run.
+///
+/// Local keys are kept in memory, seeded with the given boot info, so they never
+/// outlive the run. Global keys are kept in a [LogKeyValueStore] in the datadir,
+/// shared by every run using it, or in memory when no datadir is given.
+pub fn open_storage(
+    datadir: Option<&Path>,
+    boot_info: &BootInfoSource,
+) -> Result<SplitKeyValueStore> {
+    let local = MemoryKeyValueStore::from_iter(boot_info.preimages());
+    let global: Arc<dyn KeyValueStore> = match datadir {
+        Some(datadir) => {
+            tracing::info!(target: "palmtop::host", "Opening preimage store in {}", datadir.display());
+            Arc::new(LogKeyValueStore::open(datadir)?)
+        }
+        None => Arc::new(MemoryKeyValueStore::new()),
+    };
+    Ok(SplitKeyValueStore::new(Arc::new(local), global))
+}
+
+/// Opens the preimage storage of a single offline run.
+///
+/// Like [open_storage], but the datadir must already hold a preimage log, since an
+/// offline run can only serve the preimages already in it.
+pub fn open_offline_storage(
+    datadir: &Path,
+    boot_info: &BootInfoSource,
+) -> Result<SplitKeyValueStore> {
+    let local = MemoryKeyValueStore::from_iter(boot_info.preimages());
+    tracing::info!(target: "palmtop::host", "Opening preimage store in {}", datadir.display());
+    let global = LogKeyValueStore::open_existing(datadir)?;
+    Ok(SplitKeyValueStore::new(Arc::new(local), Arc::new(global)))
+}
+
+/// Opens the preimage storage of a single run served from a witness bundle.
+///
+/// Like [open_storage], local keys are kept in memory, seeded with the given boot
+/// info, while global keys are served from the read-only bundle.
+pub fn open_bundle_storage(
+    bundle: &Path,
+    boot_info: &BootInfoSource,
+) -> Result<SplitKeyValueStore> {
+    let local = MemoryKeyValueStore::from_iter(boot_info.preimages());
+    let global = BundleKeyValueStore::open(bundle)?;
+    Ok(SplitKeyValueStore::new(Arc::new(local), Arc::new(global)))
+}
+
+/// Exports every preimage of the datadir to a witness bundle at the given path,
+/// returning the number of preimages exported.
+pub fn export_bundle(
+    datadir: &Path,
+    bundle: &Path,
+    compression: BundleCompression,
+) -> Result<usize> {
+    let store = LogKeyValueStore::open(datadir)?;
+    let entries = store
+        .keys()
+        .into_iter()
+        .map(|key| {
+            Ok((
+                key,
+                store.get(key)?.ok_or_else(|| palmtop_kv::not_found(&key))?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let count = entries.len();
+    BundleKeyValueStore::export(bundle, entries, compression)?;
+    Ok(count)
+}
+
+/// Writes a witness store to the given directory holding only the given keys'
+/// preimages, read from the store.
+///
+/// Local keys are skipped, since the boot info is given anew to every run. The
+/// witness is a [LogKeyValueStore], so it can be served as an offline datadir.
+/// Returns a report of the preimages written.
+pub fn write_witness(
+    store: &dyn KeyValueStore,
+    keys: &[PreimageKey],
+    directory: &Path,
+) -> Result<ReadReport> {
+    if directory.join(palmtop_kv::log::LOG_FILE).exists() {
+        eyre::bail!("witness directory {} is not empty", directory.display());
+    }
+    let witness = LogKeyValueStore::open(directory)?;
+    let mut written = ReadReport::default();
+    for key in keys {
+        if PreimageKeyType::of(key)? == PreimageKeyType::Local {
+            continue;
+        }
+        let value = store.get(*key)?.ok_or_else(|| palmtop_kv::not_found(key))?;
+        written.count += 1;
+        written.bytes += value.len();
+        written.keys.push(*key);
+        witness.put(*key, value)?;
+    }
+    witness.sync()?;
+    tracing::info!(
+        target: "palmtop::host",
+        "Wrote a witness of {} preimages ({} bytes) to {}",
+        written.count,
+        written.bytes,
+        directory.display()
+    );
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::B256;
+    use palmtop_primitives::{BootKey, PreimageKeyType};
+
+    #[test]
+    fn test_datadir_does_not_keep_boot_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let keccak_key = PreimageKeyType::Keccak256.key([7; 32]);
+        let first_run = BootInfoSource {
+            l1_head: Some(B256::repeat_byte(1)),
+            ..Default::default()
+        };
+        {
+            let storage = open_storage(Some(dir.path()), &first_run).unwrap();
+            assert_eq!(
+                storage.get(BootKey::L1Head.key()).unwrap(),
+                Some(vec![1; 32])
+            );
+            storage.put(keccak_key, vec![2]).unwrap();
+        }
+
+        let storage = open_storage(Some(dir.path()), &BootInfoSource::default()).unwrap();
+        assert_eq!(storage.get(BootKey::L1Head.key()).unwrap(), None);
+        assert_eq!(storage.get(keccak_key).unwrap(), Some(vec![2]));
+    }
+
+    #[test]
+    fn test_offline_storage_requires_existing_datadir() {
+        let dir = tempfile::tempdir().unwrap();
+        let datadir = dir.path().join("datadir");
+        let boot_info = BootInfoSource::default();
+        assert!(open_offline_storage(&datadir, &boot_info).is_err());
+        assert!(!datadir.exists());
+
+        open_storage(Some(&datadir), &boot_info).unwrap();
+        assert!(open_offline_storage(&datadir, &boot_info).is_ok());
+    }
+
+    #[test]
+    fn test_write_witness_keeps_only_given_keys() {
+        let read = PreimageKeyType::Keccak256.key([1; 32]);
+        let unread = PreimageKeyType::Keccak256.key([2; 32]);
+        let boot_info = BootInfoSource {
+            l1_head: Some(B256::repeat_byte(1)),
+            ..Default::default()
+        };
+        let storage = open_storage(None, &boot_info).unwrap();
+        storage.put(read, vec![3; 4]).unwrap();
+        storage.put(unread, vec![4; 8]).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let keys = [BootKey::L1Head.key(), read];
+        let written = write_witness(&storage, &keys, dir.path()).unwrap();
+        assert_eq!((written.count, written.bytes), (1, 4));
+
+        let witness = LogKeyValueStore::open(dir.path()).unwrap();
+        assert_eq!(witness.keys(), vec![read]);
+        assert_eq!(witness.get(read).unwrap(), Some(vec![3; 4]));
+        drop(witness);
+
+        let err = write_witness(&storage, &[read], dir.path()).unwrap_err();
+        assert!(err.to_string().contains("not empty"));
+    }
+
+    #[test]
+    fn test_write_witness_missing_key() {
+        let storage = open_storage(None, &BootInfoSource::default()).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let missing = PreimageKeyType::Keccak256.key([1; 32]);
+        assert!(write_witness(&storage, &[missing], dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_export_and_serve_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let datadir = dir.path().join("datadir");
+        let preimage = b"palmtop".to_vec();
+        let key = PreimageKeyType::Keccak256.key(alloy_primitives::keccak256(&preimage).0);
+        open_storage(Some(&datadir), &BootInfoSource::default())
+            .unwrap()
+            .put(key, preimage.clone())
+            .unwrap();
+
+        let bundle = dir.path().join("witness.bundle");
+        let count = export_bundle(&datadir, &bundle, BundleCompression::Zlib).unwrap();
+        assert_eq!(count, 1);
+
+        let boot_info = BootInfoSource {
+            l1_head: Some(B256::repeat_byte(1)),
+            ..Default::default()
+        };
+        let storage = open_bundle_storage(&bundle, &boot_info).unwrap();
+        assert_eq!(storage.get(key).unwrap(), Some(preimage));
+        assert_eq!(
+            storage.get(BootKey::L1Head.key()).unwrap(),
+            Some(vec![1; 32])
+        );
+    }
+}
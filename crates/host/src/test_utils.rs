@@ -0,0 +1,88 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+/// A handler of mock HTTP requests, given the method, path and body of a request
+/// and returning the status and JSON body of the response.
+type HttpHandler = dyn Fn(&str, &str, &[u8]) -> (u16, String) + Send + Sync;
+
+/// A local HTTP server for testing the host against mocked endpoints.
+pub(crate) struct MockServer {
+    url: String,
+}
+
+impl MockServer {
+    /// Starts a server answering every request with the given handler.
+    pub(crate) fn http(
+        handler: impl Fn(&str, &str, &[u8]) -> (u16, String) + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handler: Arc<HttpHandler> = Arc::new(handler);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let handler = handler.clone();
+                std::thread::spawn(move || respond(stream, handler.as_ref()));
+            }
+        });
+        Self { url }
+    }
+
+    /// Starts a JSON-RPC server answering every call with the given handler,
+    /// which returns either the result or the error object of the call.
+    pub(crate) fn json_rpc(
+        handler: impl Fn(&str, &Value) -> Result<Value, Value> + Send + Sync + 'static,
+    ) -> Self {
+        Self::http(move |_, _, body| {
+            let request: Value = serde_json::from_slice(body).unwrap();
+            let method = request["method"].as_str().unwrap();
+            let response = match handler(method, &request["params"]) {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }),
+                Err(error) => json!({ "jsonrpc": "2.0", "id": request["id"], "error": error }),
+            };
+            (200, response.to_string())
+        })
+    }
+
+    /// Returns the URL of the server.
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+/// Reads a single HTTP request from the stream and writes the handler's response.
+fn respond(stream: TcpStream, handler: &HttpHandler) {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+
+    let (status, response) = handler(&method, &path, &body);
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.1 {status} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+        response.len()
+    )
+    .unwrap();
+    stream.flush().unwrap();
+}
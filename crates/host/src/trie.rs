@@ -0,0 +1,92 @@
+use alloy_primitives::B256;
+use alloy_trie::{proof::ProofRetainer, HashBuilder, Nibbles, EMPTY_ROOT_HASH};
+
+/// The RLP encoding of an empty trie node.
+const EMPTY_NODE: u8 = alloy_rlp::EMPTY_STRING_CODE;
+
+/// Builds the Merkle-Patricia trie of an ordered list, like the transactions or
+/// receipts of a block, where each value is keyed by the RLP encoding of its index.
+///
+/// Returns the root hash of the trie and the RLP encoding of every node that is
+/// referenced by hash, including the root node, so that each of them can be stored
+/// as a keccak256 preimage. Nodes shorter than 32 bytes are inlined into their
+/// parent and need not be stored.
+pub fn ordered_trie_nodes(values: &[Vec<u8>]) -> (B256, Vec<Vec<u8>>) {
+    if values.is_empty() {
+        return (EMPTY_ROOT_HASH, vec![vec![EMPTY_NODE]]);
+    }
+
+    let mut leaves = values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| (Nibbles::unpack(alloy_rlp::encode(index)), value))
+        .collect::<Vec<_>>();
+    leaves.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let retainer = ProofRetainer::new(leaves.iter().map(|(key, _)| key.clone()).collect());
+    let mut builder = HashBuilder::default().with_proof_retainer(retainer);
+    for (key, value) in &leaves {
+        builder.add_leaf(key.clone(), value);
+    }
+    let root = builder.root();
+    let nodes = builder
+        .take_proofs()
+        .into_iter()
+        .filter(|(path, node)| path.is_empty() || node.len() >= 32)
+        .map(|(_, node)| node.to_vec())
+        .collect();
+    (root, nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::keccak256;
+
+    #[test]
+    fn test_empty_trie() {
+        let (root, nodes) = ordered_trie_nodes(&[]);
+        assert_eq!(root, EMPTY_ROOT_HASH);
+        assert_eq!(keccak256(&nodes[0]), root);
+    }
+
+    #[test]
+    fn test_ordered_trie_nodes() {
+        let values = (0..300u32)
+            .map(|i| i.to_be_bytes().repeat(10))
+            .collect::<Vec<_>>();
+        let (root, nodes) = ordered_trie_nodes(&values);
+
+        let mut leaves = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (Nibbles::unpack(alloy_rlp::encode(index)), value))
+            .collect::<Vec<_>>();
+        leaves.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut builder = HashBuilder::default();
+        for (key, value) in leaves {
+            builder.add_leaf(key, value);
+        }
+        assert_eq!(root, builder.root());
+
+        // Every node other than the root is referenced by hash from another node.
+        assert!(nodes.len() > 16);
+        for node in &nodes {
+            let hash = keccak256(node);
+            assert!(
+                hash == root
+                    || nodes
+                        .iter()
+                        .any(|parent| parent.windows(32).any(|w| w == hash.as_slice()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_single_value_trie_keeps_short_root() {
+        let (root, nodes) = ordered_trie_nodes(&[vec![1]]);
+        assert_eq!(nodes.len(), 1);
+        assert!(nodes[0].len() < 32);
+        assert_eq!(keccak256(&nodes[0]), root);
+    }
+}
@@ -0,0 +1,367 @@
+use alloy_primitives::keccak256;
+use eyre::Result;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+use tempfile::NamedTempFile;
+
+use palmtop_primitives::{Preimage, PreimageKey, PreimageKeyType};
+
+use crate::KeyValueStore;
+
+/// The magic bytes starting a bundle, ending in the format version.
+const BUNDLE_MAGIC: [u8; 8] = *b"PTBNDL\0\x02";
+
+/// The length of a bundle header: magic, compression, body length and content hash.
+const HEADER_LEN: usize = 8 + 1 + 8 + 32;
+
+/// The largest uncompressed body a bundle may declare.
+pub const MAX_BUNDLE_BODY_LEN: u64 = 1 << 32;
+
+/// The length of a manifest entry: key, key type and preimage length.
+const MANIFEST_ENTRY_LEN: usize = 32 + 1 + 4;
+
+/// The compression of a bundle's body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BundleCompression {
+    /// zlib compression.
+    #[default]
+    Zlib = 1,
+    /// Brotli compression.
+    Brotli = 2,
+}
+
+impl BundleCompression {
+    fn compress(self, body: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::Zlib => {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(body)?;
+                Ok(encoder.finish()?)
+            }
+            Self::Brotli => {
+                let mut compressed = Vec::new();
+                let mut encoder = brotli::CompressorWriter::new(&mut compressed, 4096, 9, 22);
+                encoder.write_all(body)?;
+                drop(encoder);
+                Ok(compressed)
+            }
+        }
+    }
+
+    /// Decompresses a body of the given length, reading no more than one byte
+    /// past it so that a corrupt bundle cannot exhaust memory.
+    fn decompress(self, compressed: &[u8], len: u64) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        match self {
+            Self::Zlib => flate2::read::ZlibDecoder::new(compressed)
+                .take(len + 1)
+                .read_to_end(&mut body)?,
+            Self::Brotli => brotli::Decompressor::new(compressed, 4096)
+                .take(len + 1)
+                .read_to_end(&mut body)?,
+        };
+        if body.len() as u64 != len {
+            eyre::bail!(
+                "bundle body does not match its declared length of {} bytes",
+                len
+            );
+        }
+        Ok(body)
+    }
+}
+
+impl TryFrom<u8> for BundleCompression {
+    type Error = eyre::Report;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            1 => Ok(Self::Zlib),
+            2 => Ok(Self::Brotli),
+            _ => eyre::bail!("unknown bundle compression {}", value),
+        }
+    }
+}
+
+impl FromStr for BundleCompression {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "zlib" => Ok(Self::Zlib),
+            "brotli" => Ok(Self::Brotli),
+            _ => eyre::bail!("unknown bundle compression {}, expected zlib or brotli", s),
+        }
+    }
+}
+
+impl fmt::Display for BundleCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Zlib => write!(f, "zlib"),
+            Self::Brotli => write!(f, "brotli"),
+        }
+    }
+}
+
+/// ## BundleKeyValueStore
+///
+/// The BundleKeyValueStore serves the preimages of a witness bundle, a single file
+/// for moving a run's preimages between machines. It is read-only.
+///
+/// A bundle starts with a header of the magic bytes, the compression of its body,
+/// the length of the uncompressed body as a big-endian u64 and its sha256 hash.
+/// The body holds the entry count as a big-endian u32, a manifest of each entry's
+/// key, key type and preimage length as a big-endian u32, then every preimage in
+/// manifest order.
+///
+/// Opening a bundle checks its content hash and that every keccak256 and sha256
+/// preimage hashes to its key.
+#[derive(Debug)]
+pub struct BundleKeyValueStore {
+    preimages: HashMap<PreimageKey, Preimage>,
+}
+
+impl BundleKeyValueStore {
+    /// Opens and verifies the bundle at the given path.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| eyre::eyre!("failed to read bundle {}: {}", path.display(), e))?;
+        let store = Self::decode(&bytes)?;
+        tracing::info!(
+            target: "palmtop::kv",
+            "Opened bundle of {} preimages from {}",
+            store.len(),
+            path.display()
+        );
+        Ok(store)
+    }
+
+    /// Decodes and verifies a bundle.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LEN || bytes[..8] != BUNDLE_MAGIC {
+            eyre::bail!("not a palmtop bundle");
+        }
+        let compression = BundleCompression::try_from(bytes[8])?;
+        let len = u64::from_be_bytes(bytes[9..17].try_into()?);
+        if len > MAX_BUNDLE_BODY_LEN {
+            eyre::bail!("bundle body of {} bytes is too large", len);
+        }
+        let body = compression.decompress(&bytes[HEADER_LEN..], len)?;
+        if Sha256::digest(&body)[..] != bytes[17..HEADER_LEN] {
+            eyre::bail!("bundle content hash mismatch");
+        }
+
+        let count = u32::from_be_bytes(take(&body, 0, 4)?.try_into()?) as usize;
+        let manifest = take(&body, 4, count * MANIFEST_ENTRY_LEN)?;
+        let mut offset = 4 + manifest.len();
+        let mut preimages = HashMap::with_capacity(count);
+        for entry in manifest.chunks_exact(MANIFEST_ENTRY_LEN) {
+            let key: PreimageKey = entry[..32].try_into()?;
+            let key_type = PreimageKeyType::try_from(entry[32])?;
+            if PreimageKeyType::of(&key)? != key_type {
+                eyre::bail!(
+                    "bundle entry 0x{} has key type {}",
+                    hex::encode(key),
+                    key_type
+                );
+            }
+            let len = u32::from_be_bytes(entry[33..].try_into()?) as usize;
+            let value = take(&body, offset, len)?.to_vec();
+            offset += len;
+            verify(&key, key_type, &value)?;
+            if preimages.insert(key, value).is_some() {
+                eyre::bail!("duplicate bundle entry 0x{}", hex::encode(key));
+            }
+        }
+        if offset != body.len() {
+            eyre::bail!("bundle has {} trailing bytes", body.len() - offset);
+        }
+        Ok(Self { preimages })
+    }
+
+    /// Encodes the given preimages as a bundle, sorted by key.
+    pub fn encode(
+        entries: impl IntoIterator<Item = (PreimageKey, Preimage)>,
+        compression: BundleCompression,
+    ) -> Result<Vec<u8>> {
+        let mut entries = entries.into_iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(key, _)| *key);
+        let mut manifest = Vec::with_capacity(entries.len() * MANIFEST_ENTRY_LEN);
+        let mut data = Vec::new();
+        for (key, value) in &entries {
+            manifest.extend_from_slice(key);
+            manifest.push(PreimageKeyType::of(key)? as u8);
+            manifest.extend_from_slice(&u32::try_from(value.len())?.to_be_bytes());
+            data.extend_from_slice(value);
+        }
+        let body = [
+            &u32::try_from(entries.len())?.to_be_bytes()[..],
+            &manifest,
+            &data,
+        ]
+        .concat();
+
+        let mut bundle = BUNDLE_MAGIC.to_vec();
+        bundle.push(compression as u8);
+        bundle.extend_from_slice(&(body.len() as u64).to_be_bytes());
+        bundle.extend_from_slice(&Sha256::digest(&body));
+        bundle.extend_from_slice(&compression.compress(&body)?);
+        Ok(bundle)
+    }
+
+    /// Writes the given preimages as a bundle to the given path, atomically
+    /// replacing any existing file.
+    pub fn export(
+        path: impl AsRef<Path>,
+        entries: impl IntoIterator<Item = (PreimageKey, Preimage)>,
+        compression: BundleCompression,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let bundle = Self::encode(entries, compression)?;
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut file = NamedTempFile::new_in(directory)?;
+        file.write_all(&bundle)?;
+        file.as_file().sync_all()?;
+        file.persist(path)?;
+        tracing::info!(
+            target: "palmtop::kv",
+            "Exported a {} bundle of {} bytes to {}",
+            compression,
+            bundle.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Returns the number of preimages in the bundle.
+    pub fn len(&self) -> usize {
+        self.preimages.len()
+    }
+
+    /// Returns true if the bundle holds no preimages.
+    pub fn is_empty(&self) -> bool {
+        self.preimages.is_empty()
+    }
+}
+
+impl KeyValueStore for BundleKeyValueStore {
+    fn get(&self, key: PreimageKey) -> Result<Option<Preimage>> {
+        Ok(self.preimages.get(&key).cloned())
+    }
+
+    fn put(&self, key: PreimageKey, _: Preimage) -> Result<()> {
+        eyre::bail!("cannot store 0x{} in a read-only bundle", hex::encode(key))
+    }
+}
+
+/// Returns the given range of the body, failing if it is truncated.
+fn take(body: &[u8], offset: usize, len: usize) -> Result<&[u8]> {
+    body.get(offset..offset + len)
+        .ok_or_else(|| eyre::eyre!("bundle is truncated"))
+}
+
+/// Checks that a keccak256 or sha256 preimage hashes to its key.
+fn verify(key: &PreimageKey, key_type: PreimageKeyType, value: &[u8]) -> Result<()> {
+    let hash = match key_type {
+        PreimageKeyType::Keccak256 => keccak256(value).0,
+        PreimageKeyType::Sha256 => Sha256::digest(value).into(),
+        _ => return Ok(()),
+    };
+    if key_type.key(hash) != *key {
+        eyre::bail!(
+            "bundle preimage does not match its key 0x{}",
+            hex::encode(key)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use palmtop_primitives::local_key;
+
+    fn entries() -> Vec<(PreimageKey, Preimage)> {
+        let keccak = b"keccak".to_vec();
+        let sha = b"sha".to_vec();
+        vec![
+            (PreimageKeyType::Keccak256.key(keccak256(&keccak).0), keccak),
+            (
+                PreimageKeyType::Sha256.key(Sha256::digest(&sha).into()),
+                sha,
+            ),
+            (PreimageKeyType::Blob.key([5; 32]), vec![0; 32]),
+            (local_key(1), vec![]),
+        ]
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        for compression in [BundleCompression::Zlib, BundleCompression::Brotli] {
+            let bundle = BundleKeyValueStore::encode(entries(), compression).unwrap();
+            assert_eq!(bundle[8], compression as u8);
+            let store = BundleKeyValueStore::decode(&bundle).unwrap();
+            assert_eq!(store.len(), 4);
+            for (key, value) in entries() {
+                assert_eq!(store.get(key).unwrap(), Some(value));
+            }
+            assert!(store.put(local_key(2), vec![]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_bundle_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("witness.bundle");
+        BundleKeyValueStore::export(&path, entries(), BundleCompression::Brotli).unwrap();
+        assert_eq!(BundleKeyValueStore::open(&path).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_bundle_rejects_corruption() {
+        let mut bundle = BundleKeyValueStore::encode(entries(), BundleCompression::Zlib).unwrap();
+        bundle[20] ^= 1;
+        let err = BundleKeyValueStore::decode(&bundle).unwrap_err();
+        assert!(err.to_string().contains("content hash"));
+        assert!(BundleKeyValueStore::decode(b"PTLOG").is_err());
+    }
+
+    #[test]
+    fn test_bundle_bounds_decompressed_size() {
+        // A small body that decompresses to far more than its declared length.
+        let body = vec![0; 1 << 24];
+        for compression in [BundleCompression::Zlib, BundleCompression::Brotli] {
+            let mut bundle = BUNDLE_MAGIC.to_vec();
+            bundle.push(compression as u8);
+            bundle.extend_from_slice(&100u64.to_be_bytes());
+            bundle.extend_from_slice(&Sha256::digest(&body));
+            bundle.extend_from_slice(&compression.compress(&body).unwrap());
+            let err = BundleKeyValueStore::decode(&bundle).unwrap_err();
+            assert!(err.to_string().contains("declared length"));
+        }
+
+        let mut bundle = BundleKeyValueStore::encode(entries(), BundleCompression::Zlib).unwrap();
+        bundle[9..17].copy_from_slice(&(MAX_BUNDLE_BODY_LEN + 1).to_be_bytes());
+        let err = BundleKeyValueStore::decode(&bundle).unwrap_err();
+        assert!(err.to_string().contains("too large"));
+    }
+
+    #[test]
+    fn test_bundle_rejects_mismatched_preimage() {
+        let key = PreimageKeyType::Keccak256.key([1; 32]);
+        let bundle =
+            BundleKeyValueStore::encode([(key, b"wrong".to_vec())], BundleCompression::Zlib)
+                .unwrap();
+        let err = BundleKeyValueStore::decode(&bundle).unwrap_err();
+        assert!(err.to_string().contains("does not match"));
+    }
+}
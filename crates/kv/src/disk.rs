@@ -0,0 +1,132 @@
+use eyre::Result;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+use palmtop_primitives::{Preimage, PreimageKey};
+
+use crate::KeyValueStore;
+
+/// ## DiskKeyValueStore
+///
+/// The DiskKeyValueStore stores each preimage in its own file, named after the hex
+/// encoding of its key, inside a single directory.
+///
+/// Values are written to a temporary file in the same directory which is then
+/// renamed over the final path, so concurrent readers only ever observe complete
+/// preimages and a crash mid-write leaves no partial entry behind.
+#[derive(Debug, Clone)]
+pub struct DiskKeyValueStore {
+    directory: PathBuf,
+}
+
+impl DiskKeyValueStore {
+    /// Creates a new [DiskKeyValueStore] in the given directory,
+    /// creating the directory if it does not exist.
+    pub fn new(directory: impl Into<PathBuf>) -> Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        Ok(Self { directory })
+    }
+
+    /// Returns the directory of the store.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns the path of the file holding the preimage for the given key.
+    fn path(&self, key: &PreimageKey) -> PathBuf {
+        self.directory.join(hex::encode(key))
+    }
+}
+
+impl KeyValueStore for DiskKeyValueStore {
+    fn get(&self, key: PreimageKey) -> Result<Option<Preimage>> {
+        match fs::read(self.path(&key)) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn put(&self, key: PreimageKey, value: Preimage) -> Result<()> {
+        let mut file = NamedTempFile::new_in(&self.directory)?;
+        file.write_all(&value)?;
+        file.as_file().sync_all()?;
+        file.persist(self.path(&key))?;
+        tracing::debug!(target: "palmtop::kv", "Stored preimage for key 0x{}", hex::encode(key));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use palmtop_preimage::server::{OracleServer, OracleServerImpl};
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_disk_store_get_put() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DiskKeyValueStore::new(dir.path().join("preimages")).unwrap();
+        let key = [1; 32];
+        assert_eq!(store.get(key).unwrap(), None);
+
+        store.put(key, vec![1, 2, 3, 4]).unwrap();
+        assert_eq!(store.get(key).unwrap(), Some(vec![1, 2, 3, 4]));
+        assert!(store.directory().join(hex::encode(key)).exists());
+
+        store.put(key, vec![5]).unwrap();
+        assert_eq!(store.get(key).unwrap(), Some(vec![5]));
+        assert_eq!(fs::read_dir(store.directory()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_disk_store_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        DiskKeyValueStore::new(dir.path())
+            .unwrap()
+            .put([2; 32], vec![1, 2])
+            .unwrap();
+        let store = DiskKeyValueStore::new(dir.path()).unwrap();
+        assert_eq!(store.get([2; 32]).unwrap(), Some(vec![1, 2]));
+    }
+
+    #[test]
+    fn test_disk_store_concurrent_readers() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(DiskKeyValueStore::new(dir.path()).unwrap());
+        let key = [3; 32];
+        store.put(key, vec![0; 1024]).unwrap();
+        std::thread::scope(|scope| {
+            for i in 1..4u8 {
+                let store = store.clone();
+                scope.spawn(move || store.put(key, vec![i; 1024]).unwrap());
+            }
+            for _ in 0..4 {
+                let store = store.clone();
+                scope.spawn(move || {
+                    let value = store.get(key).unwrap().unwrap();
+                    assert_eq!(value.len(), 1024);
+                    assert!(value.iter().all(|b| *b == value[0]));
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_disk_store_serves_oracle() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(DiskKeyValueStore::new(dir.path()).unwrap());
+        store.put([1; 32], vec![1, 2, 3, 4]).unwrap();
+
+        let mut wtr = vec![];
+        let mut server = OracleServerImpl::new(Cursor::new(vec![1; 32]), &mut wtr);
+        server
+            .next_preimage_request(crate::getter(store))
+            .expect("Should not error");
+        assert_eq!(wtr, [0, 0, 0, 0, 0, 0, 0, 4, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,76 @@
+#![cfg_attr(docsrs, feature(doc_cfg))]
+#![doc(
+    html_logo_url = "https://raw.githubusercontent.com/refcell/palmtop/main/extra/palmtop.png",
+    html_favicon_url = "https://avatars0.githubusercontent.com/u/97369466?s=256",
+    issue_tracker_base_url = "https://github.com/refcell/palmtop/issues/"
+)]
+#![warn(missing_docs, unreachable_pub, unused_crate_dependencies)]
+#![deny(unused_must_use, rust_2018_idioms)]
+#![doc(test(
+    no_crate_inject,
+    attr(deny(warnings, rust_2018_idioms), allow(dead_code, unused_variables))
+))]
+
+//! Palmtop Key-Value Stores
+//!
+//! Storage backends for the preimages served by the palmtop host.
+
+use eyre::Result;
+use std::sync::Arc;
+
+use palmtop_primitives::{Preimage, PreimageGetter, PreimageKey, PreimageKeyType};
+
+/// Read-only key-value store over a witness bundle.
+pub mod bundle;
+pub use bundle::{BundleCompression, BundleKeyValueStore};
+
+/// Directory-backed key-value store.
+pub mod disk;
+pub use disk::DiskKeyValueStore;
+
+/// Single-file append-only key-value store.
+pub mod log;
+pub use log::LogKeyValueStore;
+
+/// In-memory key-value store with snapshot export and import.
+pub mod memory;
+pub use memory::MemoryKeyValueStore;
+
+/// Key-value store recording the keys read from another store.
+pub mod recording;
+pub use recording::{ReadReport, RecordingKeyValueStore};
+
+/// Key-value store routing local and global keys to separate stores.
+pub mod split;
+pub use split::SplitKeyValueStore;
+
+/// ## KeyValueStore
+///
+/// The KeyValueStore trait defines the interface for a store of preimages keyed by
+/// their [PreimageKey]. Stores are shared between the hint handlers that write
+/// preimages and the oracle server that reads them, so both operations take `&self`.
+pub trait KeyValueStore: Send + Sync + std::fmt::Debug {
+    /// Returns the preimage stored under the given key, if any.
+    fn get(&self, key: PreimageKey) -> Result<Option<Preimage>>;
+
+    /// Stores the preimage under the given key, replacing any existing value.
+    fn put(&self, key: PreimageKey, value: Preimage) -> Result<()>;
+}
+
+/// Returns a [PreimageGetter] that serves preimages from the given store,
+/// failing for keys that are not in the store.
+pub fn getter<S: KeyValueStore + ?Sized + 'static>(store: Arc<S>) -> PreimageGetter {
+    Box::new(move |key| store.get(key)?.ok_or_else(|| not_found(&key)))
+}
+
+/// Returns the error for a key that is not in a store, naming the key and its type.
+pub fn not_found(key: &PreimageKey) -> eyre::Report {
+    let key_type = PreimageKeyType::of(key)
+        .map(|t| t.to_string())
+        .unwrap_or_else(|_| format!("unknown type {}", key[0]));
+    eyre::eyre!(
+        "preimage not found for {} key 0x{}",
+        key_type,
+        hex::encode(key)
+    )
+}
@@ -0,0 +1,752 @@
+use eyre::{Result, WrapErr};
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use tempfile::NamedTempFile;
+
+use palmtop_primitives::{Preimage, PreimageKey};
+
+use crate::KeyValueStore;
+
+/// The name of the append-only log file.
+pub const LOG_FILE: &str = "preimages.log";
+
+/// The name of the index file.
+pub const INDEX_FILE: &str = "preimages.idx";
+
+/// The name of the lock file, locked by the store that has the directory open.
+pub const LOCK_FILE: &str = "preimages.lock";
+
+/// The magic bytes at the start of the log file.
+const LOG_MAGIC: [u8; 8] = *b"PTLOG\0\0\x01";
+
+/// The magic bytes at the start of the index file.
+const INDEX_MAGIC: [u8; 8] = *b"PTIDX\0\0\x01";
+
+/// The size of the log header: the magic bytes followed by the log generation.
+const LOG_HEADER_LEN: u64 = 8 + 8;
+
+/// The size of the index header: the magic bytes, the log generation and the
+/// length of the log covered by the index.
+const INDEX_HEADER_LEN: usize = 8 + 8 + 8;
+
+/// The size of a record header: the key followed by the value length.
+const RECORD_HEADER_LEN: u64 = 32 + 4;
+
+/// The size of a record checksum.
+const RECORD_CHECKSUM_LEN: u64 = 4;
+
+/// The size of an index entry: the key, the value offset and the value length.
+const INDEX_ENTRY_LEN: usize = 32 + 8 + 4;
+
+/// The location of a value inside the log file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Location {
+    offset: u64,
+    len: u32,
+}
+
+/// The memory map of the log file, with the handle it is remapped from.
+#[derive(Debug)]
+struct LogMap {
+    file: File,
+    mmap: Mmap,
+}
+
+impl LogMap {
+    /// Maps the given log file.
+    fn new(file: File) -> Result<Self> {
+        // Safety: the log is only ever appended to or atomically replaced, so the
+        // mapped bytes are never modified while mapped.
+        let mmap = unsafe { Mmap::map(&file)? };
+        Ok(Self { file, mmap })
+    }
+
+    /// Remaps the log file to cover the records appended since it was mapped.
+    fn remap(&mut self) -> Result<()> {
+        self.mmap = unsafe { Mmap::map(&self.file)? };
+        Ok(())
+    }
+}
+
+/// The append handle of the log file.
+#[derive(Debug)]
+struct Appender {
+    file: File,
+    len: u64,
+    generation: u64,
+}
+
+impl Appender {
+    /// Appends a record to the log with the given write of the log file.
+    ///
+    /// If the write fails partway, the log is truncated back to its length
+    /// before the record: the part already written would otherwise shift every
+    /// later record away from the offset it is indexed at.
+    fn append(
+        &mut self,
+        record: &[u8],
+        write: impl FnOnce(&File, &[u8]) -> std::io::Result<()>,
+    ) -> Result<()> {
+        if let Err(err) = write(&self.file, record) {
+            self.file
+                .set_len(self.len)
+                .wrap_err("failed to truncate a partial record off the preimage log")?;
+            return Err(err.into());
+        }
+        self.len += record.len() as u64;
+        Ok(())
+    }
+}
+
+/// ## LogKeyValueStore
+///
+/// The LogKeyValueStore keeps every preimage in a single append-only log file,
+/// with an index file mapping each key to the location of its value in the log.
+///
+/// Each log record is the key, the big-endian u32 value length, the value and a
+/// crc32 checksum of the three. The log is the source of truth: the index is only
+/// rewritten on [LogKeyValueStore::sync], compaction and drop, and on open any
+/// records past the end covered by the index are replayed. A partial or corrupt
+/// record at the tail of the log, left behind by a crash mid-append, is truncated.
+/// Compaction bumps the generation in the log header, so an index left over from
+/// before a compaction is never applied to the compacted log.
+///
+/// Reads go through a shared memory map of the log, which is remapped whenever
+/// a lookup falls past the end of the current mapping.
+///
+/// A store holds an exclusive lock on the [LOCK_FILE] of its directory while it
+/// is open, so a directory is never opened by two stores at once.
+#[derive(Debug)]
+pub struct LogKeyValueStore {
+    directory: PathBuf,
+    index: RwLock<HashMap<PreimageKey, Location>>,
+    map: RwLock<LogMap>,
+    appender: Mutex<Appender>,
+    _lock: File,
+}
+
+/// Statistics reported by a [LogKeyValueStore] compaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionStats {
+    /// The number of records kept.
+    pub records: usize,
+    /// The size of the log before compaction.
+    pub bytes_before: u64,
+    /// The size of the log after compaction.
+    pub bytes_after: u64,
+}
+
+impl LogKeyValueStore {
+    /// Opens the [LogKeyValueStore] in the given directory, creating the directory
+    /// and log file if they do not exist and recovering from an interrupted append.
+    ///
+    /// Fails if the directory is already open in another store.
+    pub fn open(directory: impl Into<PathBuf>) -> Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        let lock = lock(&directory)?;
+        let log_path = directory.join(LOG_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&log_path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(&LOG_MAGIC)?;
+            file.write_all(&0u64.to_be_bytes())?;
+            file.sync_all()?;
+        }
+        let log_len = file.metadata()?.len();
+
+        let map = LogMap::new(file.try_clone()?)?.mmap;
+        if map.get(..LOG_MAGIC.len()) != Some(&LOG_MAGIC[..]) || log_len < LOG_HEADER_LEN {
+            eyre::bail!("{} is not a preimage log", log_path.display());
+        }
+        let generation = u64::from_be_bytes(map[8..16].try_into()?);
+
+        let index_path = directory.join(INDEX_FILE);
+        let (mut index, covered) = match read_index(&index_path, log_len, generation) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                tracing::warn!(target: "palmtop::kv", "Rebuilding preimage index: {}", e);
+                (HashMap::new(), LOG_HEADER_LEN)
+            }
+        };
+        let mut valid_len = replay(&map, covered, &mut index);
+        if valid_len < log_len && covered > LOG_HEADER_LEN {
+            // The index may not line up with the log, so rebuild it from a full scan
+            // before deciding how much of the log is a partial tail.
+            index.clear();
+            valid_len = replay(&map, LOG_HEADER_LEN, &mut index);
+        }
+        drop(map);
+        if valid_len < log_len {
+            tracing::warn!(
+                target: "palmtop::kv",
+                "Truncating {} bytes of partial records from the preimage log",
+                log_len - valid_len
+            );
+            file.set_len(valid_len)?;
+            file.sync_all()?;
+        }
+        let map = LogMap::new(file.try_clone()?)?;
+
+        Ok(Self {
+            directory,
+            index: RwLock::new(index),
+            map: RwLock::new(map),
+            appender: Mutex::new(Appender {
+                file,
+                len: valid_len,
+                generation,
+            }),
+            _lock: lock,
+        })
+    }
+
+    /// Opens the existing [LogKeyValueStore] in the given directory, failing if the
+    /// directory or its log file does not exist.
+    pub fn open_existing(directory: impl Into<PathBuf>) -> Result<Self> {
+        let directory = directory.into();
+        if !directory.join(LOG_FILE).is_file() {
+            eyre::bail!("{} holds no preimage log", directory.display());
+        }
+        Self::open(directory)
+    }
+
+    /// Returns the directory of the store.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns the number of preimages in the store.
+    pub fn len(&self) -> usize {
+        self.index.read().expect("index lock poisoned").len()
+    }
+
+    /// Returns true if the store holds no preimages.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns every key in the store.
+    pub fn keys(&self) -> Vec<PreimageKey> {
+        let index = self.index.read().expect("index lock poisoned");
+        index.keys().copied().collect()
+    }
+
+    /// Flushes the log to disk and rewrites the index file.
+    pub fn sync(&self) -> Result<()> {
+        let appender = self.appender.lock().expect("appender lock poisoned");
+        appender.file.sync_data()?;
+        let index = self.index.read().expect("index lock poisoned");
+        write_index(&self.directory, &index, appender.generation, appender.len)
+    }
+
+    /// Rewrites the log with only the latest record of each key, dropping the
+    /// records of overwritten values, and rewrites the index to match.
+    pub fn compact(&self) -> Result<CompactionStats> {
+        let mut appender = self.appender.lock().expect("appender lock poisoned");
+        let mut index = self.index.write().expect("index lock poisoned");
+        let mut map = self.map.write().expect("map lock poisoned");
+        if (map.mmap.len() as u64) < appender.len {
+            map.remap()?;
+        }
+
+        let mut keys = index.keys().copied().collect::<Vec<_>>();
+        keys.sort_by_key(|key| index[key].offset);
+        let generation = appender.generation + 1;
+        let tmp = NamedTempFile::new_in(&self.directory)?;
+        let mut writer = BufWriter::new(tmp);
+        writer.write_all(&LOG_MAGIC)?;
+        writer.write_all(&generation.to_be_bytes())?;
+        let mut len = LOG_HEADER_LEN;
+        let mut compacted = HashMap::with_capacity(keys.len());
+        for key in keys {
+            let location = index[&key];
+            let value = &map.mmap[location.offset as usize..][..location.len as usize];
+            write_record(&mut writer, &key, value)?;
+            compacted.insert(
+                key,
+                Location {
+                    offset: len + RECORD_HEADER_LEN,
+                    len: location.len,
+                },
+            );
+            len += RECORD_HEADER_LEN + location.len as u64 + RECORD_CHECKSUM_LEN;
+        }
+        let tmp = writer.into_inner().map_err(|e| e.into_error())?;
+        tmp.as_file().sync_all()?;
+        tmp.persist(self.directory.join(LOG_FILE))?;
+        write_index(&self.directory, &compacted, generation, len)?;
+
+        let stats = CompactionStats {
+            records: compacted.len(),
+            bytes_before: appender.len,
+            bytes_after: len,
+        };
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(self.directory.join(LOG_FILE))?;
+        *map = LogMap::new(file.try_clone()?)?;
+        *appender = Appender {
+            file,
+            len,
+            generation,
+        };
+        *index = compacted;
+        tracing::info!(target: "palmtop::kv", "Compacted preimage log: {:?}", stats);
+        Ok(stats)
+    }
+
+    /// Reads the value at the given location through the memory map,
+    /// remapping the log if the location is past the end of the mapping.
+    ///
+    /// The caller must hold the index lock the location was read under, so a
+    /// compaction cannot replace the log in between.
+    fn read(&self, location: Location) -> Result<Preimage> {
+        let end = location.offset as usize + location.len as usize;
+        {
+            let map = self.map.read().expect("map lock poisoned");
+            if end <= map.mmap.len() {
+                return Ok(map.mmap[location.offset as usize..end].to_vec());
+            }
+        }
+        let mut map = self.map.write().expect("map lock poisoned");
+        if map.mmap.len() < end {
+            map.remap()?;
+        }
+        map.mmap
+            .get(location.offset as usize..end)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| eyre::eyre!("preimage log is shorter than its index"))
+    }
+}
+
+impl KeyValueStore for LogKeyValueStore {
+    fn get(&self, key: PreimageKey) -> Result<Option<Preimage>> {
+        let index = self.index.read().expect("index lock poisoned");
+        index
+            .get(&key)
+            .map(|location| self.read(*location))
+            .transpose()
+    }
+
+    fn put(&self, key: PreimageKey, value: Preimage) -> Result<()> {
+        let len = u32::try_from(value.len())
+            .map_err(|_| eyre::eyre!("preimage of {} bytes is too large", value.len()))?;
+        if self.get(key)?.as_deref() == Some(value.as_slice()) {
+            return Ok(());
+        }
+
+        let mut appender = self.appender.lock().expect("appender lock poisoned");
+        let mut record = Vec::with_capacity(value.len() + 40);
+        write_record(&mut record, &key, &value)?;
+        let location = Location {
+            offset: appender.len + RECORD_HEADER_LEN,
+            len,
+        };
+        appender.append(&record, |mut file, record| file.write_all(record))?;
+        self.index
+            .write()
+            .expect("index lock poisoned")
+            .insert(key, location);
+        tracing::debug!(target: "palmtop::kv", "Appended preimage for key 0x{}", hex::encode(key));
+        Ok(())
+    }
+}
+
+impl Drop for LogKeyValueStore {
+    fn drop(&mut self) {
+        if let Err(e) = self.sync() {
+            tracing::warn!(target: "palmtop::kv", "Failed to sync preimage log: {}", e);
+        }
+    }
+}
+
+/// Takes the exclusive lock of the store in the given directory, failing if
+/// another store holds it.
+#[cfg(unix)]
+fn lock(directory: &Path) -> Result<File> {
+    use std::os::unix::io::AsRawFd;
+
+    let path = directory.join(LOCK_FILE);
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)?;
+    // Safety: flock only reads the descriptor, which stays open for the call.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() == std::io::ErrorKind::WouldBlock {
+            eyre::bail!(
+                "preimage store {} is already open in another process",
+                directory.display()
+            );
+        }
+        return Err(err.into());
+    }
+    Ok(file)
+}
+
+/// Opens the lock file of the store in the given directory. Directories are not
+/// locked on platforms without `flock`.
+#[cfg(not(unix))]
+fn lock(directory: &Path) -> Result<File> {
+    Ok(OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(directory.join(LOCK_FILE))?)
+}
+
+/// Writes a single log record for the given key and value.
+fn write_record(writer: &mut impl Write, key: &PreimageKey, value: &[u8]) -> Result<()> {
+    let len = (value.len() as u32).to_be_bytes();
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(key);
+    hasher.update(&len);
+    hasher.update(value);
+    writer.write_all(key)?;
+    writer.write_all(&len)?;
+    writer.write_all(value)?;
+    writer.write_all(&hasher.finalize().to_be_bytes())?;
+    Ok(())
+}
+
+/// Replays the records of the log starting at the given offset into the index,
+/// returning the end of the last complete and valid record.
+fn replay(log: &[u8], mut offset: u64, index: &mut HashMap<PreimageKey, Location>) -> u64 {
+    loop {
+        let start = offset as usize;
+        let Some(header) = log.get(start..start + RECORD_HEADER_LEN as usize) else {
+            return offset;
+        };
+        let key: PreimageKey = header[..32].try_into().expect("slice is 32 bytes");
+        let len = u32::from_be_bytes(header[32..].try_into().expect("slice is 4 bytes"));
+        let value_start = start + RECORD_HEADER_LEN as usize;
+        let value_end = value_start + len as usize;
+        let Some(checksum) = log.get(value_end..value_end + RECORD_CHECKSUM_LEN as usize) else {
+            return offset;
+        };
+        if crc32fast::hash(&log[start..value_end]).to_be_bytes() != checksum {
+            return offset;
+        }
+        index.insert(
+            key,
+            Location {
+                offset: value_start as u64,
+                len,
+            },
+        );
+        offset = (value_end + RECORD_CHECKSUM_LEN as usize) as u64;
+    }
+}
+
+/// Reads the index file of the given log generation,
+/// returning the index and the log offset it covers.
+fn read_index(
+    path: &Path,
+    log_len: u64,
+    generation: u64,
+) -> Result<(HashMap<PreimageKey, Location>, u64)> {
+    let mut bytes = vec![];
+    File::open(path)?.read_to_end(&mut bytes)?;
+    if bytes.get(..8) != Some(&INDEX_MAGIC[..]) || bytes.len() < INDEX_HEADER_LEN {
+        eyre::bail!("invalid index header");
+    }
+    let index_generation = u64::from_be_bytes(bytes[8..16].try_into()?);
+    if index_generation != generation {
+        eyre::bail!(
+            "index is for log generation {} but the log is generation {}",
+            index_generation,
+            generation
+        );
+    }
+    let covered = u64::from_be_bytes(bytes[16..24].try_into()?);
+    if covered > log_len {
+        eyre::bail!("index covers {} bytes but the log has {}", covered, log_len);
+    }
+    let entries = &bytes[INDEX_HEADER_LEN..];
+    if entries.len() % INDEX_ENTRY_LEN != 0 {
+        eyre::bail!("truncated index entries");
+    }
+    let index = entries
+        .chunks_exact(INDEX_ENTRY_LEN)
+        .map(|entry| {
+            let key: PreimageKey = entry[..32].try_into().expect("slice is 32 bytes");
+            let offset = u64::from_be_bytes(entry[32..40].try_into().expect("slice is 8 bytes"));
+            let len = u32::from_be_bytes(entry[40..].try_into().expect("slice is 4 bytes"));
+            (key, Location { offset, len })
+        })
+        .collect();
+    Ok((index, covered))
+}
+
+/// Atomically rewrites the index file for a log of the given generation and length.
+fn write_index(
+    directory: &Path,
+    index: &HashMap<PreimageKey, Location>,
+    generation: u64,
+    covered: u64,
+) -> Result<()> {
+    let mut writer = BufWriter::new(NamedTempFile::new_in(directory)?);
+    writer.write_all(&INDEX_MAGIC)?;
+    writer.write_all(&generation.to_be_bytes())?;
+    writer.write_all(&covered.to_be_bytes())?;
+    for (key, location) in index {
+        writer.write_all(key)?;
+        writer.write_all(&location.offset.to_be_bytes())?;
+        writer.write_all(&location.len.to_be_bytes())?;
+    }
+    let tmp = writer.into_inner().map_err(|e| e.into_error())?;
+    tmp.as_file().sync_all()?;
+    tmp.persist(directory.join(INDEX_FILE))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Closes the store without syncing it, releasing its lock like the exit of
+    /// a crashed process would.
+    fn crash(store: LogKeyValueStore) {
+        let mut store = std::mem::ManuallyDrop::new(store);
+        store._lock = tempfile::tempfile().unwrap();
+    }
+
+    #[test]
+    fn test_log_store_get_put() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        assert!(store.is_empty());
+        store.put([1; 32], vec![1, 2, 3]).unwrap();
+        store.put([2; 32], vec![4; 100]).unwrap();
+        assert_eq!(store.get([1; 32]).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(store.get([2; 32]).unwrap(), Some(vec![4; 100]));
+        assert_eq!(store.get([3; 32]).unwrap(), None);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn test_log_store_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let store = LogKeyValueStore::open(dir.path()).unwrap();
+            store.put([1; 32], vec![1, 2, 3]).unwrap();
+            store.sync().unwrap();
+            // Records appended after the last index write are replayed on open.
+            store.put([2; 32], vec![4, 5]).unwrap();
+            crash(store);
+        }
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        assert_eq!(store.get([1; 32]).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(store.get([2; 32]).unwrap(), Some(vec![4, 5]));
+    }
+
+    #[test]
+    fn test_log_store_truncates_partial_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let valid_len = {
+            let store = LogKeyValueStore::open(dir.path()).unwrap();
+            store.put([1; 32], vec![1, 2, 3]).unwrap();
+            store.sync().unwrap();
+            fs::metadata(dir.path().join(LOG_FILE)).unwrap().len()
+        };
+
+        // Simulate a crash halfway through appending a record.
+        let mut partial = vec![];
+        write_record(&mut partial, &[2; 32], &[9; 64]).unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(LOG_FILE))
+            .unwrap();
+        file.write_all(&partial[..50]).unwrap();
+        drop(file);
+
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        assert_eq!(store.get([1; 32]).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(store.get([2; 32]).unwrap(), None);
+        assert_eq!(
+            fs::metadata(dir.path().join(LOG_FILE)).unwrap().len(),
+            valid_len
+        );
+        store.put([2; 32], vec![9; 64]).unwrap();
+        assert_eq!(store.get([2; 32]).unwrap(), Some(vec![9; 64]));
+    }
+
+    #[test]
+    fn test_log_store_rebuilds_corrupt_index() {
+        let dir = tempfile::tempdir().unwrap();
+        LogKeyValueStore::open(dir.path())
+            .unwrap()
+            .put([1; 32], vec![1])
+            .unwrap();
+        fs::write(dir.path().join(INDEX_FILE), b"garbage").unwrap();
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        assert_eq!(store.get([1; 32]).unwrap(), Some(vec![1]));
+    }
+
+    #[test]
+    fn test_log_store_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        store.put([1; 32], vec![1; 100]).unwrap();
+        store.put([1; 32], vec![2; 100]).unwrap();
+        store.put([2; 32], vec![3; 10]).unwrap();
+        // Identical rewrites are not appended.
+        store.put([2; 32], vec![3; 10]).unwrap();
+
+        let stats = store.compact().unwrap();
+        assert_eq!(stats.records, 2);
+        assert!(stats.bytes_after < stats.bytes_before);
+        assert_eq!(store.get([1; 32]).unwrap(), Some(vec![2; 100]));
+        assert_eq!(store.get([2; 32]).unwrap(), Some(vec![3; 10]));
+
+        store.put([3; 32], vec![4]).unwrap();
+        drop(store);
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.get([1; 32]).unwrap(), Some(vec![2; 100]));
+        assert_eq!(store.get([3; 32]).unwrap(), Some(vec![4]));
+    }
+
+    #[test]
+    fn test_log_store_ignores_index_of_previous_generation() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        store.put([1; 32], vec![1; 100]).unwrap();
+        store.put([1; 32], vec![2; 100]).unwrap();
+        store.sync().unwrap();
+        let stale_index = fs::read(dir.path().join(INDEX_FILE)).unwrap();
+        store.compact().unwrap();
+        drop(store);
+
+        // Simulate a crash between replacing the log and rewriting the index.
+        fs::write(dir.path().join(INDEX_FILE), stale_index).unwrap();
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        assert_eq!(store.get([1; 32]).unwrap(), Some(vec![2; 100]));
+    }
+
+    #[test]
+    fn test_log_store_open_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        assert!(LogKeyValueStore::open_existing(&missing).is_err());
+        assert!(!missing.exists());
+        assert!(LogKeyValueStore::open_existing(dir.path()).is_err());
+        assert!(!dir.path().join(LOG_FILE).exists());
+
+        LogKeyValueStore::open(dir.path())
+            .unwrap()
+            .put([1; 32], vec![1])
+            .unwrap();
+        let store = LogKeyValueStore::open_existing(dir.path()).unwrap();
+        assert_eq!(store.get([1; 32]).unwrap(), Some(vec![1]));
+    }
+
+    #[test]
+    fn test_log_store_is_locked_while_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        store.put([1; 32], vec![1]).unwrap();
+        let err = LogKeyValueStore::open(dir.path()).unwrap_err();
+        assert!(err.to_string().contains("already open"));
+
+        // The lock outlives compaction, which replaces the log file.
+        store.compact().unwrap();
+        assert!(LogKeyValueStore::open(dir.path()).is_err());
+
+        drop(store);
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        assert_eq!(store.get([1; 32]).unwrap(), Some(vec![1]));
+    }
+
+    #[test]
+    fn test_log_store_get_during_compaction() {
+        // Each key's value is its byte repeated, in one of two lengths.
+        let value = |key: u8, round: u8| vec![key; 64 + key as usize + 32 * (round % 2) as usize];
+        let dir = tempfile::tempdir().unwrap();
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        for key in 0..32u8 {
+            store.put([key; 32], value(key, 0)).unwrap();
+        }
+        let done = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                        for key in 0..32u8 {
+                            let found = store.get([key; 32]).unwrap().unwrap();
+                            assert!(found == value(key, 0) || found == value(key, 1));
+                        }
+                    }
+                });
+            }
+            // Overwriting a key moves its record to the end of the log, so the
+            // records after it move on each compaction.
+            for round in 0..100u8 {
+                store
+                    .put([round % 32; 32], value(round % 32, round / 32 + 1))
+                    .unwrap();
+                store.compact().unwrap();
+            }
+            done.store(true, std::sync::atomic::Ordering::Relaxed);
+        });
+    }
+
+    #[test]
+    fn test_log_store_truncates_partial_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        store.put([1; 32], vec![1; 100]).unwrap();
+
+        let mut record = Vec::new();
+        write_record(&mut record, &[2; 32], &[2; 100]).unwrap();
+        let result = store
+            .appender
+            .lock()
+            .unwrap()
+            .append(&record, |mut file, record| {
+                file.write_all(&record[..50])?;
+                Err(std::io::Error::new(std::io::ErrorKind::Other, "disk full"))
+            });
+        assert!(result.is_err());
+
+        store.put([3; 32], vec![3; 100]).unwrap();
+        assert_eq!(store.get([1; 32]).unwrap(), Some(vec![1; 100]));
+        assert_eq!(store.get([3; 32]).unwrap(), Some(vec![3; 100]));
+        assert_eq!(
+            fs::metadata(dir.path().join(LOG_FILE)).unwrap().len(),
+            store.appender.lock().unwrap().len
+        );
+        drop(store);
+
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        assert_eq!(store.get([2; 32]).unwrap(), None);
+        assert_eq!(store.get([3; 32]).unwrap(), Some(vec![3; 100]));
+    }
+
+    #[test]
+    fn test_log_store_remaps_for_new_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LogKeyValueStore::open(dir.path()).unwrap();
+        for i in 0..64u8 {
+            store.put([i; 32], vec![i; 512]).unwrap();
+            assert_eq!(store.get([i; 32]).unwrap(), Some(vec![i; 512]));
+        }
+        assert_eq!(
+            fs::metadata(dir.path().join(LOG_FILE)).unwrap().len(),
+            store.appender.lock().unwrap().len
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use eyre::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::RwLock;
+use tempfile::NamedTempFile;
+
+use palmtop_primitives::{Preimage, PreimageKey};
+
+use crate::KeyValueStore;
+
+/// ## MemoryKeyValueStore
+///
+/// The MemoryKeyValueStore keeps preimages in a [HashMap], for tests and short runs.
+///
+/// Its contents can be exported to a snapshot file and loaded back. Snapshots are
+/// JSON objects mapping each 0x-prefixed hex key to its 0x-prefixed hex preimage,
+/// sorted by key so the same contents always produce the same file.
+#[derive(Debug, Default)]
+pub struct MemoryKeyValueStore {
+    preimages: RwLock<HashMap<PreimageKey, Preimage>>,
+}
+
+impl MemoryKeyValueStore {
+    /// Creates a new, empty [MemoryKeyValueStore].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of preimages in the store.
+    pub fn len(&self) -> usize {
+        self.preimages.read().expect("store lock poisoned").len()
+    }
+
+    /// Returns true if the store holds no preimages.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns every key in the store.
+    pub fn keys(&self) -> Vec<PreimageKey> {
+        let preimages = self.preimages.read().expect("store lock poisoned");
+        preimages.keys().copied().collect()
+    }
+
+    /// Writes a snapshot of the store to the given path,
+    /// atomically replacing any existing file.
+    pub fn export_snapshot(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let snapshot = {
+            let preimages = self.preimages.read().expect("store lock poisoned");
+            preimages
+                .iter()
+                .map(|(key, value)| (to_hex(key), to_hex(value)))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut file = NamedTempFile::new_in(directory)?;
+        serde_json::to_writer_pretty(&mut file, &snapshot)?;
+        file.write_all(b"\n")?;
+        file.as_file().sync_all()?;
+        file.persist(path)?;
+        tracing::info!(
+            target: "palmtop::kv",
+            "Exported {} preimages to {}",
+            snapshot.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Loads a store from a snapshot written by [MemoryKeyValueStore::export_snapshot].
+    pub fn import_snapshot(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let snapshot: BTreeMap<String, String> = serde_json::from_slice(&fs::read(path)?)?;
+        let preimages = snapshot
+            .into_iter()
+            .map(|(key, value)| {
+                let key: PreimageKey = from_hex(&key)?
+                    .try_into()
+                    .map_err(|_| eyre::eyre!("snapshot key {} is not 32 bytes", key))?;
+                Ok((key, from_hex(&value)?))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        tracing::info!(
+            target: "palmtop::kv",
+            "Imported {} preimages from {}",
+            preimages.len(),
+            path.display()
+        );
+        Ok(Self {
+            preimages: RwLock::new(preimages),
+        })
+    }
+}
+
+impl FromIterator<(PreimageKey, Preimage)> for MemoryKeyValueStore {
+    fn from_iter<I: IntoIterator<Item = (PreimageKey, Preimage)>>(iter: I) -> Self {
+        Self {
+            preimages: RwLock::new(iter.into_iter().collect()),
+        }
+    }
+}
+
+impl KeyValueStore for MemoryKeyValueStore {
+    fn get(&self, key: PreimageKey) -> Result<Option<Preimage>> {
+        let preimages = self.preimages.read().expect("store lock poisoned");
+        Ok(preimages.get(&key).cloned())
+    }
+
+    fn put(&self, key: PreimageKey, value: Preimage) -> Result<()> {
+        let mut preimages = self.preimages.write().expect("store lock poisoned");
+        preimages.insert(key, value);
+        Ok(())
+    }
+}
+
+/// Encodes the given bytes as 0x-prefixed hex.
+fn to_hex(bytes: impl AsRef<[u8]>) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// Decodes 0x-prefixed hex.
+fn from_hex(value: &str) -> Result<Vec<u8>> {
+    let digits = value
+        .strip_prefix("0x")
+        .ok_or_else(|| eyre::eyre!("snapshot value {} is not 0x-prefixed", value))?;
+    Ok(hex::decode(digits)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_store_get_put() {
+        let store = MemoryKeyValueStore::new();
+        assert_eq!(store.get([1; 32]).unwrap(), None);
+        store.put([1; 32], vec![1, 2]).unwrap();
+        assert_eq!(store.get([1; 32]).unwrap(), Some(vec![1, 2]));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn test_memory_store_snapshot_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snapshot.json");
+        let store = MemoryKeyValueStore::from_iter([([2; 32], vec![3, 4]), ([1; 32], vec![])]);
+        store.export_snapshot(&path).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.find(&to_hex([1; 32])) < contents.find(&to_hex([2; 32])));
+
+        let imported = MemoryKeyValueStore::import_snapshot(&path).unwrap();
+        assert_eq!(imported.len(), 2);
+        assert_eq!(imported.get([1; 32]).unwrap(), Some(vec![]));
+        assert_eq!(imported.get([2; 32]).unwrap(), Some(vec![3, 4]));
+    }
+
+    #[test]
+    fn test_memory_store_rejects_invalid_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snapshot.json");
+        fs::write(&path, r#"{"0x0101": "0x02"}"#).unwrap();
+        assert!(MemoryKeyValueStore::import_snapshot(&path).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use eyre::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use palmtop_primitives::{Preimage, PreimageKey};
+
+use crate::KeyValueStore;
+
+/// ## RecordingKeyValueStore
+///
+/// The RecordingKeyValueStore wraps another store and records every key that is
+/// read from it and found, along with the size of its preimage. Serving a run from
+/// it yields exactly the preimages the run needed.
+#[derive(Debug)]
+pub struct RecordingKeyValueStore {
+    inner: Arc<dyn KeyValueStore>,
+    reads: Mutex<BTreeMap<PreimageKey, usize>>,
+}
+
+impl RecordingKeyValueStore {
+    /// Creates a new [RecordingKeyValueStore] recording the reads of the given store.
+    pub fn new(inner: Arc<dyn KeyValueStore>) -> Self {
+        Self {
+            inner,
+            reads: Mutex::default(),
+        }
+    }
+
+    /// Returns a report of the keys read so far.
+    pub fn report(&self) -> ReadReport {
+        let reads = self.reads.lock().expect("reads lock poisoned");
+        ReadReport {
+            count: reads.len(),
+            bytes: reads.values().sum(),
+            keys: reads.keys().copied().collect(),
+        }
+    }
+}
+
+impl KeyValueStore for RecordingKeyValueStore {
+    fn get(&self, key: PreimageKey) -> Result<Option<Preimage>> {
+        let value = self.inner.get(key)?;
+        if let Some(value) = &value {
+            let mut reads = self.reads.lock().expect("reads lock poisoned");
+            reads.insert(key, value.len());
+        }
+        Ok(value)
+    }
+
+    fn put(&self, key: PreimageKey, value: Preimage) -> Result<()> {
+        self.inner.put(key, value)
+    }
+}
+
+/// ## ReadReport
+///
+/// The distinct keys read from a [RecordingKeyValueStore], with their count and
+/// the total size of their preimages.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadReport {
+    /// The number of distinct keys read.
+    pub count: usize,
+    /// The total size of the preimages read, counting each key once.
+    pub bytes: usize,
+    /// The keys read, in ascending order.
+    pub keys: Vec<PreimageKey>,
+}
+
+impl ReadReport {
+    /// Returns the report as a JSON object, with 0x-prefixed hex keys.
+    pub fn to_json(&self) -> serde_json::Value {
+        let keys = self
+            .keys
+            .iter()
+            .map(|key| format!("0x{}", hex::encode(key)))
+            .collect::<Vec<_>>();
+        serde_json::json!({
+            "count": self.count,
+            "bytes": self.bytes,
+            "keys": keys,
+        })
+    }
+
+    /// Writes the report as JSON to the given path.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut json = serde_json::to_vec_pretty(&self.to_json())?;
+        json.push(b'\n');
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryKeyValueStore;
+    use palmtop_primitives::PreimageKeyType;
+
+    #[test]
+    fn test_recording_store_reports_reads() {
+        let first = PreimageKeyType::Keccak256.key([1; 32]);
+        let second = PreimageKeyType::Keccak256.key([2; 32]);
+        let unread = PreimageKeyType::Keccak256.key([3; 32]);
+        let missing = PreimageKeyType::Keccak256.key([4; 32]);
+        let inner = Arc::new(MemoryKeyValueStore::new());
+        let store = RecordingKeyValueStore::new(inner.clone());
+        store.put(second, vec![0; 5]).unwrap();
+        store.put(first, vec![0; 3]).unwrap();
+        store.put(unread, vec![0; 7]).unwrap();
+
+        store.get(second).unwrap();
+        store.get(first).unwrap();
+        store.get(second).unwrap();
+        assert_eq!(store.get(missing).unwrap(), None);
+
+        let report = store.report();
+        assert_eq!(report.count, 2);
+        assert_eq!(report.bytes, 8);
+        assert_eq!(report.keys, vec![first, second]);
+        assert_eq!(inner.len(), 3);
+        assert_eq!(
+            report.to_json()["keys"][0],
+            format!("0x{}", hex::encode(first))
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use eyre::Result;
+use std::sync::Arc;
+
+use palmtop_primitives::{Preimage, PreimageKey, PreimageKeyType};
+
+use crate::KeyValueStore;
+
+/// ## SplitKeyValueStore
+///
+/// The SplitKeyValueStore routes [PreimageKeyType::Local] keys to a store owned by a
+/// single run and every other key to a store that may be shared between runs.
+///
+/// Local keys, like the boot info, only have meaning within the run that defined
+/// them, while keccak256, sha256 and other global keys identify the same preimage
+/// in every run. Keeping them apart lets a shared datadir cache global preimages
+/// without one run's local keys leaking into another.
+#[derive(Debug, Clone)]
+pub struct SplitKeyValueStore {
+    local: Arc<dyn KeyValueStore>,
+    global: Arc<dyn KeyValueStore>,
+}
+
+impl SplitKeyValueStore {
+    /// Creates a new [SplitKeyValueStore] from the per-run local store
+    /// and the shared global store.
+    pub fn new(local: Arc<dyn KeyValueStore>, global: Arc<dyn KeyValueStore>) -> Self {
+        Self { local, global }
+    }
+
+    /// Returns the per-run local store.
+    pub fn local(&self) -> &Arc<dyn KeyValueStore> {
+        &self.local
+    }
+
+    /// Returns the shared global store.
+    pub fn global(&self) -> &Arc<dyn KeyValueStore> {
+        &self.global
+    }
+
+    /// Returns the store responsible for the given key.
+    fn route(&self, key: &PreimageKey) -> &dyn KeyValueStore {
+        match PreimageKeyType::of(key) {
+            Ok(PreimageKeyType::Local) => self.local.as_ref(),
+            _ => self.global.as_ref(),
+        }
+    }
+}
+
+impl KeyValueStore for SplitKeyValueStore {
+    fn get(&self, key: PreimageKey) -> Result<Option<Preimage>> {
+        self.route(&key).get(key)
+    }
+
+    fn put(&self, key: PreimageKey, value: Preimage) -> Result<()> {
+        self.route(&key).put(key, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryKeyValueStore;
+    use palmtop_primitives::local_key;
+
+    #[test]
+    fn test_split_store_routes_by_key_type() {
+        let local = Arc::new(MemoryKeyValueStore::new());
+        let global = Arc::new(MemoryKeyValueStore::new());
+        let store = SplitKeyValueStore::new(local.clone(), global.clone());
+
+        let keccak_key = PreimageKeyType::Keccak256.key([7; 32]);
+        store.put(local_key(1), vec![1]).unwrap();
+        store.put(keccak_key, vec![2]).unwrap();
+
+        assert_eq!(local.keys(), vec![local_key(1)]);
+        assert_eq!(global.keys(), vec![keccak_key]);
+        assert_eq!(store.get(local_key(1)).unwrap(), Some(vec![1]));
+        assert_eq!(store.get(keccak_key).unwrap(), Some(vec![2]));
+    }
+
+    #[test]
+    fn test_split_store_does_not_share_local_keys() {
+        let global = Arc::new(MemoryKeyValueStore::new());
+        let first = SplitKeyValueStore::new(Arc::new(MemoryKeyValueStore::new()), global.clone());
+        let second = SplitKeyValueStore::new(Arc::new(MemoryKeyValueStore::new()), global);
+
+        let keccak_key = PreimageKeyType::Keccak256.key([7; 32]);
+        first.put(local_key(1), vec![1]).unwrap();
+        first.put(keccak_key, vec![2]).unwrap();
+
+        assert_eq!(second.get(local_key(1)).unwrap(), None);
+        assert_eq!(second.get(keccak_key).unwrap(), Some(vec![2]));
+    }
+}
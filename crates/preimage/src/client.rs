@@ -15,6 +15,12 @@ pub trait OracleClient {
     fn get(&mut self, key: PreimageKey) -> Result<Preimage>;
 }
 
+impl<T: OracleClient + ?Sized> OracleClient for &mut T {
+    fn get(&mut self, key: PreimageKey) -> Result<Preimage> {
+        (**self).get(key)
+    }
+}
+
 /// Creates a new OracleClientImpl using a file for reading and writing.
 pub fn new_file_client(
     read_filepath: &PathBuf,
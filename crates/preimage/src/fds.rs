@@ -0,0 +1,42 @@
+use std::fs::File;
+use std::os::fd::{FromRawFd, RawFd};
+
+/// The file descriptor the client reads hint acknowledgements from.
+pub const HINT_CLIENT_READ_FD: RawFd = 3;
+
+/// The file descriptor the client writes hints to.
+pub const HINT_CLIENT_WRITE_FD: RawFd = 4;
+
+/// The file descriptor the client reads preimages from.
+pub const PREIMAGE_CLIENT_READ_FD: RawFd = 5;
+
+/// The file descriptor the client writes preimage keys to.
+pub const PREIMAGE_CLIENT_WRITE_FD: RawFd = 6;
+
+/// Takes ownership of the inherited hint channel file descriptors,
+/// returning the reader and writer of the channel.
+///
+/// # Safety
+///
+/// The hint file descriptors must be open and must not be owned by anything else
+/// in the process, so this must be called at most once.
+pub unsafe fn hint_channel() -> (File, File) {
+    (
+        File::from_raw_fd(HINT_CLIENT_READ_FD),
+        File::from_raw_fd(HINT_CLIENT_WRITE_FD),
+    )
+}
+
+/// Takes ownership of the inherited preimage channel file descriptors,
+/// returning the reader and writer of the channel.
+///
+/// # Safety
+///
+/// The preimage file descriptors must be open and must not be owned by anything
+/// else in the process, so this must be called at most once.
+pub unsafe fn preimage_channel() -> (File, File) {
+    (
+        File::from_raw_fd(PREIMAGE_CLIENT_READ_FD),
+        File::from_raw_fd(PREIMAGE_CLIENT_WRITE_FD),
+    )
+}
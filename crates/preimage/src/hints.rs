@@ -1,14 +1,12 @@
 use byteorder::{BigEndian, WriteBytesExt};
 use eyre::Result;
-use std::fs::File;
-use std::fs::OpenOptions;
-use std::io::{BufReader, BufWriter, Read, Write};
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use tracing::instrument;
 
 use crate::inner::ReadWriter;
 
-use palmtop_primitives::{Hint, Hinter};
+use palmtop_primitives::{Hint, HintBatch, Hinter};
 
 /// ## HintWriter
 ///
@@ -67,23 +65,177 @@ impl HintReader {
     }
 }
 
-type HintHandler = fn(hint: String) -> Result<()>;
+/// The largest number of threads routing the hints of a batch.
+pub const MAX_HINT_WORKERS: usize = 16;
+
+/// HintHandler prepares the preimages requested by a hint.
+pub type HintHandler = dyn Fn(String) -> Result<()> + Send + Sync;
 
 impl HintReader {
     /// Reads the next hint from the reader and passes it to the router.
+    ///
+    /// If the hint is a batched hint frame, each hint of the batch is routed
+    /// concurrently and a single acknowledgement is written once all of them
+    /// have been processed.
     #[instrument(
         name = "hint_reader",
         skip(self, router),
         fields(server = "hint_reader")
     )]
-    pub fn next_hint(&mut self, router: HintHandler) -> Result<()> {
+    pub fn next_hint(&mut self, router: &HintHandler) -> Result<()> {
         let length = self.inner.read_length_prefix()?;
-        let mut payload = vec![0u8; length as usize];
+        let mut payload = vec![0u8; length];
         self.inner.reader().read_exact(&mut payload)?;
         let hint = String::from_utf8(payload)?;
-        router(hint)?;
+        match HintBatch::parse(&hint) {
+            Some(hints) => Self::route_batch(hints, router)?,
+            None => router(hint)?,
+        }
         self.inner.writer().write_all(&[0])?;
         self.inner.writer().flush()?;
         Ok(())
     }
+
+    /// Routes the hints of a batch concurrently on at most [MAX_HINT_WORKERS]
+    /// threads, failing if any hint fails.
+    fn route_batch(hints: Vec<&str>, router: &HintHandler) -> Result<()> {
+        let count = hints.len();
+        let next = AtomicUsize::new(0);
+        let workers = count.min(MAX_HINT_WORKERS);
+        let failures = std::thread::scope(|scope| {
+            let handles = (0..workers)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut failures = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(hint) = hints.get(index) else {
+                                return failures;
+                            };
+                            if let Err(err) = router(hint.to_string()) {
+                                failures.push(err);
+                            }
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| match handle.join() {
+                    Ok(failures) => failures,
+                    Err(_) => vec![eyre::eyre!("hint handler panicked")],
+                })
+                .collect::<Vec<_>>()
+        });
+        tracing::debug!(target: "palmtop::hints", "Routed batch of {} hints", count);
+        match failures.into_iter().next() {
+            Some(err) => Err(err.wrap_err(format!("failed to route hint batch of {count} hints"))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inner::FileReadWriter;
+    use std::io::Cursor;
+    use std::sync::Mutex;
+    use std::thread::ThreadId;
+
+    #[test]
+    fn test_hint_batch_single_write() {
+        let mut batch = HintBatch::new();
+        batch
+            .push("l1-block-header 0x01")
+            .unwrap()
+            .push("l1-transactions 0x01")
+            .unwrap();
+        let mut hinter = HintWriter::new(Cursor::new(vec![0u8]), vec![]);
+        hinter.hint_batch(batch).expect("Should not error");
+
+        let frame = "batch\nl1-block-header 0x01\nl1-transactions 0x01";
+        let mut expected = (frame.len() as u32).to_be_bytes().to_vec();
+        expected.extend_from_slice(frame.as_bytes());
+        assert_eq!(hinter.writer, expected);
+        assert_eq!(hinter.reader.position(), 1);
+    }
+
+    #[test]
+    fn test_empty_hint_batch_is_not_sent() {
+        let mut hinter = HintWriter::new(Cursor::new(vec![]), vec![]);
+        hinter
+            .hint_batch(HintBatch::new())
+            .expect("Should not error");
+        assert!(hinter.writer.is_empty());
+    }
+
+    #[test]
+    fn test_hint_reader_routes_batch() {
+        static ROUTED: AtomicUsize = AtomicUsize::new(0);
+        let mut batch = HintBatch::new();
+        for hint in ["a 0x01", "b 0x02", "c 0x03"] {
+            batch.push(hint).unwrap();
+        }
+        let mut hinter = HintWriter::new(Cursor::new(vec![0u8]), vec![]);
+        hinter.hint_batch(batch).expect("Should not error");
+
+        let inner = FileReadWriter::new(Box::new(Cursor::new(hinter.writer)), Box::new(vec![]));
+        let mut reader = HintReader::new(Box::new(inner));
+        reader
+            .next_hint(&|_| {
+                ROUTED.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+            .expect("Should not error");
+        assert_eq!(ROUTED.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_hint_reader_batch_failure() {
+        let mut batch = HintBatch::new();
+        batch.push("ok 0x01").unwrap().push("bad 0x02").unwrap();
+        let mut hinter = HintWriter::new(Cursor::new(vec![0u8]), vec![]);
+        hinter.hint_batch(batch).expect("Should not error");
+
+        let inner = FileReadWriter::new(Box::new(Cursor::new(hinter.writer)), Box::new(vec![]));
+        let mut reader = HintReader::new(Box::new(inner));
+        let res = reader.next_hint(&|hint| match hint.starts_with("bad") {
+            true => Err(eyre::eyre!("unknown hint")),
+            false => Ok(()),
+        });
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_hint_batch_rejects_newlines() {
+        let mut batch = HintBatch::new();
+        assert!(batch.push("a 0x01\nb 0x02").is_err());
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn test_hint_reader_bounds_batch_threads() {
+        static THREADS: Mutex<Vec<ThreadId>> = Mutex::new(Vec::new());
+        let mut batch = HintBatch::new();
+        for i in 0..1000 {
+            batch.push(format!("a 0x{i:04x}")).unwrap();
+        }
+        let mut hinter = HintWriter::new(Cursor::new(vec![0u8]), vec![]);
+        hinter.hint_batch(batch).expect("Should not error");
+
+        let inner = FileReadWriter::new(Box::new(Cursor::new(hinter.writer)), Box::new(vec![]));
+        let mut reader = HintReader::new(Box::new(inner));
+        reader
+            .next_hint(&|_| {
+                THREADS.lock().unwrap().push(std::thread::current().id());
+                Ok(())
+            })
+            .expect("Should not error");
+        let mut threads = THREADS.lock().unwrap().clone();
+        assert_eq!(threads.len(), 1000);
+        threads.sort_by_key(|id| format!("{id:?}"));
+        threads.dedup();
+        assert!(threads.len() <= MAX_HINT_WORKERS);
+    }
 }
@@ -25,6 +25,13 @@ pub mod client;
 /// Hints
 pub mod hints;
 
+/// In-memory pipes.
+pub mod pipe;
+
+/// File descriptors of the oracle channels.
+#[cfg(unix)]
+pub mod fds;
+
 /// Test utilities for the preimage oracle.
 #[cfg(feature = "test-utils")]
 pub mod test_utils;
@@ -0,0 +1,110 @@
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Creates an in-memory pipe, returning its reader and writer.
+///
+/// Like an OS pipe, reads block until data is written and return end of file
+/// once the writer is dropped, so a client and host can run in one process
+/// over the same wire protocol as over file descriptors.
+pub fn pipe() -> (PipeReader, PipeWriter) {
+    let (tx, rx) = mpsc::channel();
+    (
+        PipeReader {
+            rx,
+            chunk: Vec::new(),
+            pos: 0,
+        },
+        PipeWriter { tx },
+    )
+}
+
+/// The reading end of an in-memory [pipe].
+#[derive(Debug)]
+pub struct PipeReader {
+    rx: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+/// The writing end of an in-memory [pipe].
+#[derive(Debug, Clone)]
+pub struct PipeWriter {
+    tx: Sender<Vec<u8>>,
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.pos == self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                // Every writer is gone, so no more data will arrive.
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.pos);
+        buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.tx
+            .send(buf.to_vec())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "pipe reader closed"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipe_across_threads() {
+        let (mut reader, mut writer) = pipe();
+        let handle = std::thread::spawn(move || {
+            writer.write_all(&[1, 2, 3]).unwrap();
+            writer.write_all(&[4]).unwrap();
+        });
+        let mut data = vec![];
+        reader.read_to_end(&mut data).unwrap();
+        handle.join().unwrap();
+        assert_eq!(data, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_pipe_partial_reads() {
+        let (mut reader, mut writer) = pipe();
+        writer.write_all(&[1, 2, 3, 4, 5]).unwrap();
+        let mut buf = [0; 2];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2]);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [3, 4]);
+        drop(writer);
+        assert!(reader.read_exact(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_pipe_closed_reader() {
+        let (reader, mut writer) = pipe();
+        drop(reader);
+        let err = writer.write_all(&[1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+}
@@ -113,8 +113,9 @@ pub mod test_utils {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use palmtop_primitives::{Preimage, PreimageKey};
+    use palmtop_kv::MemoryKeyValueStore;
     use std::io::Cursor;
+    use std::sync::Arc;
 
     #[test]
     fn test_length_prefix() {
@@ -131,17 +132,9 @@ mod tests {
     fn test_file_server() {
         let td = test_utils::init();
         let mut server = test_utils::create_test_server(td.path().to_owned());
-        let get_preimage = |key: PreimageKey| -> Result<Preimage> {
-            let preimage = vec![1, 2, 3, 4];
-            let preimage_key = [1; 32];
-            if key == preimage_key {
-                Ok(preimage.clone())
-            } else {
-                Err(eyre::eyre!("No preimage found"))
-            }
-        };
+        let store = MemoryKeyValueStore::from_iter([([1; 32], vec![1, 2, 3, 4])]);
         server
-            .next_preimage_request(Box::new(get_preimage))
+            .next_preimage_request(palmtop_kv::getter(Arc::new(store)))
             .expect("Should not error");
     }
 
@@ -150,17 +143,20 @@ mod tests {
         let mut wtr = vec![];
         let mut rdr = Cursor::new(vec![1; 32]);
         let mut server = OracleServerImpl::new(&mut rdr, &mut wtr);
-        let get_preimage = |key: PreimageKey| -> Result<Preimage> {
-            let preimage = vec![1, 2, 3, 4];
-            let preimage_key = [1; 32];
-            if key == preimage_key {
-                Ok(preimage.clone())
-            } else {
-                Err(eyre::eyre!("No preimage found"))
-            }
-        };
+        let store = MemoryKeyValueStore::from_iter([([1; 32], vec![1, 2, 3, 4])]);
         server
-            .next_preimage_request(Box::new(get_preimage))
+            .next_preimage_request(palmtop_kv::getter(Arc::new(store)))
             .expect("Should not error");
+        assert_eq!(wtr, [0, 0, 0, 0, 0, 0, 0, 4, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_server_missing_preimage() {
+        let mut wtr = vec![];
+        let mut server = OracleServerImpl::new(Cursor::new(vec![2; 32]), &mut wtr);
+        let store = MemoryKeyValueStore::from_iter([([1; 32], vec![1, 2, 3, 4])]);
+        assert!(server
+            .next_preimage_request(palmtop_kv::getter(Arc::new(store)))
+            .is_err());
     }
 }
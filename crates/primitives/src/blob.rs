@@ -0,0 +1,94 @@
+use alloy_primitives::{keccak256, U256};
+use std::sync::OnceLock;
+
+use crate::preimage::{PreimageKey, PreimageKeyType};
+
+/// The number of field elements in a blob.
+pub const FIELD_ELEMENTS_PER_BLOB: usize = 4096;
+
+/// The number of bytes in a blob field element.
+pub const BYTES_PER_FIELD_ELEMENT: usize = 32;
+
+/// The number of bytes in a blob.
+pub const BYTES_PER_BLOB: usize = FIELD_ELEMENTS_PER_BLOB * BYTES_PER_FIELD_ELEMENT;
+
+/// The number of bytes in a KZG commitment.
+pub const BYTES_PER_COMMITMENT: usize = 48;
+
+/// The modulus of the BLS12-381 scalar field blob field elements live in.
+pub const BLS_MODULUS: U256 = U256::from_limbs([
+    0xffffffff00000001,
+    0x53bda402fffe5bfe,
+    0x3339d80809a1d805,
+    0x73eda753299d7d48,
+]);
+
+/// The generator of the multiplicative group of the BLS12-381 scalar field.
+const PRIMITIVE_ROOT_OF_UNITY: u64 = 7;
+
+/// Returns the roots of unity a blob's polynomial is evaluated at, in the
+/// bit-reversed order of the blob's field elements.
+pub fn roots_of_unity() -> &'static [U256; FIELD_ELEMENTS_PER_BLOB] {
+    static ROOTS: OnceLock<[U256; FIELD_ELEMENTS_PER_BLOB]> = OnceLock::new();
+    ROOTS.get_or_init(|| {
+        let order = U256::from(FIELD_ELEMENTS_PER_BLOB);
+        let root = U256::from(PRIMITIVE_ROOT_OF_UNITY)
+            .pow_mod((BLS_MODULUS - U256::from(1)) / order, BLS_MODULUS);
+        let mut powers = [U256::from(1); FIELD_ELEMENTS_PER_BLOB];
+        for i in 1..FIELD_ELEMENTS_PER_BLOB {
+            powers[i] = powers[i - 1].mul_mod(root, BLS_MODULUS);
+        }
+        let bits = FIELD_ELEMENTS_PER_BLOB.trailing_zeros();
+        std::array::from_fn(|i| powers[i.reverse_bits() >> (usize::BITS - bits)])
+    })
+}
+
+/// Returns the keccak256 preimage of the blob key of a field element:
+/// the blob's commitment followed by the root of unity the element is evaluated at.
+pub fn blob_key_preimage(
+    commitment: &[u8; BYTES_PER_COMMITMENT],
+    index: usize,
+) -> [u8; BYTES_PER_COMMITMENT + 32] {
+    let mut preimage = [0; BYTES_PER_COMMITMENT + 32];
+    preimage[..BYTES_PER_COMMITMENT].copy_from_slice(commitment);
+    preimage[BYTES_PER_COMMITMENT..].copy_from_slice(&roots_of_unity()[index].to_be_bytes::<32>());
+    preimage
+}
+
+/// Returns the blob [PreimageKey] of the field element at the given index of the
+/// blob with the given commitment.
+pub fn blob_key(commitment: &[u8; BYTES_PER_COMMITMENT], index: usize) -> PreimageKey {
+    PreimageKeyType::Blob.key(keccak256(blob_key_preimage(commitment, index)).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roots_of_unity() {
+        let roots = roots_of_unity();
+        let one = U256::from(1);
+        assert_eq!(roots[0], one);
+        assert_eq!(roots[1], BLS_MODULUS - one);
+        assert_eq!(roots[2].mul_mod(roots[2], BLS_MODULUS), BLS_MODULUS - one);
+
+        // The first power of the primitive root sits at the bit-reversal of 1.
+        let root = roots[FIELD_ELEMENTS_PER_BLOB / 2];
+        let order = U256::from(FIELD_ELEMENTS_PER_BLOB);
+        assert_eq!(root.pow_mod(order, BLS_MODULUS), one);
+        assert_ne!(root.pow_mod(order / U256::from(2), BLS_MODULUS), one);
+    }
+
+    #[test]
+    fn test_blob_key() {
+        let commitment = [0xc0; BYTES_PER_COMMITMENT];
+        let key = blob_key(&commitment, 1);
+        assert_eq!(key[0], PreimageKeyType::Blob as u8);
+        assert_ne!(key, blob_key(&commitment, 2));
+
+        let preimage = blob_key_preimage(&commitment, 1);
+        assert_eq!(&preimage[..48], &commitment);
+        assert_eq!(&key[1..], &keccak256(preimage)[1..]);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::preimage::{local_key, PreimageKey};
+
+/// ## BootKey
+///
+/// The local preimage keys the host serves to bootstrap the program.
+/// Each variant's discriminant is the index of its local key.
+#[repr(u64)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BootKey {
+    /// The L1 head block hash the program derives from.
+    L1Head = 1,
+    /// The agreed upon L2 output root.
+    L2OutputRoot = 2,
+    /// The disputed L2 output root claim.
+    L2Claim = 3,
+    /// The L2 block number of the disputed claim.
+    L2ClaimBlockNumber = 4,
+    /// The L2 chain ID.
+    L2ChainId = 5,
+    /// The JSON-encoded L2 chain config.
+    L2ChainConfig = 6,
+    /// The JSON-encoded rollup config.
+    RollupConfig = 7,
+}
+
+impl BootKey {
+    /// All boot keys, ordered by local key index.
+    pub const ALL: [BootKey; 7] = [
+        Self::L1Head,
+        Self::L2OutputRoot,
+        Self::L2Claim,
+        Self::L2ClaimBlockNumber,
+        Self::L2ChainId,
+        Self::L2ChainConfig,
+        Self::RollupConfig,
+    ];
+
+    /// Returns the local [PreimageKey] of the boot key.
+    pub fn key(self) -> PreimageKey {
+        local_key(self as u64)
+    }
+
+    /// Returns the boot key for the given local [PreimageKey], if any.
+    pub fn from_key(key: &PreimageKey) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|boot_key| boot_key.key() == *key)
+    }
+}
@@ -1,4 +1,6 @@
 use eyre::Result;
+use std::fmt;
+use std::str::FromStr;
 
 /// Hint is an interface that enables any program type to function as a hint,
 /// when passed to the Hinter interface, returning a string representation
@@ -15,4 +17,253 @@ pub trait Hint {
 pub trait Hinter {
     /// Hint the pre-image oracle service with the given hint.
     fn hint(&mut self, hint: impl Hint) -> Result<()>;
+
+    /// Hint the pre-image oracle service with every hint queued in the given
+    /// [HintBatch], using a single write and a single acknowledgement.
+    /// Empty batches are not sent.
+    fn hint_batch(&mut self, batch: HintBatch) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        self.hint(batch)
+    }
+}
+
+impl<T: Hinter> Hinter for &mut T {
+    fn hint(&mut self, hint: impl Hint) -> Result<()> {
+        (**self).hint(hint)
+    }
+
+    fn hint_batch(&mut self, batch: HintBatch) -> Result<()> {
+        (**self).hint_batch(batch)
+    }
+}
+
+/// The hint type that prefixes a batched hint frame.
+pub const HINT_BATCH_TYPE: &str = "batch";
+
+/// The separator between the hints of a batched hint frame.
+const HINT_BATCH_SEPARATOR: char = '\n';
+
+/// ## HintBatch
+///
+/// HintBatch queues several hints so they can be sent to the host as one frame.
+/// The frame is the [HINT_BATCH_TYPE] followed by each queued hint on its own line,
+/// which lets the host tell it apart from a regular hint and process the hints
+/// concurrently before sending back a single acknowledgement.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HintBatch {
+    hints: Vec<String>,
+}
+
+impl HintBatch {
+    /// Creates a new, empty [HintBatch].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues the given hint in the batch.
+    ///
+    /// Fails if the hint contains the batch separator, since it would be split
+    /// into several hints by the host.
+    pub fn push(&mut self, hint: impl Hint) -> Result<&mut Self> {
+        let hint = hint.hint();
+        if hint.contains(HINT_BATCH_SEPARATOR) {
+            eyre::bail!("hint {:?} cannot be batched: it contains a newline", hint);
+        }
+        self.hints.push(hint);
+        Ok(self)
+    }
+
+    /// Returns the number of queued hints.
+    pub fn len(&self) -> usize {
+        self.hints.len()
+    }
+
+    /// Returns true if no hints are queued.
+    pub fn is_empty(&self) -> bool {
+        self.hints.is_empty()
+    }
+
+    /// Splits a received hint into the hints of a batched hint frame.
+    /// Returns [None] if the hint is not a batched hint frame.
+    pub fn parse(hint: &str) -> Option<Vec<&str>> {
+        let hints = hint
+            .strip_prefix(HINT_BATCH_TYPE)?
+            .strip_prefix(HINT_BATCH_SEPARATOR)?;
+        Some(hints.split(HINT_BATCH_SEPARATOR).collect())
+    }
+}
+
+impl Hint for HintBatch {
+    fn hint(&self) -> String {
+        let mut frame = String::from(HINT_BATCH_TYPE);
+        for hint in &self.hints {
+            frame.push(HINT_BATCH_SEPARATOR);
+            frame.push_str(hint);
+        }
+        frame
+    }
+}
+
+impl Hint for String {
+    fn hint(&self) -> String {
+        self.clone()
+    }
+}
+
+impl Hint for &str {
+    fn hint(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// ## HintType
+///
+/// The type of a [TypedHint], naming the data the host should prepare preimages for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HintType {
+    /// An L1 block header, given its block hash.
+    L1BlockHeader,
+    /// The transactions trie of an L1 block, given its block hash.
+    L1Transactions,
+    /// The receipts trie of an L1 block, given its block hash.
+    L1Receipts,
+    /// An L1 blob, given its versioned hash, index in its block and the block's timestamp.
+    L1Blob,
+    /// An L1 precompile call, given the precompile's address followed by the call input.
+    L1Precompile,
+    /// An L2 state trie node, given its hash.
+    L2StateNode,
+    /// L2 contract code, given its code hash.
+    L2Code,
+    /// An L2 block header, given its block hash.
+    L2BlockHeader,
+    /// The transactions trie of an L2 block, given its block hash.
+    L2Transactions,
+    /// An L2 output, given its output root.
+    L2Output,
+}
+
+impl HintType {
+    /// Returns the name of the hint type, as it is written in a hint.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::L1BlockHeader => "l1-block-header",
+            Self::L1Transactions => "l1-transactions",
+            Self::L1Receipts => "l1-receipts",
+            Self::L1Blob => "l1-blob",
+            Self::L1Precompile => "l1-precompile",
+            Self::L2StateNode => "l2-state-node",
+            Self::L2Code => "l2-code",
+            Self::L2BlockHeader => "l2-block-header",
+            Self::L2Transactions => "l2-transactions",
+            Self::L2Output => "l2-output",
+        }
+    }
+}
+
+impl FromStr for HintType {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "l1-block-header" => Ok(Self::L1BlockHeader),
+            "l1-transactions" => Ok(Self::L1Transactions),
+            "l1-receipts" => Ok(Self::L1Receipts),
+            "l1-blob" => Ok(Self::L1Blob),
+            "l1-precompile" => Ok(Self::L1Precompile),
+            "l2-state-node" => Ok(Self::L2StateNode),
+            "l2-code" => Ok(Self::L2Code),
+            "l2-block-header" => Ok(Self::L2BlockHeader),
+            "l2-transactions" => Ok(Self::L2Transactions),
+            "l2-output" => Ok(Self::L2Output),
+            _ => Err(eyre::eyre!("unknown hint type {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for HintType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// ## TypedHint
+///
+/// A [Hint] made of a [HintType] and its data, written as the hint type
+/// followed by a space and the 0x-prefixed hex encoding of the data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypedHint {
+    /// The type of the hint.
+    pub hint_type: HintType,
+    /// The data of the hint.
+    pub data: Vec<u8>,
+}
+
+impl TypedHint {
+    /// Creates a new [TypedHint] of the given type and data.
+    pub fn new(hint_type: HintType, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            hint_type,
+            data: data.into(),
+        }
+    }
+
+    /// Parses a [TypedHint] from its string representation.
+    pub fn parse(hint: &str) -> Result<Self> {
+        let (hint_type, data) = hint
+            .split_once(' ')
+            .ok_or_else(|| eyre::eyre!("malformed hint {}", hint))?;
+        let data = data
+            .strip_prefix("0x")
+            .ok_or_else(|| eyre::eyre!("hint data of {} is not 0x-prefixed", hint))?;
+        Ok(Self::new(hint_type.parse()?, hex::decode(data)?))
+    }
+}
+
+impl Hint for TypedHint {
+    fn hint(&self) -> String {
+        format!("{} 0x{}", self.hint_type, hex::encode(&self.data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A hinter counting the batches it is sent, rather than sending them as a
+    /// single hint.
+    #[derive(Default)]
+    struct BatchCounter {
+        hints: usize,
+        batches: usize,
+    }
+
+    impl Hinter for BatchCounter {
+        fn hint(&mut self, _hint: impl Hint) -> Result<()> {
+            self.hints += 1;
+            Ok(())
+        }
+
+        fn hint_batch(&mut self, _batch: HintBatch) -> Result<()> {
+            self.batches += 1;
+            Ok(())
+        }
+    }
+
+    fn send(mut hinter: impl Hinter, batch: HintBatch) -> Result<()> {
+        hinter.hint_batch(batch)
+    }
+
+    #[test]
+    fn test_hinter_ref_forwards_hint_batch() {
+        let mut counter = BatchCounter::default();
+        let mut batch = HintBatch::new();
+        batch
+            .push(TypedHint::new(HintType::L1BlockHeader, vec![1]))
+            .unwrap();
+        send(&mut counter, batch).unwrap();
+        assert_eq!((counter.hints, counter.batches), (0, 1));
+    }
 }
@@ -17,8 +17,23 @@
 
 /// Preimage Oracle Primitives.
 pub mod preimage;
-pub use preimage::{Preimage, PreimageGetter, PreimageKey};
+pub use preimage::{local_key, Preimage, PreimageGetter, PreimageKey, PreimageKeyType};
 
 /// Preimage Hint Primitives.
 pub mod hints;
 pub use hints::*;
+
+/// Boot Info Primitives.
+pub mod boot;
+pub use boot::BootKey;
+
+/// Blob Primitives.
+pub mod blob;
+
+/// Precompile Primitives.
+pub mod precompile;
+pub use precompile::{precompile_key, PrecompileResult};
+
+/// Rollup Config Primitives.
+pub mod rollup;
+pub use rollup::RollupConfig;
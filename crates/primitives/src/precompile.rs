@@ -0,0 +1,78 @@
+use alloy_primitives::{keccak256, Address};
+use eyre::Result;
+
+use crate::preimage::{PreimageKey, PreimageKeyType};
+
+/// ## PrecompileResult
+///
+/// The result of a precompile call the host executed natively, served as the
+/// preimage of the call's precompile key.
+///
+/// It is encoded as a status byte, 1 for success and 0 for failure, followed by
+/// the output of a successful call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrecompileResult {
+    /// The call succeeded with the given output.
+    Success(Vec<u8>),
+    /// The call failed.
+    Failure,
+}
+
+impl PrecompileResult {
+    /// Encodes the result as its preimage.
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Self::Success(output) => [&[1], output.as_slice()].concat(),
+            Self::Failure => vec![0],
+        }
+    }
+
+    /// Decodes a result from its preimage.
+    pub fn decode(preimage: &[u8]) -> Result<Self> {
+        match preimage.split_first() {
+            Some((1, output)) => Ok(Self::Success(output.to_vec())),
+            Some((0, [])) => Ok(Self::Failure),
+            _ => eyre::bail!("invalid precompile result 0x{}", hex::encode(preimage)),
+        }
+    }
+}
+
+/// Returns the keccak256 preimage of the precompile key of a call:
+/// the precompile's address followed by the call input.
+pub fn precompile_key_preimage(address: Address, input: &[u8]) -> Vec<u8> {
+    [address.as_slice(), input].concat()
+}
+
+/// Returns the precompile [PreimageKey] of a call of the precompile at the given
+/// address with the given input.
+pub fn precompile_key(address: Address, input: &[u8]) -> PreimageKey {
+    PreimageKeyType::Precompile.key(keccak256(precompile_key_preimage(address, input)).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precompile_result_roundtrip() {
+        for result in [
+            PrecompileResult::Success(vec![1, 2, 3]),
+            PrecompileResult::Success(vec![]),
+            PrecompileResult::Failure,
+        ] {
+            assert_eq!(PrecompileResult::decode(&result.encode()).unwrap(), result);
+        }
+        assert!(PrecompileResult::decode(&[]).is_err());
+        assert!(PrecompileResult::decode(&[0, 1]).is_err());
+        assert!(PrecompileResult::decode(&[2]).is_err());
+    }
+
+    #[test]
+    fn test_precompile_key() {
+        let address = Address::with_last_byte(1);
+        let key = precompile_key(address, &[1, 2]);
+        assert_eq!(key[0], PreimageKeyType::Precompile as u8);
+        assert_ne!(key, precompile_key(address, &[1, 2, 3]));
+        assert_ne!(key, precompile_key(Address::with_last_byte(2), &[1, 2]));
+    }
+}
@@ -1,4 +1,5 @@
 use eyre::Result;
+use std::fmt;
 
 /// PreimageKey is a byte array of fixed length 32.
 pub type PreimageKey = [u8; 32];
@@ -8,3 +9,76 @@ pub type Preimage = Vec<u8>;
 
 /// PreimageGetter is a function that takes a preimage key and returns a preimage.
 pub type PreimageGetter = Box<dyn Fn(PreimageKey) -> Result<Preimage> + Send + Sync>;
+
+/// ## PreimageKeyType
+///
+/// The type of a [PreimageKey], stored in the first byte of the key.
+/// The remaining 31 bytes identify the preimage within its type.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PreimageKeyType {
+    /// A key that is local to a single run of the program, like its boot info.
+    Local = 1,
+    /// A key derived from the keccak256 hash of the preimage.
+    Keccak256 = 2,
+    /// A global key with a generic, type-defined derivation.
+    GlobalGeneric = 3,
+    /// A key derived from the sha256 hash of the preimage.
+    Sha256 = 4,
+    /// A key identifying a single field element of an EIP-4844 blob.
+    Blob = 5,
+    /// A key identifying the result of a precompile call.
+    Precompile = 6,
+}
+
+impl PreimageKeyType {
+    /// Returns a [PreimageKey] of this type, replacing the first byte of the
+    /// given bytes with the type byte.
+    pub fn key(self, mut bytes: [u8; 32]) -> PreimageKey {
+        bytes[0] = self as u8;
+        bytes
+    }
+
+    /// Returns the type of the given [PreimageKey].
+    pub fn of(key: &PreimageKey) -> Result<Self> {
+        Self::try_from(key[0])
+    }
+}
+
+impl TryFrom<u8> for PreimageKeyType {
+    type Error = eyre::Report;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            1 => Ok(Self::Local),
+            2 => Ok(Self::Keccak256),
+            3 => Ok(Self::GlobalGeneric),
+            4 => Ok(Self::Sha256),
+            5 => Ok(Self::Blob),
+            6 => Ok(Self::Precompile),
+            _ => Err(eyre::eyre!("unknown preimage key type {}", value)),
+        }
+    }
+}
+
+impl fmt::Display for PreimageKeyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Local => "local",
+            Self::Keccak256 => "keccak256",
+            Self::GlobalGeneric => "global-generic",
+            Self::Sha256 => "sha256",
+            Self::Blob => "blob",
+            Self::Precompile => "precompile",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Returns the local [PreimageKey] for the given index.
+/// The index is stored big-endian in the last 8 bytes of the key.
+pub fn local_key(index: u64) -> PreimageKey {
+    let mut bytes = [0u8; 32];
+    bytes[24..].copy_from_slice(&index.to_be_bytes());
+    PreimageKeyType::Local.key(bytes)
+}
@@ -0,0 +1,272 @@
+use alloy_primitives::{Address, B256};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+/// The names of the built-in rollup config presets.
+pub const PRESETS: [&str; 4] = ["optimism", "optimism-sepolia", "base", "base-sepolia"];
+
+/// ## RollupConfig
+///
+/// The rollup config of an OP Stack chain, in the JSON format of op-node's
+/// `rollup.json`. It fixes the genesis of the chain, its derivation parameters
+/// and the activation timestamps of its hardforks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollupConfig {
+    /// The genesis of the chain.
+    pub genesis: Genesis,
+    /// The seconds between L2 blocks.
+    pub block_time: u64,
+    /// The seconds an L2 block's timestamp may be ahead of its L1 origin.
+    pub max_sequencer_drift: u64,
+    /// The number of L1 blocks a batch may be submitted in after its L1 origin.
+    pub seq_window_size: u64,
+    /// The number of L1 blocks a channel may stay open for.
+    pub channel_timeout: u64,
+    /// The L1 chain ID.
+    pub l1_chain_id: u64,
+    /// The L2 chain ID.
+    pub l2_chain_id: u64,
+    /// The activation timestamp of the Regolith hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regolith_time: Option<u64>,
+    /// The activation timestamp of the Canyon hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canyon_time: Option<u64>,
+    /// The activation timestamp of the Delta hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta_time: Option<u64>,
+    /// The activation timestamp of the Ecotone hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ecotone_time: Option<u64>,
+    /// The activation timestamp of the Fjord hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fjord_time: Option<u64>,
+    /// The activation timestamp of the Granite hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub granite_time: Option<u64>,
+    /// The activation timestamp of the Holocene hardfork.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub holocene_time: Option<u64>,
+    /// The L1 address batches are sent to.
+    pub batch_inbox_address: Address,
+    /// The L1 address of the deposit contract.
+    pub deposit_contract_address: Address,
+    /// The L1 address of the system config contract.
+    pub l1_system_config_address: Address,
+    /// The L1 address of the protocol versions contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_versions_address: Option<Address>,
+}
+
+/// The genesis of an OP Stack chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Genesis {
+    /// The L1 block the chain starts deriving from.
+    pub l1: BlockId,
+    /// The first L2 block.
+    pub l2: BlockId,
+    /// The timestamp of the first L2 block.
+    pub l2_time: u64,
+    /// The system config at genesis.
+    pub system_config: SystemConfig,
+}
+
+/// The hash and number of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockId {
+    /// The block hash.
+    pub hash: B256,
+    /// The block number.
+    pub number: u64,
+}
+
+/// The L1 system config of an OP Stack chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemConfig {
+    /// The address batches must be sent from.
+    pub batcher_addr: Address,
+    /// The L1 fee overhead.
+    pub overhead: B256,
+    /// The L1 fee scalar.
+    pub scalar: B256,
+    /// The L2 block gas limit.
+    pub gas_limit: u64,
+}
+
+impl RollupConfig {
+    /// Returns the built-in rollup config with the given name, if any.
+    pub fn preset(name: &str) -> Option<Self> {
+        let json = match name {
+            "optimism" => include_str!("../presets/optimism.json"),
+            "optimism-sepolia" => include_str!("../presets/optimism-sepolia.json"),
+            "base" => include_str!("../presets/base.json"),
+            "base-sepolia" => include_str!("../presets/base-sepolia.json"),
+            _ => return None,
+        };
+        Some(Self::from_json(json.as_bytes()).expect("built-in rollup configs are valid"))
+    }
+
+    /// Returns true if the given L2 chain is the chain of one of the [PRESETS].
+    pub fn is_preset_chain(l2_chain_id: u64) -> bool {
+        PRESETS
+            .into_iter()
+            .filter_map(Self::preset)
+            .any(|config| config.l2_chain_id == l2_chain_id)
+    }
+
+    /// Parses and validates a rollup config in op-node's JSON format.
+    pub fn from_json(json: &[u8]) -> Result<Self> {
+        let config: Self = serde_json::from_slice(json)
+            .map_err(|e| eyre::eyre!("invalid rollup config: {}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Encodes the rollup config in op-node's JSON format.
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("rollup configs serialize to JSON")
+    }
+
+    /// Checks that the rollup config is usable.
+    pub fn validate(&self) -> Result<()> {
+        if self.l1_chain_id == 0 || self.l2_chain_id == 0 {
+            eyre::bail!("rollup config chain IDs must not be zero");
+        }
+        if self.block_time == 0 {
+            eyre::bail!("rollup config block time must not be zero");
+        }
+        if self.seq_window_size == 0 || self.channel_timeout == 0 {
+            eyre::bail!("rollup config sequencing window and channel timeout must not be zero");
+        }
+        if self.genesis.l1.hash.is_zero() || self.genesis.l2.hash.is_zero() {
+            eyre::bail!("rollup config genesis block hashes must not be zero");
+        }
+        if self.batch_inbox_address.is_zero() {
+            eyre::bail!("rollup config batch inbox address must not be zero");
+        }
+        let forks = [
+            ("regolith", self.regolith_time),
+            ("canyon", self.canyon_time),
+            ("delta", self.delta_time),
+            ("ecotone", self.ecotone_time),
+            ("fjord", self.fjord_time),
+            ("granite", self.granite_time),
+            ("holocene", self.holocene_time),
+        ];
+        for pair in forks.windows(2) {
+            let ((prev, prev_time), (next, next_time)) = (pair[0], pair[1]);
+            match (prev_time, next_time) {
+                (Some(prev_time), Some(next_time)) if next_time < prev_time => {
+                    eyre::bail!("rollup config activates {} before {}", next, prev)
+                }
+                (None, Some(_)) => {
+                    eyre::bail!("rollup config activates {} without {}", next, prev)
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if Regolith is active at the given timestamp.
+    pub fn is_regolith_active(&self, timestamp: u64) -> bool {
+        is_active(self.regolith_time, timestamp)
+    }
+
+    /// Returns true if Canyon is active at the given timestamp.
+    pub fn is_canyon_active(&self, timestamp: u64) -> bool {
+        is_active(self.canyon_time, timestamp)
+    }
+
+    /// Returns true if Delta is active at the given timestamp.
+    pub fn is_delta_active(&self, timestamp: u64) -> bool {
+        is_active(self.delta_time, timestamp)
+    }
+
+    /// Returns true if Ecotone is active at the given timestamp.
+    pub fn is_ecotone_active(&self, timestamp: u64) -> bool {
+        is_active(self.ecotone_time, timestamp)
+    }
+
+    /// Returns true if Fjord is active at the given timestamp.
+    pub fn is_fjord_active(&self, timestamp: u64) -> bool {
+        is_active(self.fjord_time, timestamp)
+    }
+
+    /// Returns true if Granite is active at the given timestamp.
+    pub fn is_granite_active(&self, timestamp: u64) -> bool {
+        is_active(self.granite_time, timestamp)
+    }
+
+    /// Returns true if Holocene is active at the given timestamp.
+    pub fn is_holocene_active(&self, timestamp: u64) -> bool {
+        is_active(self.holocene_time, timestamp)
+    }
+}
+
+/// Returns true if a hardfork with the given activation time is active at the timestamp.
+fn is_active(activation: Option<u64>, timestamp: u64) -> bool {
+    activation.is_some_and(|activation| timestamp >= activation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        for (name, l2_chain_id) in PRESETS.into_iter().zip([10, 11155420, 8453, 84532]) {
+            let config = RollupConfig::preset(name).unwrap();
+            assert_eq!(config.l2_chain_id, l2_chain_id);
+            assert_eq!(RollupConfig::from_json(&config.to_json()).unwrap(), config);
+            assert!(RollupConfig::is_preset_chain(l2_chain_id));
+        }
+        assert!(!RollupConfig::is_preset_chain(1));
+        assert!(RollupConfig::preset("optimism-goerli").is_none());
+    }
+
+    #[test]
+    fn test_hardfork_activation() {
+        let config = RollupConfig::preset("optimism").unwrap();
+        assert!(config.is_regolith_active(0));
+        assert!(!config.is_ecotone_active(1710374400));
+        assert!(config.is_ecotone_active(1710374401));
+        let config = RollupConfig {
+            holocene_time: None,
+            ..config
+        };
+        assert!(!config.is_holocene_active(u64::MAX));
+    }
+
+    #[test]
+    fn test_rollup_config_validation() {
+        let config = RollupConfig::preset("base").unwrap();
+        let mut json: serde_json::Value = serde_json::from_slice(&config.to_json()).unwrap();
+        json["ecotone_time"] = 0.into();
+        assert!(RollupConfig::from_json(json.to_string().as_bytes()).is_err());
+
+        let invalid = [
+            RollupConfig {
+                l2_chain_id: 0,
+                ..config.clone()
+            },
+            RollupConfig {
+                block_time: 0,
+                ..config.clone()
+            },
+            RollupConfig {
+                canyon_time: None,
+                ..config.clone()
+            },
+            RollupConfig {
+                canyon_time: Some(u64::MAX),
+                ..config.clone()
+            },
+        ];
+        for config in invalid {
+            assert!(config.validate().is_err());
+        }
+        assert!(RollupConfig::from_json(b"{}").is_err());
+    }
+}
@@ -139,7 +139,7 @@ where
     ) {
         // Print the timestamp
         let utc: String = chrono::Utc::now().to_rfc2822();
-        let strip_len = utc.find(|c| c == '+').unwrap_or(utc.len());
+        let strip_len = utc.find('+').unwrap_or(utc.len());
         let formatted_utc = utc[..strip_len].trim_end();
         print!("[{}] ", Cyan.paint(formatted_utc));
 
@@ -169,7 +169,7 @@ where
                 .metadata()
                 .name()
                 .split(' ')
-                .last()
+                .next_back()
                 .unwrap_or_default();
 
             let relative_path = current_dir()
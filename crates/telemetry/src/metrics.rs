@@ -4,6 +4,7 @@ use prometheus_exporter::{
     prometheus::{register_int_gauge, IntGauge},
     start,
 };
+use std::net::SocketAddr;
 
 lazy_static! {
     pub static ref FINALIZED_HEAD: IntGauge =
@@ -13,7 +14,8 @@ lazy_static! {
     pub static ref SYNCED: IntGauge = register_int_gauge!("synced", "synced flag").unwrap();
 }
 
-pub fn init() -> Result<()> {
-    start("0.0.0.0:9200".parse().wrap_err("Could not parse address")?)?;
+/// Starts serving metrics on the given address.
+pub fn init(addr: SocketAddr) -> Result<()> {
+    start(addr).wrap_err("Could not start metrics server")?;
     Ok(())
 }